@@ -1,37 +1,54 @@
 use rayon::ThreadPool;
-use std::sync::{
-  mpsc::{channel, Sender},
-  Arc,
+use std::{
+  collections::HashSet,
+  panic::{self, AssertUnwindSafe},
+  sync::{
+    mpsc::{channel, Sender},
+    Arc, Mutex,
+  },
 };
 
 use crate::{
+  cache::CachedModule,
   context::CompilationContext,
-  error::{CompilationError, Result},
-  module::{module_graph::ModuleGraphEdge, ResolveKind},
+  error::{panic_message, CompilationError, Result},
+  module::{
+    module::{Module, ModuleKind},
+    module_graph::ModuleGraphEdge,
+    ResolveKind,
+  },
   plugin::{
-    AnalyzeDepsHookParams, LoadHookParams, ParseHookParams, ResolveHookParams, TransformHookParams,
+    plugin_container::Hook, AnalyzeDepsHookParams, LoadHookParams, ParseHookParams,
+    ResolveHookParams, ResolveHookResult, TransformHookParams,
   },
+  reporter::{report, LogLevel},
   Compiler,
 };
 
+/// 正在构建以及已经构建完成的模块 id，避免同一个模块被重复构建
+type VisitedModules = Arc<Mutex<HashSet<String>>>;
+
 impl Compiler {
   pub(crate) fn build(&mut self) -> Result<()> {
     self.context.plugin_container.build_start(&self.context)?;
 
     let thread_pool = Arc::new(rayon::ThreadPoolBuilder::new().build().unwrap());
     let (err_sender, err_receiver) = channel::<CompilationError>();
+    let visited: VisitedModules = Arc::new(Mutex::new(HashSet::new()));
 
     self
       .context
       .config
       .input
-      .values()
+      .iter()
       .enumerate()
-      .for_each(|(order, source)| {
+      .for_each(|(order, (name, source))| {
         Self::build_module(
           thread_pool.clone(),
           err_sender.clone(),
+          visited.clone(),
           order,
+          Some(name.clone()),
           ResolveHookParams {
             source: source.clone(),
             importer: None,
@@ -43,121 +60,740 @@ impl Compiler {
 
     drop(err_sender);
 
-    if let Ok(err) = err_receiver.recv() {
+    // 等待所有模块构建完成，收集全部错误而不是只返回第一个
+    if let Some(err) = CompilationError::from_errors(err_receiver.iter().collect()) {
+      return Err(err);
+    }
+
+    self.context.plugin_container.build_end(&self.context)
+  }
+
+  /// 增量构建：只重新构建发生变化的模块，
+  /// 它们新增的依赖会被构建，已经存在于 module_graph 中的依赖只会补上边。
+  /// 最后删除不再被任何入口引用的模块。
+  pub(crate) fn rebuild(&mut self, changed_module_ids: &HashSet<String>) -> Result<()> {
+    self.context.plugin_container.build_start(&self.context)?;
+
+    let thread_pool = Arc::new(rayon::ThreadPoolBuilder::new().build().unwrap());
+    let (err_sender, err_receiver) = channel::<CompilationError>();
+
+    // module_graph 中已有的模块都视为已构建完成
+    let module_graph = self.context.module_graph.read().unwrap();
+    let visited: VisitedModules = Arc::new(Mutex::new(module_graph.module_ids().collect()));
+    let changed_modules = changed_module_ids
+      .iter()
+      .filter_map(|id| module_graph.module(id))
+      .map(|module| {
+        // 用任意一个 importer 作为错误信息中的 importer
+        let importer = module_graph
+          .dependents(&module.id)
+          .ok()
+          .and_then(|dependents| dependents.into_iter().next())
+          .map(|(id, _)| id);
+        let resolve_result = ResolveHookResult {
+          id: module.id.clone(),
+          query: module.query.clone(),
+          external: false,
+          module_kind: None,
+        };
+
+        (resolve_result, importer)
+      })
+      .collect::<Vec<_>>();
+
+    drop(module_graph);
+
+    for (resolve_result, importer) in changed_modules {
+      let c_thread_pool = thread_pool.clone();
+      let c_err_sender = err_sender.clone();
+      let visited = visited.clone();
+      let context = self.context.clone();
+
+      spawn_build_task(
+        &thread_pool,
+        err_sender.clone(),
+        resolve_result.id.clone(),
+        move || {
+          Self::build_resolved_module(
+            c_thread_pool,
+            c_err_sender,
+            visited,
+            resolve_result,
+            importer,
+            context,
+          )
+        },
+      );
+    }
+
+    drop(err_sender);
+
+    // 等待所有模块构建完成，收集全部错误而不是只返回第一个
+    if let Some(err) = CompilationError::from_errors(err_receiver.iter().collect()) {
       return Err(err);
     }
 
+    self
+      .context
+      .module_graph
+      .write()
+      .unwrap()
+      .remove_orphan_modules();
+
     self.context.plugin_container.build_end(&self.context)
   }
 
   fn build_module(
     thread_pool: Arc<ThreadPool>,
     err_sender: Sender<CompilationError>,
+    visited: VisitedModules,
     order: usize,
+    entry_name: Option<String>,
     resolve_hook_params: ResolveHookParams,
     context: Arc<CompilationContext>,
   ) {
     let c_thread_pool = thread_pool.clone();
-    let context = context.clone();
-
-    thread_pool.spawn(move || {
-      macro_rules! call_and_catch_error {
-        ($func:ident, $($arg:expr),*) => {
-          match context.plugin_container.$func($($arg),*) {
-            Ok(result) => result,
-            Err(error) => {
-              err_sender.send(error).expect("send error to main thread failed");
-              return;
-            }
-          }
-        };
+    let c_err_sender = err_sender.clone();
+    let source = resolve_hook_params.source.clone();
+
+    spawn_build_task(
+      &thread_pool,
+      err_sender,
+      source,
+      move || match Self::resolve_module(
+        &visited,
+        order,
+        entry_name.as_deref(),
+        &resolve_hook_params,
+        &context,
+      )? {
+        Some(resolve_result) => Self::build_resolved_module(
+          c_thread_pool,
+          c_err_sender,
+          visited,
+          resolve_result,
+          resolve_hook_params.importer,
+          context,
+        ),
+        None => Ok(()),
+      },
+    );
+  }
+
+  /// resolve 模块，并把模块和边加入 module_graph。
+  /// 同一个模块只会被构建一次，重复遇到时只需要补上 importer 到它的边，此时返回 `None`。
+  /// 外部依赖不需要构建，也返回 `None`。
+  /// 入口模块的 `entry_name` 为它在 `config.input` 中的 key，其他模块为 `None`
+  fn resolve_module(
+    visited: &VisitedModules,
+    order: usize,
+    entry_name: Option<&str>,
+    resolve_hook_params: &ResolveHookParams,
+    context: &Arc<CompilationContext>,
+  ) -> Result<Option<ResolveHookResult>> {
+    // resolve
+    let resolve_result = context
+      .plugin_container
+      .resolve(resolve_hook_params, context)?
+      .ok_or_else(|| CompilationError::NoResolver {
+        src: resolve_hook_params.source.clone(),
+        kind: resolve_hook_params.kind.clone(),
+        importer: resolve_hook_params.importer.clone(),
+        plugins: context
+          .plugin_container
+          .tried_plugin_names(Hook::Resolve, resolve_hook_params),
+      })?;
+    report!(
+      context,
+      LogLevel::Debug,
+      "resolve_result: {resolve_result:#?}"
+    );
+
+    let module_id = resolve_result.id.clone();
+    let mut visited_ids = visited.lock().unwrap();
+    let mut module_graph = context.module_graph.write().unwrap();
+    let is_first_visit = visited_ids.insert(module_id.clone());
+
+    if is_first_visit {
+      // 先放入一个占位模块，使其他 importer 可以在它构建完成前连上边。
+      // 外部依赖只保留这个占位模块，不会被构建
+      let mut module = Module::new(
+        module_id.clone(),
+        ModuleKind::from_file_path(&module_id),
+        None,
+      );
+      module.external = resolve_result.external;
+      module_graph.add_module(module);
+    }
+
+    if let Some(entry_name) = entry_name {
+      // 多个入口是同一个模块时，入口名会取决于并行 resolve 的先后顺序，直接报错
+      if let Some(other) = module_graph
+        .entries
+        .get(&module_id)
+        .filter(|other| *other != entry_name)
+      {
+        let mut names = [other.as_str(), entry_name];
+        names.sort();
+
+        return Err(CompilationError::ConfigError {
+          path: "input".to_string(),
+          message: format!(
+            "`{}` and `{}` resolve to the same module `{module_id}`, keep only one of them",
+            names[0], names[1]
+          ),
+        });
       }
 
-      // resolve
-      let resolve_result = call_and_catch_error!(resolve, &resolve_hook_params, &context).unwrap();
-      println!(">>> resolve_result: {resolve_result:#?}");
+      module_graph
+        .entries
+        .insert(module_id.clone(), entry_name.to_string());
+    }
 
-      // load
-      let load_params = LoadHookParams {
-        id: resolve_result.id.clone(),
-        query: resolve_result.query.clone(),
-      };
-      let load_result = call_and_catch_error!(load, &load_params, &context).unwrap();
-      println!(">>> load_result: {load_result:#?}");
+    if matches!(resolve_hook_params.kind, ResolveKind::ScriptSrc) {
+      module_graph.entries_in_html.insert(module_id.clone());
+    }
 
-      // transform
-      let transform_params = TransformHookParams {
-        id: resolve_result.id.clone(),
-        query: resolve_result.query.clone(),
-        content: load_result.content,
-        module_kind: load_result.module_kind,
-      };
-      let transform_result = call_and_catch_error!(transform, transform_params, &context);
-      println!(">>> transform_result: {transform_result:#?}");
-
-      // parse
-      let parse_params = ParseHookParams {
+    if let Some(importer) = &resolve_hook_params.importer {
+      module_graph.add_edge(
+        importer,
+        &module_id,
+        ModuleGraphEdge {
+          kind: resolve_hook_params.kind.clone(),
+          source: resolve_hook_params.source.clone(),
+          order,
+        },
+      )?;
+    }
+
+    Ok((is_first_visit && !resolve_result.external).then_some(resolve_result))
+  }
+
+  /// 对已经 resolve 的模块执行 load -> transform -> parse -> analyze_deps，
+  /// 然后把模块放入 module_graph，并递归构建它的依赖
+  fn build_resolved_module(
+    thread_pool: Arc<ThreadPool>,
+    err_sender: Sender<CompilationError>,
+    visited: VisitedModules,
+    resolve_result: ResolveHookResult,
+    importer: Option<String>,
+    context: Arc<CompilationContext>,
+  ) -> Result<()> {
+    // 构建过程中插件通过 `emit_file` 添加的资源记录在模块上，增量编译复用模块时也会保留
+    let emitted_files_collector = context.collect_emitted_files();
+
+    // load
+    let load_params = LoadHookParams {
+      id: resolve_result.id.clone(),
+      query: resolve_result.query.clone(),
+      module_kind: resolve_result
+        .module_kind
+        .clone()
+        .unwrap_or_else(|| ModuleKind::from_file_path(&resolve_result.id)),
+    };
+    let load_result = context
+      .plugin_container
+      .load(&load_params, &context)?
+      .ok_or_else(|| CompilationError::NoLoader {
         id: resolve_result.id.clone(),
-        query: resolve_result.query.clone(),
-        content: transform_result.content,
-        module_kind: transform_result.module_kind,
-      };
-      let mut module = call_and_catch_error!(parse, &parse_params, &context).unwrap();
-
-      // analyze deps
-      let mut analyze_deps_params = AnalyzeDepsHookParams {
-        module: &mut module,
-        deps: vec![],
-      };
-      call_and_catch_error!(analyze_deps, &mut analyze_deps_params, &context);
-
-      let deps = analyze_deps_params.deps;
-      println!(">>> analyze_deps {:?} -> {:#?}", resolve_result.id, deps);
-
-      // build module_graph
-      let module_id = module.id.clone();
-      let mut module_graph = context.module_graph.write().unwrap();
+        kind: load_params.module_kind.clone(),
+        importer: importer.clone(),
+        plugins: context
+          .plugin_container
+          .tried_plugin_names(Hook::Load, &load_params),
+      })?;
+    report!(context, LogLevel::Debug, "load_result: {load_result:#?}");
 
-      module_graph.add_module(module);
+    // 命中持久化缓存时，可以跳过 transform 和 analyze_deps
+    let cache_key = context.cache.as_ref().map(|cache| {
+      cache.key(
+        &resolve_result.id,
+        &resolve_result.query,
+        &load_result.content,
+        &load_result.module_kind,
+      )
+    });
+    let cached_module = context
+      .cache
+      .as_ref()
+      .zip(cache_key.as_ref())
+      .and_then(|(cache, key)| cache.get(key));
 
-      if matches!(resolve_hook_params.kind, ResolveKind::Entry) {
-        module_graph.entries.insert(module_id.clone());
+    let (content, module_kind, source_map_chain) = match &cached_module {
+      Some(cached_module) => (
+        cached_module.content.clone(),
+        cached_module.module_kind.clone(),
+        cached_module.source_map_chain.clone(),
+      ),
+      None => {
+        // transform
+        let transform_params = TransformHookParams {
+          id: resolve_result.id.clone(),
+          query: resolve_result.query.clone(),
+          content: load_result.content,
+          module_kind: load_result.module_kind,
+        };
+        let transform_result = context
+          .plugin_container
+          .transform(transform_params, &context)?;
+        report!(
+          context,
+          LogLevel::Debug,
+          "transform_result: {transform_result:#?}"
+        );
+
+        (
+          transform_result.content,
+          transform_result.module_kind,
+          transform_result.source_map_chain,
+        )
       }
+    };
 
-      if matches!(resolve_hook_params.kind, ResolveKind::ScriptSrc) {
-        module_graph.entries_in_html.insert(module_id.clone());
+    // parse
+    let parse_params = ParseHookParams {
+      id: resolve_result.id.clone(),
+      query: resolve_result.query.clone(),
+      content,
+      module_kind,
+    };
+    let mut module = context
+      .plugin_container
+      .parse(&parse_params, &context)?
+      .ok_or_else(|| CompilationError::NoParser {
+        id: resolve_result.id.clone(),
+        module_kind: parse_params.module_kind.clone(),
+        importer,
+        plugins: context
+          .plugin_container
+          .tried_plugin_names(Hook::Parse, &parse_params),
+      })?;
+    module.query = resolve_result.query.clone();
+    module.source_map_chain = source_map_chain;
+
+    let deps = match cached_module {
+      Some(cached_module) => {
+        module.emitted_files = emitted_files_collector.finish();
+        module.emitted_files.extend(cached_module.emitted_files);
+        cached_module.deps
       }
+      None => {
+        // analyze deps
+        let mut analyze_deps_params = AnalyzeDepsHookParams {
+          module: &mut module,
+          deps: vec![],
+        };
+        context
+          .plugin_container
+          .analyze_deps(&mut analyze_deps_params, &context)?;
 
-      if let Some(importer) = resolve_hook_params.importer {
-        module_graph
-          .add_edge(
-            &importer,
-            &module_id,
-            ModuleGraphEdge {
-              kind: resolve_hook_params.kind.clone(),
-              source: resolve_hook_params.source.clone(),
-              order,
+        let deps = analyze_deps_params.deps;
+        module.emitted_files = emitted_files_collector.finish();
+
+        if let (Some(cache), Some(key)) = (&context.cache, &cache_key) {
+          cache.set(
+            key,
+            &CachedModule {
+              content: parse_params.content,
+              module_kind: parse_params.module_kind,
+              deps: deps.clone(),
+              source_map_chain: module.source_map_chain.clone(),
+              emitted_files: module.emitted_files.clone(),
             },
-          )
-          .unwrap()
+          );
+        }
+
+        deps
       }
+    };
+    report!(
+      context,
+      LogLevel::Debug,
+      "analyze_deps {:?} -> {:#?}",
+      resolve_result.id,
+      deps
+    );
 
-      drop(module_graph);
+    // 用构建好的模块替换占位模块（或者增量构建时的旧模块），
+    // 旧的依赖边会被删除，由下面重新 resolve 依赖时再加回来
+    let module_id = module.id.clone();
+    let mut module_graph = context.module_graph.write().unwrap();
 
-      // build_module recursively
-      for (order, dep) in deps.iter().enumerate() {
-        Self::build_module(
-          c_thread_pool.clone(),
-          err_sender.clone(),
-          order,
-          ResolveHookParams {
-            source: dep.source.clone(),
-            importer: Some(module_id.clone()),
-            kind: dep.resolve_kind.clone(),
-          },
-          Arc::clone(&context),
-        );
+    module_graph.add_module(module);
+    module_graph.remove_dependency_edges(&module_id)?;
+
+    drop(module_graph);
+
+    // build_module recursively
+    for (order, dep) in deps.iter().enumerate() {
+      Self::build_module(
+        thread_pool.clone(),
+        err_sender.clone(),
+        visited.clone(),
+        order,
+        None,
+        ResolveHookParams {
+          source: dep.source.clone(),
+          importer: Some(module_id.clone()),
+          kind: dep.resolve_kind.clone(),
+        },
+        Arc::clone(&context),
+      );
+    }
+
+    Ok(())
+  }
+}
+
+/// 在线程池中执行构建任务，任务返回的错误会被发送到 `err_sender`。
+/// 任务中的 panic 也会被捕获并作为错误发送，否则 rayon 会直接终止进程
+fn spawn_build_task(
+  thread_pool: &ThreadPool,
+  err_sender: Sender<CompilationError>,
+  target: String,
+  task: impl FnOnce() -> Result<()> + Send + 'static,
+) {
+  thread_pool.spawn(move || {
+    let ret = panic::catch_unwind(AssertUnwindSafe(task)).unwrap_or_else(|payload| {
+      Err(CompilationError::GenericError(format!(
+        "Build `{target}` panicked: {}",
+        panic_message(payload.as_ref())
+      )))
+    });
+
+    if let Err(error) = ret {
+      err_sender
+        .send(error)
+        .expect("send error to main thread failed");
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    sync::{Arc, Mutex},
+  };
+
+  use regex::Regex;
+
+  use crate::{
+    config::{CacheConfig, Config},
+    context::CompilationContext,
+    error::{CompilationError, Result},
+    plugin::{
+      filter::PluginFilter, plugin_container::Hook, LoadHookParams, LoadHookResult, Plugin,
+      TransformHookParams, TransformHookResult,
+    },
+    Compiler,
+  };
+
+  /// 记录每个模块被 load 的次数
+  struct PluginLoadCounter {
+    counts: Mutex<HashMap<String, usize>>,
+  }
+
+  impl Plugin for PluginLoadCounter {
+    fn name(&self) -> &str {
+      "TestPluginLoadCounter"
+    }
+
+    fn priority(&self) -> i32 {
+      0
+    }
+
+    fn load(
+      &self,
+      params: &LoadHookParams,
+      _context: &Arc<CompilationContext>,
+    ) -> Result<Option<LoadHookResult>> {
+      *self
+        .counts
+        .lock()
+        .unwrap()
+        .entry(params.id.clone())
+        .or_default() += 1;
+
+      Ok(None)
+    }
+  }
+
+  /// 记录 transform 被调用的次数
+  struct PluginTransformCounter {
+    count: Mutex<usize>,
+  }
+
+  impl Plugin for PluginTransformCounter {
+    fn name(&self) -> &str {
+      "TestPluginTransformCounter"
+    }
+
+    fn transform(
+      &self,
+      _params: &TransformHookParams,
+      _context: &Arc<CompilationContext>,
+    ) -> Result<Option<TransformHookResult>> {
+      *self.count.lock().unwrap() += 1;
+      Ok(None)
+    }
+  }
+
+  /// load 指定模块时 panic
+  struct PluginLoadPanic {
+    id: &'static str,
+    /// 通过 `filter` 排除的模块 id
+    exclude: Option<&'static str>,
+  }
+
+  impl Plugin for PluginLoadPanic {
+    fn name(&self) -> &str {
+      "TestPluginLoadPanic"
+    }
+
+    fn priority(&self) -> i32 {
+      0
+    }
+
+    fn filter(&self) -> PluginFilter {
+      PluginFilter {
+        hooks: vec![Hook::Load],
+        exclude: self
+          .exclude
+          .iter()
+          .map(|id| Regex::new(&regex::escape(id)).unwrap())
+          .collect(),
+        ..PluginFilter::default()
       }
+    }
+
+    fn load(
+      &self,
+      params: &LoadHookParams,
+      _context: &Arc<CompilationContext>,
+    ) -> Result<Option<LoadHookResult>> {
+      if params.id == self.id {
+        panic!("boom");
+      }
+
+      Ok(None)
+    }
+  }
+
+  #[test]
+  fn test_build_diamond_once() {
+    let counter = Arc::new(PluginLoadCounter {
+      counts: Mutex::new(HashMap::new()),
     });
+
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/diamond")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        ..Config::default()
+      },
+      vec![counter.clone()],
+    )
+    .unwrap();
+
+    compiler.build().unwrap();
+
+    let counts = counter.counts.lock().unwrap();
+
+    assert_eq!(counts.len(), 4);
+    assert!(counts.values().all(|count| *count == 1));
+
+    let module_graph = compiler.context.module_graph.read().unwrap();
+    let shared_dependents = module_graph
+      .dependents("./shared.js")
+      .unwrap()
+      .into_iter()
+      .map(|(id, _)| id)
+      .collect::<Vec<_>>();
+
+    assert_eq!(shared_dependents, vec!["./left.js", "./right.js"]);
+  }
+
+  #[test]
+  fn test_build_with_persistent_cache() {
+    let cache_dir = std::env::temp_dir().join(format!("toy-build-cache-{}", std::process::id()));
+    let build = || {
+      let counter = Arc::new(PluginTransformCounter {
+        count: Mutex::new(0),
+      });
+      let mut compiler = Compiler::new(
+        Config {
+          root: fs::canonicalize("../../fixtures/diamond")
+            .unwrap()
+            .to_string_lossy()
+            .to_string(),
+          input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+          cache: Some(CacheConfig {
+            dir: cache_dir.to_string_lossy().to_string(),
+          }),
+          ..Config::default()
+        },
+        vec![counter.clone()],
+      )
+      .unwrap();
+
+      compiler.build().unwrap();
+
+      let module_graph = compiler.context.module_graph.read().unwrap();
+      let mut index_deps = module_graph
+        .dependencies("./index.js")
+        .unwrap()
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+      index_deps.sort();
+
+      let count = *counter.count.lock().unwrap();
+      (count, index_deps)
+    };
+
+    let (cold_count, cold_deps) = build();
+    let (warm_count, warm_deps) = build();
+
+    assert_eq!(cold_count, 4);
+    assert_eq!(warm_count, 0);
+    assert_eq!(cold_deps, warm_deps);
+    assert_eq!(warm_deps, vec!["./left.js", "./right.js"]);
+
+    fs::remove_dir_all(cache_dir).unwrap();
+  }
+
+  #[test]
+  fn test_build_collect_all_errors() {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/broken")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+
+    let Err(CompilationError::Multiple(errors)) = compiler.build() else {
+      panic!("expect multiple errors");
+    };
+
+    let errors = errors
+      .iter()
+      .map(|err| match err {
+        CompilationError::ResolveError { src, importer, .. } => {
+          format!("resolve {src} from {}", importer.as_ref().unwrap())
+        }
+        CompilationError::ParseError { id, .. } => format!("parse {id}"),
+        err => panic!("unexpected error: {err}"),
+      })
+      .collect::<Vec<_>>();
+
+    assert_eq!(
+      errors,
+      vec![
+        "resolve ./missing from ./foo.js",
+        "resolve ./bar from ./index.js",
+        "parse ./syntax.js",
+      ]
+    );
+  }
+
+  #[test]
+  fn test_build_plugin_panic() {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/diamond")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        ..Config::default()
+      },
+      vec![Arc::new(PluginLoadPanic {
+        id: "./right.js",
+        exclude: None,
+      })],
+    )
+    .unwrap();
+
+    let Err(CompilationError::PluginPanic {
+      plugin,
+      hook,
+      module_id,
+      message,
+    }) = compiler.build()
+    else {
+      panic!("expect plugin panic error");
+    };
+
+    assert_eq!(plugin, "TestPluginLoadPanic");
+    assert_eq!(hook, "load");
+    assert_eq!(module_id.as_deref(), Some("./right.js"));
+    assert_eq!(message, "boom");
+  }
+
+  #[test]
+  fn test_build_skip_filtered_plugin() {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/diamond")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        ..Config::default()
+      },
+      vec![Arc::new(PluginLoadPanic {
+        id: "./right.js",
+        exclude: Some("./right.js"),
+      })],
+    )
+    .unwrap();
+
+    compiler.build().unwrap();
+  }
+
+  #[test]
+  fn test_build_no_loader() {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/unsupported")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+
+    let err = compiler.build().unwrap_err();
+    let CompilationError::NoLoader {
+      id,
+      importer,
+      plugins,
+      ..
+    } = &err
+    else {
+      panic!("expect no loader error, got: {err}");
+    };
+
+    assert_eq!(id, "./data.json");
+    assert_eq!(importer.as_deref(), Some("./index.js"));
+    // ToyPluginScript 只处理脚本模块，不会尝试 load `./data.json`
+    assert!(!plugins.contains(&"ToyPluginScript".to_string()));
+    assert!(err
+      .to_string()
+      .starts_with("No plugin can load `./data.json`"));
   }
 }
@@ -0,0 +1,176 @@
+use std::{
+  collections::{BTreeMap, HashMap},
+  fs,
+  path::PathBuf,
+};
+
+use ring::digest::{Context, SHA256};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  content::Content, module::module::ModuleKind, plugin::AnalyzeDep,
+  resource::resource::ResourceMap, utils::to_hex,
+};
+
+/// 缓存的模块构建结果，命中缓存时可以跳过 transform 和 analyze_deps
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedModule {
+  pub content: Content,
+  pub module_kind: ModuleKind,
+  pub deps: Vec<AnalyzeDep>,
+  #[serde(default)]
+  pub source_map_chain: Vec<String>,
+  /// 构建模块时插件通过 `emit_file` 添加的资源，命中缓存时重新添加
+  #[serde(default)]
+  pub emitted_files: ResourceMap,
+}
+
+/// 基于内容哈希的持久化模块缓存，每个模块的构建结果保存为缓存目录下的一个 json 文件
+pub struct ModuleCache {
+  dir: PathBuf,
+  /// toy 的版本、插件列表及其 `cache_fingerprint`、`Config::defines` 的指纹，它们变化时所有缓存都会失效
+  fingerprint: String,
+}
+
+impl ModuleCache {
+  /// `plugins_fingerprint` 为 `PluginContainer::cache_fingerprint`
+  pub fn new(dir: PathBuf, plugins_fingerprint: &str, defines: &BTreeMap<String, String>) -> Self {
+    Self {
+      dir,
+      fingerprint: format!(
+        "{}\0{plugins_fingerprint}\0{defines:?}",
+        env!("CARGO_PKG_VERSION")
+      ),
+    }
+  }
+
+  /// 根据模块 id、query、load 得到的内容和 `fingerprint` 计算缓存 key
+  pub fn key(
+    &self,
+    id: &str,
+    query: &HashMap<String, String>,
+    content: &Content,
+    module_kind: &ModuleKind,
+  ) -> String {
+    // query 是 HashMap，需要排序后再参与计算，保证 key 稳定
+    let query = query.iter().collect::<BTreeMap<_, _>>();
+    let mut context = Context::new(&SHA256);
+
+    for part in [
+      id,
+      &format!("{query:?}"),
+      &format!("{module_kind:?}"),
+      &self.fingerprint,
+    ] {
+      context.update(part.as_bytes());
+      // 分隔符，避免不同字段拼接后产生相同的输入
+      context.update(&[0]);
+    }
+
+    context.update(content.as_bytes());
+
+    to_hex(context.finish().as_ref())
+  }
+
+  pub fn get(&self, key: &str) -> Option<CachedModule> {
+    let content = fs::read(self.path(key)).ok()?;
+    serde_json::from_slice(&content).ok()
+  }
+
+  /// 写入缓存。缓存只是加速手段，写入失败时直接忽略
+  pub fn set(&self, key: &str, cached_module: &CachedModule) {
+    let Ok(content) = serde_json::to_vec(cached_module) else {
+      return;
+    };
+
+    if fs::create_dir_all(&self.dir).is_err() {
+      return;
+    }
+
+    // 先写入临时文件再重命名，避免其他进程读到写了一半的缓存
+    let tmp_path = self.dir.join(format!("{key}.{}.tmp", std::process::id()));
+
+    if fs::write(&tmp_path, content).is_ok() {
+      let _ = fs::rename(tmp_path, self.path(key));
+    }
+  }
+
+  fn path(&self, key: &str) -> PathBuf {
+    self.dir.join(format!("{key}.json"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_module_cache() {
+    let dir = std::env::temp_dir().join(format!("toy-cache-{}", std::process::id()));
+    let cache = ModuleCache::new(dir.clone(), "a\nb", &BTreeMap::new());
+    let query = HashMap::from([("foo".to_string(), "bar".to_string())]);
+    let key = cache.key("./a.js", &query, &"content".into(), &ModuleKind::Js);
+
+    assert_eq!(
+      key,
+      cache.key("./a.js", &query, &"content".into(), &ModuleKind::Js)
+    );
+    assert_ne!(
+      key,
+      cache.key(
+        "./a.js",
+        &HashMap::new(),
+        &"content".into(),
+        &ModuleKind::Js
+      )
+    );
+    assert_ne!(
+      key,
+      ModuleCache::new(dir.clone(), "a", &BTreeMap::new()).key(
+        "./a.js",
+        &query,
+        &"content".into(),
+        &ModuleKind::Js
+      )
+    );
+    // 插件选项变化时 transform 的结果也会变化
+    assert_ne!(
+      key,
+      ModuleCache::new(dir.clone(), "a\nb\0{\"minify\":true}", &BTreeMap::new()).key(
+        "./a.js",
+        &query,
+        &"content".into(),
+        &ModuleKind::Js
+      )
+    );
+    // define 变化时 transform 的结果也会变化
+    assert_ne!(
+      key,
+      ModuleCache::new(
+        dir.clone(),
+        "a\nb",
+        &BTreeMap::from([("__DEV__".to_string(), "true".to_string())])
+      )
+      .key("./a.js", &query, &"content".into(), &ModuleKind::Js)
+    );
+    assert!(cache.get(&key).is_none());
+
+    cache.set(
+      &key,
+      &CachedModule {
+        content: "transformed".into(),
+        module_kind: ModuleKind::Js,
+        deps: vec![],
+        source_map_chain: vec![],
+        emitted_files: ResourceMap::new(),
+      },
+    );
+
+    let cached_module = cache.get(&key).unwrap();
+
+    assert_eq!(cached_module.content, "transformed".into());
+    assert_eq!(cached_module.module_kind, ModuleKind::Js);
+
+    fs::remove_dir_all(dir).unwrap();
+  }
+}
@@ -0,0 +1,492 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use oxc_resolver::AliasValue;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+  error::{CompilationError, Result},
+  generate::check_file_name_template,
+  reporter::{LogConfig, LogFormat, LogLevel},
+};
+
+use super::{
+  CacheConfig, Config, ExternalConfig, ExternalMatcher, ExternalTarget, Mode, OutputConfig,
+  ProfileConfig, RuntimeMode,
+};
+
+/// 按顺序在 `root` 下查找的配置文件
+pub const CONFIG_FILE_NAMES: [&str; 2] = ["toy.config.json", "toy.config.toml"];
+
+/// 配置文件的格式，字段使用 camelCase，没有配置的字段使用 `Config::default` 中的值。
+///
+/// ```json
+/// {
+///   "input": { "main": "./index.html" },
+///   "output": { "dir": "./dist", "sourceMap": true },
+///   "resolve": { "alias": { "@": "./src" } },
+///   "externals": [{ "source": "react", "global": "React" }],
+///   "define": { "__VERSION__": "\"1.0.0\"" },
+///   "envPrefix": "TOY_"
+/// }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct UserConfig {
+  /// 相对于配置文件所在的目录，默认为配置文件所在的目录
+  root: Option<String>,
+  input: Option<BTreeMap<String, String>>,
+  mode: Option<Mode>,
+  output: UserOutputConfig,
+  resolve: UserResolveConfig,
+  cache: Option<UserCacheConfig>,
+  log: UserLogConfig,
+  profile: Option<UserProfileConfig>,
+  externals: Vec<UserExternalConfig>,
+  define: BTreeMap<String, String>,
+  env_prefix: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct UserOutputConfig {
+  dir: Option<String>,
+  write: Option<bool>,
+  source_map: Option<bool>,
+  runtime: Option<RuntimeMode>,
+  entry_file_names: Option<String>,
+  chunk_file_names: Option<String>,
+  asset_file_names: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct UserResolveConfig {
+  /// 别名 -> 路径，相对路径相对于 `root`
+  alias: BTreeMap<String, String>,
+  extensions: Option<Vec<String>>,
+  main_fields: Option<Vec<String>>,
+  condition_names: Option<Vec<String>>,
+  modules: Option<Vec<String>>,
+  symlinks: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct UserCacheConfig {
+  dir: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct UserLogConfig {
+  level: Option<LogLevel>,
+  format: Option<LogFormat>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct UserProfileConfig {
+  path: Option<String>,
+}
+
+/// `source`、`prefix`、`pattern` 必须且只能配置一个，配置了 `global` 时读取全局变量，否则保留为原生 import
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+struct UserExternalConfig {
+  source: Option<String>,
+  prefix: Option<String>,
+  pattern: Option<String>,
+  global: Option<String>,
+}
+
+/// 在 `root` 下查找配置文件，同时存在多个配置文件时报错，避免使用了意料之外的配置
+pub fn find_config_file(root: &str) -> Result<Option<PathBuf>> {
+  let mut paths = CONFIG_FILE_NAMES
+    .iter()
+    .map(|name| Path::new(root).join(name))
+    .filter(|path| path.is_file());
+
+  match (paths.next(), paths.next()) {
+    (Some(path), Some(other)) => Err(CompilationError::ConfigError {
+      path: path.to_string_lossy().to_string(),
+      message: format!(
+        "Found multiple config files, remove one of them: `{}`, `{}`",
+        path.to_string_lossy(),
+        other.to_string_lossy()
+      ),
+    }),
+    (path, _) => Ok(path),
+  }
+}
+
+/// 读取配置文件并转换成 `Config`，根据扩展名按 json 或 toml 解析
+pub fn load_config_file(path: &Path) -> Result<Config> {
+  let config_error = |message: String| CompilationError::ConfigError {
+    path: path.to_string_lossy().to_string(),
+    message,
+  };
+
+  let content = fs::read_to_string(path).map_err(|err| config_error(err.to_string()))?;
+
+  let user_config: UserConfig = match path.extension().and_then(|ext| ext.to_str()) {
+    Some("json") => serde_json::from_str(&content).map_err(|err| config_error(err.to_string()))?,
+    Some("toml") => toml::from_str(&content).map_err(|err| config_error(err.to_string()))?,
+    _ => {
+      return Err(config_error(
+        "Unsupported config file format, expect `.json` or `.toml`".to_string(),
+      ))
+    }
+  };
+
+  let dir = path.parent().unwrap_or(Path::new("."));
+  let dir = fs::canonicalize(dir).map_err(|err| config_error(err.to_string()))?;
+
+  user_config.into_config(&dir).map_err(config_error)
+}
+
+impl Config {
+  /// 从 `root` 下的配置文件加载配置，没有配置文件时使用默认配置
+  pub fn load(root: &str) -> Result<Config> {
+    match find_config_file(root)? {
+      Some(path) => load_config_file(&path),
+      None => Ok(Config {
+        root: root.to_string(),
+        ..Config::default()
+      }),
+    }
+  }
+}
+
+impl UserConfig {
+  /// `dir` 为配置文件所在的目录
+  fn into_config(self, dir: &Path) -> std::result::Result<Config, String> {
+    let default = Config::default();
+    let root = match &self.root {
+      Some(root) => normalize(&dir.join(root)),
+      None => dir.to_string_lossy().to_string(),
+    };
+
+    let output = OutputConfig {
+      dir: self.output.dir.unwrap_or(default.output.dir),
+      write: self.output.write.unwrap_or(default.output.write),
+      source_map: self.output.source_map.unwrap_or(default.output.source_map),
+      runtime: self.output.runtime.unwrap_or(default.output.runtime),
+      entry_file_names: self.output.entry_file_names,
+      chunk_file_names: self.output.chunk_file_names,
+      asset_file_names: self.output.asset_file_names,
+    };
+
+    for (field, template) in [
+      ("entryFileNames", &output.entry_file_names),
+      ("chunkFileNames", &output.chunk_file_names),
+      ("assetFileNames", &output.asset_file_names),
+    ] {
+      if let Some(template) = template {
+        check_file_name_template(template)
+          .map_err(|message| format!("output.{field}: {message}"))?;
+      }
+    }
+
+    let mut resolve = default.resolve;
+    let user_resolve = self.resolve;
+
+    resolve.alias = user_resolve
+      .alias
+      .into_iter()
+      .map(|(name, path)| {
+        let path = if path.starts_with('.') {
+          normalize(&Path::new(&root).join(path))
+        } else {
+          path
+        };
+        (name, vec![AliasValue::Path(path)])
+      })
+      .collect();
+
+    if let Some(extensions) = user_resolve.extensions {
+      resolve.extensions = extensions;
+    }
+    if let Some(main_fields) = user_resolve.main_fields {
+      resolve.main_fields = main_fields;
+    }
+    if let Some(condition_names) = user_resolve.condition_names {
+      resolve.condition_names = condition_names;
+    }
+    if let Some(modules) = user_resolve.modules {
+      resolve.modules = modules;
+    }
+    if let Some(symlinks) = user_resolve.symlinks {
+      resolve.symlinks = symlinks;
+    }
+
+    let cache = self.cache.map(|cache| CacheConfig {
+      dir: cache.dir.unwrap_or(CacheConfig::default().dir),
+    });
+
+    let log = LogConfig {
+      level: self.log.level,
+      format: self.log.format.unwrap_or_default(),
+    };
+
+    let profile = self.profile.map(|profile| ProfileConfig {
+      path: profile.path.unwrap_or(ProfileConfig::default().path),
+    });
+
+    let externals = self
+      .externals
+      .into_iter()
+      .enumerate()
+      .map(|(index, external)| {
+        external
+          .into_external_config()
+          .map_err(|message| format!("externals[{index}]: {message}"))
+      })
+      .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    // 空的前缀会把所有环境变量打包到产物中
+    let env_prefix = match self.env_prefix {
+      Some(prefix) if prefix.is_empty() => return Err("envPrefix must not be empty".to_string()),
+      Some(prefix) => prefix,
+      None => default.env_prefix,
+    };
+
+    Ok(Config {
+      root,
+      input: self.input.unwrap_or(default.input),
+      mode: self.mode.unwrap_or(default.mode),
+      output,
+      resolve,
+      cache,
+      log,
+      profile,
+      externals,
+      define: self.define,
+      env_prefix,
+    })
+  }
+}
+
+/// 去掉路径中的 `.`
+fn normalize(path: &Path) -> String {
+  path
+    .components()
+    .collect::<PathBuf>()
+    .to_string_lossy()
+    .to_string()
+}
+
+impl UserExternalConfig {
+  fn into_external_config(self) -> std::result::Result<ExternalConfig, String> {
+    let matcher = match (self.source, self.prefix, self.pattern) {
+      (Some(source), None, None) => ExternalMatcher::Exact(source),
+      (None, Some(prefix), None) => ExternalMatcher::Prefix(prefix),
+      (None, None, Some(pattern)) => ExternalMatcher::Pattern(
+        Regex::new(&pattern).map_err(|err| format!("invalid `pattern`: {err}"))?,
+      ),
+      _ => return Err("expect exactly one of `source`, `prefix` or `pattern`".to_string()),
+    };
+
+    let target = match self.global {
+      Some(global) => ExternalTarget::Global(global),
+      None => ExternalTarget::Import,
+    };
+
+    Ok(ExternalConfig { matcher, target })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// 在临时目录中写入配置文件，返回该目录
+  fn write_config(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("toy-config-{name}-{}", std::process::id()));
+
+    if dir.exists() {
+      fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fs::create_dir_all(&dir).unwrap();
+
+    for (file, content) in files {
+      fs::write(dir.join(file), content).unwrap();
+    }
+
+    fs::canonicalize(dir).unwrap()
+  }
+
+  #[test]
+  fn test_load_json() {
+    let dir = write_config(
+      "json",
+      &[(
+        "toy.config.json",
+        r#"{
+          "root": "./app",
+          "input": { "admin": "./admin.html" },
+          "mode": "production",
+          "output": { "sourceMap": true, "runtime": "separate", "entryFileNames": "js/[name]-[hash].js" },
+          "resolve": { "alias": { "@": "./src", "vue": "vue/dist/vue.esm.js" }, "extensions": [".ts"] },
+          "cache": {},
+          "define": { "__VERSION__": "\"1.0.0\"" },
+          "envPrefix": "APP_",
+          "log": { "level": "info", "format": "json" },
+          "externals": [
+            { "source": "react", "global": "React" },
+            { "prefix": "lodash/" },
+            { "pattern": "^day" }
+          ]
+        }"#,
+      )],
+    );
+    let config = Config::load(&dir.to_string_lossy()).unwrap();
+    let root = dir.join("app");
+
+    assert_eq!(config.root, root.to_string_lossy());
+    assert_eq!(
+      config.input,
+      BTreeMap::from([("admin".to_string(), "./admin.html".to_string())])
+    );
+    assert_eq!(config.mode, Mode::Production);
+    assert_eq!(config.output.dir, "./dist");
+    assert!(config.output.source_map);
+    assert_eq!(config.output.runtime, RuntimeMode::Separate);
+    assert_eq!(
+      config.output.entry_file_names.as_deref(),
+      Some("js/[name]-[hash].js")
+    );
+    assert!(config.output.chunk_file_names.is_none());
+    assert_eq!(
+      config.resolve.alias,
+      vec![
+        (
+          "@".to_string(),
+          vec![AliasValue::Path(
+            root.join("src").to_string_lossy().to_string()
+          )]
+        ),
+        (
+          "vue".to_string(),
+          vec![AliasValue::Path("vue/dist/vue.esm.js".to_string())]
+        ),
+      ]
+    );
+    assert_eq!(config.resolve.extensions, vec![".ts"]);
+    assert_eq!(
+      config.resolve.main_fields,
+      vec!["browser", "module", "main"]
+    );
+    assert_eq!(config.cache.as_ref().unwrap().dir, "./node_modules/.toy");
+    assert_eq!(config.log.level, Some(LogLevel::Info));
+    assert_eq!(config.log.format, LogFormat::Json);
+    assert!(config.profile.is_none());
+    assert_eq!(config.define["__VERSION__"], "\"1.0.0\"");
+    assert_eq!(config.env_prefix, "APP_");
+    assert_eq!(
+      config.external_target("react"),
+      Some(&ExternalTarget::Global("React".to_string()))
+    );
+    assert_eq!(
+      config.external_target("lodash/get"),
+      Some(&ExternalTarget::Import)
+    );
+    assert_eq!(
+      config.external_target("dayjs"),
+      Some(&ExternalTarget::Import)
+    );
+    assert_eq!(config.external_target("vue"), None);
+  }
+
+  #[test]
+  fn test_load_toml() {
+    let dir = write_config(
+      "toml",
+      &[(
+        "toy.config.toml",
+        r#"
+          [input]
+          main = "./index.js"
+
+          [output]
+          dir = "./build"
+          write = false
+
+          [profile]
+          path = "./profile.json"
+        "#,
+      )],
+    );
+    let config = Config::load(&dir.to_string_lossy()).unwrap();
+
+    assert_eq!(config.root, dir.to_string_lossy());
+    assert_eq!(config.input["main"], "./index.js");
+    assert_eq!(config.mode, Mode::Development);
+    assert_eq!(config.env_prefix, "TOY_");
+    assert_eq!(config.output.dir, "./build");
+    assert!(!config.output.write);
+    assert!(config.cache.is_none());
+    assert_eq!(config.profile.unwrap().path, "./profile.json");
+  }
+
+  #[test]
+  fn test_load_errors() {
+    let message = |name: &str, files: &[(&str, &str)]| {
+      let dir = write_config(name, files);
+      Config::load(&dir.to_string_lossy())
+        .unwrap_err()
+        .to_string()
+    };
+
+    assert!(message(
+      "unknown-field",
+      &[("toy.config.json", r#"{ "output": { "sourcemap": true } }"#)]
+    )
+    .contains("unknown field `sourcemap`, expected one of `dir`, `write`, `sourceMap`, `runtime`, `entryFileNames`, `chunkFileNames`, `assetFileNames`"));
+    assert!(message(
+      "file-names",
+      &[(
+        "toy.config.json",
+        r#"{ "output": { "chunkFileNames": "[name]-[chunkhash].js" } }"#
+      )]
+    )
+    .contains("output.chunkFileNames: unknown placeholder `[chunkhash]`"));
+    assert!(message(
+      "unknown-toml-field",
+      &[("toy.config.toml", "[outptu]\ndir = \"./build\"")]
+    )
+    .contains("unknown field `outptu`"));
+    assert!(message(
+      "external",
+      &[(
+        "toy.config.json",
+        r#"{ "externals": [{ "source": "react", "prefix": "react/" }] }"#
+      )]
+    )
+    .contains("externals[0]: expect exactly one of `source`, `prefix` or `pattern`"));
+    assert!(message(
+      "env-prefix",
+      &[("toy.config.json", r#"{ "envPrefix": "" }"#)]
+    )
+    .contains("envPrefix must not be empty"));
+    assert!(message(
+      "multiple",
+      &[("toy.config.json", "{}"), ("toy.config.toml", "")]
+    )
+    .contains("Found multiple config files"));
+  }
+
+  #[test]
+  fn test_load_without_config_file() {
+    let dir = write_config("empty", &[]);
+    let config = Config::load(&dir.to_string_lossy()).unwrap();
+
+    assert_eq!(config.root, dir.to_string_lossy());
+    assert_eq!(config.input["main"], "./index.html");
+  }
+}
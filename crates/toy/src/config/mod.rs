@@ -1,28 +1,205 @@
-use std::{collections::HashMap, env};
+use std::{collections::BTreeMap, env};
 
 use oxc_resolver::ResolveOptions;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::reporter::LogConfig;
+
+pub use file::{find_config_file, load_config_file, CONFIG_FILE_NAMES};
+
+mod file;
+
+/// 构建模式
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+  #[default]
+  Development,
+  Production,
+}
+
+impl Mode {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Self::Development => "development",
+      Self::Production => "production",
+    }
+  }
+}
+
+/// 模块系统运行时的输出方式
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeMode {
+  /// 内联到每个入口 JS 资源的开头
+  #[default]
+  Inline,
+  /// 输出为单独的运行时文件，被所有入口共享，html 中会在入口脚本之前引入
+  Separate,
+}
 
 #[derive(Debug)]
 pub struct OutputConfig {
   pub dir: String,
+  /// 是否把资源写入 `dir`，为 `false` 时只在内存中生成资源，可以通过 `Compiler::output` 获取
+  pub write: bool,
+  /// 是否为 js 资源生成 `.map` 文件
+  pub source_map: bool,
+  pub runtime: RuntimeMode,
+  /// 入口 JS 资源的文件名模板，支持 `[name]`、`[hash]`、`[contenthash:8]`、`[ext]`、`[dir]`，
+  /// 其中入口的 `[name]` 为 `input` 的 key，例如 `assets/[name]-[hash].js`。
+  /// 为 `None` 时使用 resource_pot 的 id 作为资源名，下同
+  pub entry_file_names: Option<String>,
+  /// 动态加载的 JS 资源以及单独输出的运行时的文件名模板
+  pub chunk_file_names: Option<String>,
+  /// css 等其他资源的文件名模板，html 资源的名称不会改变
+  pub asset_file_names: Option<String>,
+}
+
+impl Default for OutputConfig {
+  fn default() -> Self {
+    Self {
+      dir: "./dist".to_string(),
+      write: true,
+      source_map: false,
+      runtime: RuntimeMode::default(),
+      entry_file_names: None,
+      chunk_file_names: None,
+      asset_file_names: None,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct CacheConfig {
+  /// 缓存目录，相对于 `root`
+  pub dir: String,
+}
+
+impl Default for CacheConfig {
+  fn default() -> Self {
+    Self {
+      dir: "./node_modules/.toy".to_string(),
+    }
+  }
+}
+
+#[derive(Debug)]
+pub struct ProfileConfig {
+  /// Chrome trace_event 格式的 profile 文件路径，相对于 `root`
+  pub path: String,
+}
+
+impl Default for ProfileConfig {
+  fn default() -> Self {
+    Self {
+      path: "./toy-profile.json".to_string(),
+    }
+  }
+}
+
+/// 外部依赖的匹配规则，匹配 import 的 source
+#[derive(Debug, Clone)]
+pub enum ExternalMatcher {
+  /// 完全相同，例如 `react`
+  Exact(String),
+  /// 以它开头，例如 `lodash/`
+  Prefix(String),
+  /// 正则匹配
+  Pattern(Regex),
+}
+
+impl ExternalMatcher {
+  pub fn matches(&self, source: &str) -> bool {
+    match self {
+      Self::Exact(name) => source == name,
+      Self::Prefix(prefix) => source.starts_with(prefix.as_str()),
+      Self::Pattern(pattern) => pattern.is_match(source),
+    }
+  }
+}
+
+/// 外部依赖在产物中的引用方式
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternalTarget {
+  /// 读取全局变量，例如 `React` 会被转换成 `globalThis.React`，可以用 `.` 访问嵌套的属性
+  Global(String),
+  /// 保留为原生的 `import`，产物需要以 `<script type="module">` 加载
+  Import,
+}
+
+/// 不打包的外部依赖
+#[derive(Debug, Clone)]
+pub struct ExternalConfig {
+  pub matcher: ExternalMatcher,
+  pub target: ExternalTarget,
 }
 
 #[derive(Debug)]
 pub struct Config {
   pub root: String,
-  pub input: HashMap<String, String>,
+  /// 入口，按名称排序，保证每次构建的入口顺序一致
+  pub input: BTreeMap<String, String>,
+  pub mode: Mode,
   pub output: OutputConfig,
   pub resolve: ResolveOptions,
+  /// 持久化缓存，为 `None` 时不开启
+  pub cache: Option<CacheConfig>,
+  /// 日志配置，默认不输出任何日志
+  pub log: LogConfig,
+  /// 记录每个插件每个钩子的耗时，为 `None` 时不开启
+  pub profile: Option<ProfileConfig>,
+  /// 外部依赖，按顺序匹配，使用第一个匹配的规则
+  pub externals: Vec<ExternalConfig>,
+  /// 构建时替换的全局标识符或成员表达式，值为替换成的 JS 代码，例如
+  /// `__VERSION__` -> `"1.0.0"`、`process.env.API_URL` -> `"https://example.com"`
+  pub define: BTreeMap<String, String>,
+  /// 只有以它开头的环境变量（包括 `.env` 文件中的变量）会暴露给 `import.meta.env` 和 html 中的 `%ENV_NAME%`
+  pub env_prefix: String,
+}
+
+impl Config {
+  /// 需要替换的全部标识符：根据 `mode` 生成的 `process.env.NODE_ENV`、`import.meta.env.MODE`、
+  /// `import.meta.env.DEV`、`import.meta.env.PROD`，以及 `define`，`define` 优先
+  pub fn defines(&self) -> BTreeMap<String, String> {
+    let mode = format!("\"{}\"", self.mode.as_str());
+    let is_production = self.mode == Mode::Production;
+
+    let mut defines = BTreeMap::from([
+      ("process.env.NODE_ENV".to_string(), mode.clone()),
+      ("import.meta.env.MODE".to_string(), mode),
+      (
+        "import.meta.env.DEV".to_string(),
+        (!is_production).to_string(),
+      ),
+      (
+        "import.meta.env.PROD".to_string(),
+        is_production.to_string(),
+      ),
+    ]);
+
+    defines.extend(self.define.clone());
+    defines
+  }
+
+  /// source 匹配的外部依赖的引用方式，不是外部依赖时返回 `None`
+  pub fn external_target(&self, source: &str) -> Option<&ExternalTarget> {
+    self
+      .externals
+      .iter()
+      .find(|external| external.matcher.matches(source))
+      .map(|external| &external.target)
+  }
 }
 
 impl Default for Config {
   fn default() -> Self {
     Self {
       root: env::current_dir().unwrap().to_string_lossy().to_string(),
-      input: HashMap::from([("main".to_string(), "./index.html".to_string())]),
-      output: OutputConfig {
-        dir: "./dist".to_string(),
-      },
+      input: BTreeMap::from([("main".to_string(), "./index.html".to_string())]),
+      mode: Mode::default(),
+      output: OutputConfig::default(),
       resolve: ResolveOptions {
         extensions: vec![
           ".js".to_string(),
@@ -37,6 +214,12 @@ impl Default for Config {
         ],
         ..ResolveOptions::default()
       },
+      cache: None,
+      log: LogConfig::default(),
+      profile: None,
+      externals: vec![],
+      define: BTreeMap::new(),
+      env_prefix: "TOY_".to_string(),
     }
   }
 }
@@ -0,0 +1,79 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::{CompilationError, Result};
+
+/// 模块或资源的内容。文本以 `String` 保存，图片、字体、wasm 等二进制文件以 `Vec<u8>` 保存，
+/// 避免按 UTF-8 转换时损坏内容
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Content {
+  Text(String),
+  Bytes(Vec<u8>),
+}
+
+impl Content {
+  /// 文本内容。二进制内容是合法的 UTF-8 时也会返回文本，否则返回 `None`
+  pub fn as_text(&self) -> Option<&str> {
+    match self {
+      Self::Text(text) => Some(text),
+      Self::Bytes(bytes) => std::str::from_utf8(bytes).ok(),
+    }
+  }
+
+  /// 需要按文本解析的地方使用，内容不是合法的 UTF-8 时返回 `ParseError`
+  pub fn try_as_text(&self, id: &str) -> Result<&str> {
+    self.as_text().ok_or_else(|| CompilationError::ParseError {
+      id: id.to_string(),
+      message: "Content is not valid UTF-8 text".to_string(),
+    })
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    match self {
+      Self::Text(text) => text.as_bytes(),
+      Self::Bytes(bytes) => bytes,
+    }
+  }
+}
+
+impl Default for Content {
+  fn default() -> Self {
+    Self::Text(String::new())
+  }
+}
+
+impl From<String> for Content {
+  fn from(text: String) -> Self {
+    Self::Text(text)
+  }
+}
+
+impl From<&str> for Content {
+  fn from(text: &str) -> Self {
+    Self::Text(text.to_string())
+  }
+}
+
+impl From<Vec<u8>> for Content {
+  fn from(bytes: Vec<u8>) -> Self {
+    Self::Bytes(bytes)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_content() {
+    let text = Content::from("hello");
+    assert_eq!(text.as_text(), Some("hello"));
+    assert_eq!(text.as_bytes(), b"hello");
+
+    let bytes = Content::from(vec![0x89, b'P', b'N', b'G', 0xff]);
+    assert_eq!(bytes.as_text(), None);
+    assert_eq!(bytes.as_bytes().len(), 5);
+
+    // 合法 UTF-8 的二进制内容也可以按文本读取
+    assert_eq!(Content::from(b"abc".to_vec()).as_text(), Some("abc"));
+  }
+}
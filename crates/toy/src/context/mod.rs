@@ -1,38 +1,220 @@
-use std::sync::{Arc, RwLock};
+use std::{
+  cell::RefCell,
+  collections::{BTreeMap, HashSet},
+  path::{Path, PathBuf},
+  sync::{Arc, RwLock},
+};
 
 use crate::{
+  cache::ModuleCache,
   config::Config,
+  content::Content,
+  env::{env_defines, load_env},
+  error::Result,
   module::{module_graph::ModuleGraph, module_group::ModuleGroupMap},
   oxc::OxcAstBuilder,
   plugin::{plugin_container::PluginContainer, Plugin},
-  resource::{resource::ResourceMap, resource_pot::ResourcePotMap},
+  profile::Profiler,
+  reporter::{create_reporter, report, LogLevel, Reporter},
+  resource::{
+    resource::{Resource, ResourceKind, ResourceMap},
+    resource_pot::ResourcePotMap,
+  },
+  utils::content_hash,
 };
 
+thread_local! {
+  /// 当前线程中正在收集的 `emit_file` 添加的资源，见 `EmittedFilesCollector`
+  static EMITTED_FILES: RefCell<Option<ResourceMap>> = RefCell::new(None);
+}
+
 pub struct CompilationContext {
   pub config: Config,
+  /// 从 `.env` 文件和进程环境变量中加载的、以 `config.env_prefix` 开头的变量
+  pub env: BTreeMap<String, String>,
+  /// 构建时替换的全部标识符，包括 `env` 对应的 `import.meta.env.*` 和 `Config::defines`
+  pub defines: BTreeMap<String, String>,
   pub plugin_container: PluginContainer,
   pub module_graph: RwLock<ModuleGraph>,
   pub module_group_map: RwLock<ModuleGroupMap>,
   pub resource_pot_map: RwLock<ResourcePotMap>,
   pub resource_map: RwLock<ResourceMap>,
   pub ast_builder: OxcAstBuilder,
+  /// load 阶段读取过的文件，watch 模式下会监听这些文件的变化
+  pub watch_files: RwLock<HashSet<String>>,
+  /// 持久化的模块缓存，`config.cache` 为 `None` 时不开启
+  pub cache: Option<ModuleCache>,
+  pub reporter: Box<dyn Reporter>,
+  /// 插件钩子的耗时记录，`config.profile` 为 `None` 时不开启
+  pub profiler: Option<Profiler>,
+  /// 本次编译产生的警告
+  pub warnings: RwLock<Vec<String>>,
+  /// 插件在模块构建、resource_pot 渲染之外（例如 generate_end）通过 `emit_file` 添加的资源，资源名 -> 资源，
+  /// 每次编译开始时清空，会和 `Module::emitted_files`、`ResourcePot::emitted_files` 一起在 write_resources 之前合并到 resource_map
+  pub emitted_files: RwLock<ResourceMap>,
+  /// 插件通过 `add_runtime_module` 添加的运行时模块，名称 -> 代码
+  pub runtime_modules: RwLock<BTreeMap<String, String>>,
 }
 
 impl CompilationContext {
-  pub fn new(mut config: Config, plugins: Vec<Arc<dyn Plugin>>) -> Self {
+  pub fn new(config: Config, plugins: Vec<Arc<dyn Plugin>>) -> Result<Self> {
+    let reporter = create_reporter(&config.log);
+    Self::new_with_reporter(config, plugins, reporter)
+  }
+
+  /// 插件的 `run_before`、`run_after` 之间存在环，或者插件的 config 钩子失败时返回错误
+  pub fn new_with_reporter(
+    mut config: Config,
+    plugins: Vec<Arc<dyn Plugin>>,
+    reporter: Box<dyn Reporter>,
+  ) -> Result<Self> {
     let ast_builder = OxcAstBuilder::build();
-    let plugin_container = PluginContainer::new(plugins);
+    let plugin_container = PluginContainer::new(plugins)?;
 
-    plugin_container.config(&mut config).unwrap();
+    plugin_container.config(&mut config)?;
 
-    Self {
+    let env = load_env(&config.root, config.mode, &config.env_prefix);
+    let defines = env_defines(&config, &env);
+
+    let cache = config.cache.as_ref().map(|cache_config| {
+      ModuleCache::new(
+        PathBuf::from(&config.root).join(&cache_config.dir),
+        &plugin_container.cache_fingerprint(),
+        &defines,
+      )
+    });
+
+    let profiler = config.profile.as_ref().map(|_| Profiler::new());
+
+    Ok(Self {
       config,
+      env,
+      defines,
       ast_builder,
       plugin_container,
       module_graph: RwLock::new(ModuleGraph::new()),
       module_group_map: RwLock::new(ModuleGroupMap::new()),
       resource_pot_map: RwLock::new(ResourcePotMap::new()),
       resource_map: RwLock::new(ResourceMap::new()),
+      watch_files: RwLock::new(HashSet::new()),
+      cache,
+      reporter,
+      profiler,
+      warnings: RwLock::new(vec![]),
+      emitted_files: RwLock::new(ResourceMap::new()),
+      runtime_modules: RwLock::new(BTreeMap::new()),
+    })
+  }
+
+  /// 记录一个被读取的文件，文件变化时会触发 watch 模式的重新编译
+  pub fn add_watch_file(&self, path: &str) {
+    // 去掉路径中的 `./`，使其与文件系统事件中的路径一致
+    let path = Path::new(path).components().collect::<PathBuf>();
+
+    self
+      .watch_files
+      .write()
+      .unwrap()
+      .insert(path.to_string_lossy().to_string());
+  }
+
+  /// 记录一条警告，同时通过 reporter 输出
+  pub fn add_warning(&self, message: String) {
+    report!(self, LogLevel::Warn, "{message}");
+    self.warnings.write().unwrap().push(message);
+  }
+
+  /// 添加一个额外的输出文件，可以在任意钩子中调用（例如 transform、render_resource_pot、generate_end）。
+  /// `name` 中的 `[hash]` 会被替换为内容的 hash，返回最终的资源名，插件可以直接在代码中引用。
+  ///
+  /// 构建模块（load ~ analyze_deps）时添加的资源跟随模块保存，命中持久化缓存或者增量编译时复用模块也会保留；
+  /// render_resource_pot、generate_resources 中添加的资源跟随 resource_pot 保存。
+  /// 只有在调用钩子的线程中添加的资源才会被这样记录，插件自己创建的线程中添加的资源只在本次编译中有效
+  pub fn emit_file(&self, name: &str, content: impl Into<Content>, kind: ResourceKind) -> String {
+    let content = content.into();
+    let mut name = name.replace("[hash]", &content_hash(content.as_bytes()));
+
+    if !name.starts_with("./") {
+      name = format!("./{}", name.trim_start_matches('/'));
+    }
+
+    let resource = Resource {
+      name: name.clone(),
+      content: content.clone(),
+      resource_kind: kind,
+      emitted: false,
+      // 插件添加的资源不属于任何 resource_pot
+      resource_pot_id: String::new(),
+    };
+    let prev = EMITTED_FILES.with(|collected| match collected.borrow_mut().as_mut() {
+      Some(collected) => collected.insert(name.clone(), resource),
+      None => self
+        .emitted_files
+        .write()
+        .unwrap()
+        .insert(name.clone(), resource),
+    });
+
+    if prev.is_some_and(|prev| prev.content != content) {
+      self.add_warning(format!(
+        "File `{name}` is emitted more than once with different content, the last one wins"
+      ));
     }
+
+    name
+  }
+
+  /// 添加一个运行时模块（例如 HMR、动态加载），它会在模块系统创建之后、入口模块执行之前执行，
+  /// 代码中可以通过 `__toyModuleSystem__` 访问模块系统。运行时模块按名称排序，同名的会被覆盖。
+  /// 需要在 generate 阶段之前调用，例如在 `config` 或 `build_start` 钩子中
+  pub fn add_runtime_module(&self, name: &str, code: String) {
+    self
+      .runtime_modules
+      .write()
+      .unwrap()
+      .insert(name.to_string(), code);
+  }
+
+  /// 开始收集当前线程中通过 `emit_file` 添加的资源，直到 `EmittedFilesCollector::finish`
+  pub(crate) fn collect_emitted_files(&self) -> EmittedFilesCollector {
+    EmittedFilesCollector {
+      prev: Some(EMITTED_FILES.with(|collected| collected.replace(Some(ResourceMap::new())))),
+    }
+  }
+
+  /// 清空上一次编译的产物，用于全量重新编译
+  pub fn reset(&self) {
+    *self.module_graph.write().unwrap() = ModuleGraph::new();
+    *self.module_group_map.write().unwrap() = ModuleGroupMap::new();
+    *self.resource_pot_map.write().unwrap() = ResourcePotMap::new();
+    *self.resource_map.write().unwrap() = ResourceMap::new();
+    *self.emitted_files.write().unwrap() = ResourceMap::new();
+  }
+}
+
+/// 收集当前线程中通过 `emit_file` 添加的资源，结束（或者被 drop）时恢复之前的收集状态，
+/// 因此可以嵌套，例如 rayon 在等待时执行了另一个 resource_pot 的渲染
+pub(crate) struct EmittedFilesCollector {
+  prev: Option<Option<ResourceMap>>,
+}
+
+impl EmittedFilesCollector {
+  pub fn finish(mut self) -> ResourceMap {
+    self.restore()
+  }
+
+  fn restore(&mut self) -> ResourceMap {
+    match self.prev.take() {
+      Some(prev) => EMITTED_FILES
+        .with(|collected| collected.replace(prev))
+        .unwrap_or_default(),
+      None => ResourceMap::new(),
+    }
+  }
+}
+
+impl Drop for EmittedFilesCollector {
+  fn drop(&mut self) {
+    self.restore();
   }
 }
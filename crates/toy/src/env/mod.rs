@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, env, fs, path::Path};
+
+use crate::config::{Config, Mode};
+
+/// 按优先级从低到高排列的 env 文件，`[mode]` 会被替换为 `config.mode`
+pub const ENV_FILE_NAMES: [&str; 3] = [".env", ".env.local", ".env.[mode]"];
+
+/// 加载 `root` 下的 env 文件，只返回以 `prefix` 开头的变量，避免把密钥等变量打包到产物中。
+/// 进程的环境变量优先于 env 文件，`prefix` 为空时不加载任何变量。
+///
+/// env 文件只在创建 `Compiler` 时加载一次，修改后需要重新创建 `Compiler`
+pub fn load_env(root: &str, mode: Mode, prefix: &str) -> BTreeMap<String, String> {
+  if prefix.is_empty() {
+    return BTreeMap::new();
+  }
+
+  let mut vars = BTreeMap::new();
+
+  for name in ENV_FILE_NAMES {
+    let path = Path::new(root).join(name.replace("[mode]", mode.as_str()));
+
+    // env 文件是可选的，不存在或者无法读取时跳过
+    if let Ok(content) = fs::read_to_string(path) {
+      vars.extend(parse_env(&content));
+    }
+  }
+
+  vars.extend(env::vars());
+  vars.retain(|key, _| key.starts_with(prefix));
+  vars
+}
+
+/// 解析 env 文件，每行一个 `KEY=VALUE`，支持 `#` 注释、`export` 前缀以及单引号、双引号包裹的值，
+/// 双引号中可以使用 `\n` 等转义，无法解析的行会被忽略
+fn parse_env(content: &str) -> Vec<(String, String)> {
+  content
+    .lines()
+    .filter_map(|line| {
+      let line = line.trim();
+      let line = line.strip_prefix("export ").unwrap_or(line);
+      let (key, value) = line.split_once('=')?;
+      let key = key.trim();
+
+      if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+      }
+
+      Some((key.to_string(), parse_value(value.trim())))
+    })
+    .collect()
+}
+
+fn parse_value(value: &str) -> String {
+  if let Some(quoted) = value.strip_prefix('"') {
+    let mut result = String::new();
+    let mut chars = quoted.chars();
+
+    while let Some(c) = chars.next() {
+      match c {
+        '"' => break,
+        '\\' => match chars.next() {
+          Some('n') => result.push('\n'),
+          Some('r') => result.push('\r'),
+          Some('t') => result.push('\t'),
+          Some(c) => result.push(c),
+          None => break,
+        },
+        c => result.push(c),
+      }
+    }
+
+    return result;
+  }
+
+  if let Some(quoted) = value.strip_prefix('\'') {
+    return quoted.split('\'').next().unwrap_or_default().to_string();
+  }
+
+  // 没有引号时，` #` 之后是注释
+  value
+    .split(" #")
+    .next()
+    .unwrap_or_default()
+    .trim()
+    .to_string()
+}
+
+/// 构建时替换的全部标识符：`env` 中的变量对应的 `import.meta.env.*`，`import.meta.env` 整体替换为对象，
+/// 再加上 `Config::defines`，`Config::defines` 优先
+pub fn env_defines(config: &Config, env: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+  let mut object = serde_json::Map::new();
+  object.insert("MODE".to_string(), config.mode.as_str().into());
+  object.insert("DEV".to_string(), (config.mode == Mode::Development).into());
+  object.insert("PROD".to_string(), (config.mode == Mode::Production).into());
+
+  let mut defines = BTreeMap::new();
+
+  for (key, value) in env {
+    object.insert(key.clone(), value.as_str().into());
+    defines.insert(
+      format!("import.meta.env.{key}"),
+      serde_json::Value::from(value.as_str()).to_string(),
+    );
+  }
+
+  defines.insert(
+    "import.meta.env".to_string(),
+    serde_json::Value::Object(object).to_string(),
+  );
+
+  defines.extend(config.defines());
+  defines
+}
+
+#[cfg(test)]
+mod tests {
+  use std::process::Command;
+
+  use super::*;
+  use crate::{config::OutputConfig, utils::node_available, Compiler};
+
+  #[test]
+  fn test_parse_env() {
+    let vars = parse_env(
+      r#"
+# comment
+TOY_A=a
+export TOY_B = "line\nbreak" # comment
+TOY_C='single # quoted'
+TOY_D=value # comment
+not a var
+"#,
+    );
+
+    assert_eq!(
+      vars,
+      vec![
+        ("TOY_A".to_string(), "a".to_string()),
+        ("TOY_B".to_string(), "line\nbreak".to_string()),
+        ("TOY_C".to_string(), "single # quoted".to_string()),
+        ("TOY_D".to_string(), "value".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_load_env() {
+    let root = fs::canonicalize("../../fixtures/env").unwrap();
+    let root = root.to_string_lossy();
+    let env = load_env(&root, Mode::Production, "TOY_");
+
+    // .env.production 覆盖 .env.local 覆盖 .env，没有前缀的变量不会被加载
+    assert_eq!(env["TOY_TITLE"], "Toy App");
+    assert_eq!(env["TOY_API_URL"], "https://example.com");
+    assert!(!env.contains_key("SECRET_KEY"));
+
+    let env = load_env(&root, Mode::Development, "TOY_");
+    assert_eq!(env["TOY_API_URL"], "http://localhost:3000");
+
+    assert!(load_env(&root, Mode::Production, "").is_empty());
+  }
+
+  #[test]
+  fn test_compile_with_env() {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/env")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        mode: Mode::Production,
+        output: OutputConfig {
+          write: false,
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let output = compiler.output();
+    let html = output.content("./index.html").unwrap();
+
+    assert!(html.contains("<title>Toy App (production)</title>"));
+    assert!(html.contains(r#"content="https://example.com""#));
+    assert!(html.contains(r#"content="%SECRET_KEY%""#));
+
+    let code = output.content("./index.js").unwrap();
+    assert!(!code.contains("secret"));
+
+    if !node_available() {
+      return;
+    }
+
+    let result = Command::new("node").arg("-e").arg(code).output().unwrap();
+
+    assert!(result.status.success(), "{:?}", result);
+    assert_eq!(
+      String::from_utf8(result.stdout).unwrap(),
+      "https://example.com\nToy App_production_undefined\n"
+    );
+  }
+}
@@ -1,6 +1,8 @@
-use std::{error::Error, result::Result as StdResult};
+use std::{any::Any, error::Error, result::Result as StdResult};
 use thiserror::Error;
 
+use crate::module::{module::ModuleKind, ResolveKind};
+
 /// 错误枚举
 #[derive(Error, Debug)]
 pub enum CompilationError {
@@ -13,6 +15,8 @@ pub enum CompilationError {
   ResolveError {
     src: String,
     base: String,
+    /// 发起 resolve 的模块 id，入口模块为 `None`
+    importer: Option<String>,
     #[source]
     source: Option<Box<dyn Error + Send + Sync>>,
   },
@@ -24,6 +28,186 @@ pub enum CompilationError {
     #[source]
     source: Option<Box<dyn Error + Send + Sync>>,
   },
+
+  // parse
+  #[error("Parse `{id}` failed.\nError: {message}")]
+  ParseError { id: String, message: String },
+
+  /// 没有插件可以 resolve 该依赖
+  #[error(
+    "No plugin can resolve `{src}` ({kind:?}){}.\nTried plugins: {}",
+    imported_by(importer),
+    plugins.join(", ")
+  )]
+  NoResolver {
+    src: String,
+    kind: ResolveKind,
+    importer: Option<String>,
+    plugins: Vec<String>,
+  },
+
+  /// 没有插件可以 load 该模块，例如没有插件处理 `.json`、`.png` 文件
+  #[error(
+    "No plugin can load `{id}` ({kind:?}){}.\nTried plugins: {}",
+    imported_by(importer),
+    plugins.join(", ")
+  )]
+  NoLoader {
+    id: String,
+    kind: ModuleKind,
+    importer: Option<String>,
+    plugins: Vec<String>,
+  },
+
+  /// 没有插件可以 parse 该类型的模块
+  #[error(
+    "No plugin can parse `{id}` ({module_kind:?}){}.\nTried plugins: {}",
+    imported_by(importer),
+    plugins.join(", ")
+  )]
+  NoParser {
+    id: String,
+    module_kind: ModuleKind,
+    importer: Option<String>,
+    plugins: Vec<String>,
+  },
+
+  /// 没有插件处理必须有返回值的钩子，例如 `analyze_module_graph`、`merge_modules`
+  #[error("No plugin handles hook `{hook}`.\nTried plugins: {}", plugins.join(", "))]
+  NoPluginForHook { hook: String, plugins: Vec<String> },
+
+  /// 插件钩子中发生了 panic
+  #[error(
+    "Plugin `{plugin}` panicked in hook `{hook}`{}: {message}",
+    module_id.as_ref().map(|id| format!(" while processing `{id}`")).unwrap_or_default()
+  )]
+  PluginPanic {
+    plugin: String,
+    hook: String,
+    module_id: Option<String>,
+    message: String,
+  },
+
+  /// 进程外插件通信失败，或者插件返回了错误
+  #[error("External plugin `{plugin}` failed in hook `{hook}`: {message}")]
+  ExternalPluginError {
+    plugin: String,
+    hook: String,
+    message: String,
+  },
+
+  /// 插件的 `run_before` / `run_after` 之间存在环
+  #[error("Plugin order has a cycle: {}", plugins.join(" -> "))]
+  PluginOrderCycle { plugins: Vec<String> },
+
+  /// 输出资源到文件系统失败，例如磁盘已满或者没有权限
+  #[error("Write `{path}` failed.\nError: {source}")]
+  WriteError {
+    path: String,
+    #[source]
+    source: std::io::Error,
+  },
+
+  /// 配置文件不合法，例如包含未知的字段
+  #[error("Invalid config file `{path}`.\nError: {message}")]
+  ConfigError { path: String, message: String },
+
+  /// 一次构建中出现的多个错误
+  #[error("Found {} errors:\n\n{}", .0.len(), .0.iter().map(|err| err.to_string()).collect::<Vec<_>>().join("\n\n"))]
+  Multiple(Vec<CompilationError>),
+}
+
+impl CompilationError {
+  /// 把多个错误合并成一个：去重并按模块 id 排序，只有一个错误时直接返回该错误
+  pub fn from_errors(errors: Vec<CompilationError>) -> Option<CompilationError> {
+    let mut errors = errors
+      .into_iter()
+      .flat_map(|err| match err {
+        Self::Multiple(errors) => errors,
+        err => vec![err],
+      })
+      .map(|err| ((err.module_id().to_string(), err.to_string()), err))
+      .collect::<Vec<_>>();
+
+    errors.sort_by(|(a, _), (b, _)| a.cmp(b));
+    errors.dedup_by(|(a, _), (b, _)| a == b);
+
+    let mut errors = errors.into_iter().map(|(_, err)| err).collect::<Vec<_>>();
+
+    match errors.len() {
+      0 => None,
+      1 => errors.pop(),
+      _ => Some(Self::Multiple(errors)),
+    }
+  }
+
+  /// 出错的模块 id，resolve 错误使用发起 resolve 的模块
+  fn module_id(&self) -> &str {
+    match self {
+      Self::ResolveError { importer, .. } => importer.as_deref().unwrap_or_default(),
+      Self::LoadError { id, .. } | Self::ParseError { id, .. } => id,
+      Self::PluginPanic { module_id, .. } => module_id.as_deref().unwrap_or_default(),
+      Self::NoResolver { importer, .. } => importer.as_deref().unwrap_or_default(),
+      Self::NoLoader { id, .. } | Self::NoParser { id, .. } => id,
+      Self::GenericError(_)
+      | Self::NoPluginForHook { .. }
+      | Self::ExternalPluginError { .. }
+      | Self::PluginOrderCycle { .. }
+      | Self::ConfigError { .. }
+      | Self::WriteError { .. }
+      | Self::Multiple(_) => "",
+    }
+  }
 }
 
 pub type Result<T> = StdResult<T, CompilationError>;
+
+fn imported_by(importer: &Option<String>) -> String {
+  importer
+    .as_ref()
+    .map(|importer| format!(" imported by `{importer}`"))
+    .unwrap_or_default()
+}
+
+/// 从 panic 的 payload 中取出 panic 信息
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+  payload
+    .downcast_ref::<&str>()
+    .map(|message| message.to_string())
+    .or_else(|| payload.downcast_ref::<String>().cloned())
+    .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn load_error(id: &str) -> CompilationError {
+    CompilationError::LoadError {
+      id: id.to_string(),
+      source: None,
+    }
+  }
+
+  #[test]
+  fn test_from_errors() {
+    assert!(CompilationError::from_errors(vec![]).is_none());
+    assert!(matches!(
+      CompilationError::from_errors(vec![load_error("./a.js"), load_error("./a.js")]),
+      Some(CompilationError::LoadError { .. })
+    ));
+
+    let Some(CompilationError::Multiple(errors)) = CompilationError::from_errors(vec![
+      load_error("./c.js"),
+      load_error("./a.js"),
+      load_error("./c.js"),
+      CompilationError::Multiple(vec![load_error("./b.js")]),
+    ]) else {
+      panic!("expect multiple errors");
+    };
+
+    let ids = errors.iter().map(|err| err.module_id()).collect::<Vec<_>>();
+
+    assert_eq!(ids, vec!["./a.js", "./b.js", "./c.js"]);
+  }
+}
@@ -0,0 +1,414 @@
+use std::{
+  collections::{BTreeMap, HashSet},
+  path::Path,
+  sync::OnceLock,
+};
+
+use regex::{Captures, Regex};
+
+use crate::{
+  context::CompilationContext,
+  error::{CompilationError, Result},
+  resource::{
+    resource::{ResourceKind, ResourceMap},
+    resource_pot::ResourcePotMap,
+  },
+  utils::sha256_hex,
+};
+
+/// 文件名模板中的占位符，例如 `[name]`、`[contenthash:8]`
+const PLACEHOLDER: &str = r"\[([a-z]+)(?::(\d+))?\]";
+/// 没有指定长度时 hash 的长度
+const DEFAULT_HASH_LENGTH: usize = 8;
+/// js 资源末尾的 source map 注释，它引用的 `.map` 资源名由 js 资源名决定，不参与 hash 计算
+const SOURCE_MAPPING_URL: &str = "\n//# sourceMappingURL=";
+
+fn placeholder_regex() -> &'static Regex {
+  static REGEX: OnceLock<Regex> = OnceLock::new();
+  REGEX.get_or_init(|| Regex::new(PLACEHOLDER).unwrap())
+}
+
+/// 检查文件名模板，只能使用 `[name]`、`[hash]`、`[contenthash]`、`[ext]`、`[dir]`，
+/// hash 可以指定 1 ~ 64 的长度，例如 `[hash:6]`，并且结果必须在输出目录中
+pub fn check_file_name_template(template: &str) -> std::result::Result<(), String> {
+  for captures in placeholder_regex().captures_iter(template) {
+    let placeholder = &captures[0];
+
+    match (&captures[1], captures.get(2)) {
+      ("name" | "ext" | "dir" | "hash" | "contenthash", None) => {}
+      ("hash" | "contenthash", Some(length)) => {
+        if !matches!(length.as_str().parse(), Ok(1..=64)) {
+          return Err(format!(
+            "the hash length of `{placeholder}` must be between 1 and 64"
+          ));
+        }
+      }
+      _ => return Err(format!("unknown placeholder `{placeholder}`")),
+    }
+  }
+
+  if template.starts_with('/') || template.split('/').any(|segment| segment == "..") {
+    return Err("must be a relative path inside the output dir".to_string());
+  }
+
+  Ok(())
+}
+
+/// 填充文件名模板中的占位符
+struct FileNameParams<'a> {
+  /// 入口为 `config.input` 的 key，其他为 resource_pot id 的文件名（不包含扩展名）
+  name: &'a str,
+  /// resource_pot id 所在的目录，相对于 root，例如 `src/pages`
+  dir: &'a str,
+  /// 资源的扩展名，不包含 `.`
+  ext: &'a str,
+  /// 计算 hash 的内容
+  content: &'a [u8],
+}
+
+/// 把模板渲染为资源名，结果以 `./` 开头，`[dir]` 为空时不会留下多余的 `/`。
+/// 模板需要先经过 `check_file_name_template` 检查，`[name]` 来自 `config.input` 的 key，
+/// 渲染结果仍然可能包含 `..`，此时返回错误
+fn render_file_name(
+  template: &str,
+  params: &FileNameParams,
+) -> std::result::Result<String, String> {
+  let hash = sha256_hex(params.content);
+  let file_name =
+    placeholder_regex().replace_all(template, |captures: &Captures| match &captures[1] {
+      "name" => params.name.to_string(),
+      "dir" => params.dir.to_string(),
+      "ext" => params.ext.to_string(),
+      _ => {
+        let length = captures
+          .get(2)
+          .and_then(|length| length.as_str().parse().ok())
+          .unwrap_or(DEFAULT_HASH_LENGTH);
+        hash[..length].to_string()
+      }
+    });
+
+  let segments = file_name
+    .split('/')
+    .filter(|segment| !segment.is_empty() && *segment != ".")
+    .collect::<Vec<_>>();
+
+  if segments.contains(&"..") {
+    return Err(format!(
+      "`{file_name}` rendered from `{template}` must be a relative path inside the output dir"
+    ));
+  }
+
+  Ok(format!("./{}", segments.join("/")))
+}
+
+/// 根据 `config.output` 中的文件名模板重命名本次生成的资源，需要在 html 插件的 write_resources 之前执行，
+/// html 中引用的资源名以及 `BuildOutput` 中的资源名都会使用重命名后的名称：
+/// - 入口 JS 资源使用 `entry_file_names`
+/// - 动态加载的 JS 资源、单独输出的运行时使用 `chunk_file_names`
+/// - css 等其他资源使用 `asset_file_names`
+/// - source map 跟随对应的 js 资源，命名为 `<js 资源名>.map`
+/// - html 资源、插件通过 `emit_file` 添加的资源以及增量编译时复用的资源不会被重命名
+///
+/// hash 根据资源的最终内容计算，js 资源末尾的 `sourceMappingURL` 注释除外。
+///
+/// 运行时中动态加载资源的 manifest（`module-system.js` 中的 `dynamicIdToResource`）还没有注入：
+/// 动态加载的 resource_pot 目前不会被渲染，也就没有需要写入 manifest 的资源名。
+/// 支持动态加载之后，需要根据这里重命名后的资源名生成 manifest
+pub fn apply_file_names(
+  context: &CompilationContext,
+  resource_pot_map: &mut ResourcePotMap,
+  resource_map: &mut ResourceMap,
+  reused_resource_pot_ids: &HashSet<String>,
+) -> Result<()> {
+  let output = &context.config.output;
+
+  // 配置文件中的模板在加载时已经检查过，这里检查直接在 Rust 中构造的 `Config`
+  for (field, template) in [
+    ("entry_file_names", &output.entry_file_names),
+    ("chunk_file_names", &output.chunk_file_names),
+    ("asset_file_names", &output.asset_file_names),
+  ] {
+    if let Some(template) = template {
+      check_file_name_template(template).map_err(|message| CompilationError::ConfigError {
+        path: format!("output.{field}"),
+        message,
+      })?;
+    }
+  }
+
+  if output.entry_file_names.is_none()
+    && output.chunk_file_names.is_none()
+    && output.asset_file_names.is_none()
+  {
+    return Ok(());
+  }
+
+  let module_graph = context.module_graph.read().unwrap();
+  // 原资源名 -> 新资源名
+  let mut renamed = BTreeMap::new();
+
+  for resource_pot in resource_pot_map
+    .values_mut()
+    .filter(|resource_pot| !reused_resource_pot_ids.contains(&resource_pot.id))
+  {
+    let entry_name = module_graph.entries.get(&resource_pot.module_group_id);
+    let id = Path::new(resource_pot.id.trim_start_matches("./"));
+    let stem = id
+      .file_stem()
+      .map(|stem| stem.to_string_lossy().to_string())
+      .unwrap_or_default();
+    let name = entry_name.cloned().unwrap_or(stem);
+    let dir = id
+      .parent()
+      .map(|dir| dir.to_string_lossy().to_string())
+      .unwrap_or_default();
+
+    for resource_id in &resource_pot.resource_ids {
+      let resource = &resource_map[resource_id];
+      let (field, template) = match resource.resource_kind {
+        ResourceKind::Js if entry_name.is_some() => ("entry_file_names", &output.entry_file_names),
+        ResourceKind::Js | ResourceKind::Runtime => ("chunk_file_names", &output.chunk_file_names),
+        ResourceKind::Css | ResourceKind::Asset | ResourceKind::Custom(_) => {
+          ("asset_file_names", &output.asset_file_names)
+        }
+        ResourceKind::Html | ResourceKind::SourceMap => continue,
+      };
+
+      let Some(template) = template else {
+        continue;
+      };
+
+      let bytes = resource.content.as_bytes();
+      let content = match resource.content.as_text() {
+        Some(code) if resource.resource_kind != ResourceKind::Css => code
+          .rsplit_once(SOURCE_MAPPING_URL)
+          .map_or(bytes, |(code, _)| code.as_bytes()),
+        _ => bytes,
+      };
+      let ext = Path::new(&resource.name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+      let file_name = render_file_name(
+        template,
+        &FileNameParams {
+          name: &name,
+          dir: &dir,
+          ext: &ext,
+          content,
+        },
+      )
+      .map_err(|message| CompilationError::ConfigError {
+        path: format!("output.{field}"),
+        message,
+      })?;
+
+      renamed.insert(resource_id.clone(), file_name);
+    }
+
+    // source map 跟随对应的 js 资源
+    for resource_id in &resource_pot.resource_ids {
+      if resource_map[resource_id].resource_kind == ResourceKind::SourceMap {
+        let js_resource_id = resource_id.trim_end_matches(".map");
+
+        if let Some(js_file_name) = renamed.get(js_resource_id) {
+          renamed.insert(resource_id.clone(), format!("{js_file_name}.map"));
+        }
+      }
+    }
+
+    for resource_id in resource_pot.resource_ids.iter_mut() {
+      if let Some(file_name) = renamed.get(resource_id) {
+        *resource_id = file_name.clone();
+      }
+    }
+  }
+
+  let mut resources = renamed
+    .iter()
+    .map(|(resource_id, file_name)| (file_name, resource_map.remove(resource_id).unwrap()))
+    .collect::<Vec<_>>();
+
+  for (file_name, resource) in resources.iter_mut() {
+    // 更新 js 资源中引用的 source map 文件名
+    if let Some(code) = resource.content.as_text() {
+      if let Some((code, source_map_url)) = code.rsplit_once(SOURCE_MAPPING_URL) {
+        let source_map_id = Path::new(&resource.name).with_file_name(source_map_url);
+        let source_map_id = source_map_id.to_string_lossy();
+
+        if let Some(source_map_name) = renamed.get(source_map_id.as_ref()) {
+          let source_map_file_name = Path::new(source_map_name).file_name().unwrap();
+          resource.content = format!(
+            "{code}{SOURCE_MAPPING_URL}{}",
+            source_map_file_name.to_string_lossy()
+          )
+          .into();
+        }
+      }
+    }
+
+    resource.name = file_name.to_string();
+  }
+
+  for (file_name, resource) in resources {
+    if resource_map.insert(file_name.clone(), resource).is_some() {
+      return Err(CompilationError::GenericError(format!(
+        "Multiple resources are named `{file_name}`, add `[dir]` or `[hash]` to the output file name templates"
+      )));
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::BTreeMap, fs};
+
+  use super::*;
+  use crate::{
+    config::{Config, OutputConfig, RuntimeMode},
+    Compiler,
+  };
+
+  #[test]
+  fn test_render_file_name() {
+    let params = FileNameParams {
+      name: "main",
+      dir: "src/pages",
+      ext: "js",
+      content: b"console.log(1);",
+    };
+    let hash = sha256_hex(params.content);
+
+    assert_eq!(
+      render_file_name("[dir]/[name]-[hash].[ext]", &params).unwrap(),
+      format!("./src/pages/main-{}.js", &hash[..8])
+    );
+    assert_eq!(
+      render_file_name("assets/[name].[contenthash:12].[ext]", &params).unwrap(),
+      format!("./assets/main.{}.js", &hash[..12])
+    );
+    assert_eq!(
+      render_file_name("[dir]/[name].[ext]", &FileNameParams { dir: "", ..params }).unwrap(),
+      "./main.js"
+    );
+    // `[name]` 来自 `config.input` 的 key，不能渲染到输出目录之外
+    assert!(render_file_name(
+      "[name].[ext]",
+      &FileNameParams {
+        name: "../evil",
+        ..params
+      }
+    )
+    .is_err());
+  }
+
+  #[test]
+  fn test_check_file_name_template() {
+    assert!(check_file_name_template("[dir]/[name]-[hash:6].[ext]").is_ok());
+    assert_eq!(
+      check_file_name_template("[name]-[chunkhash].js"),
+      Err("unknown placeholder `[chunkhash]`".to_string())
+    );
+    assert_eq!(
+      check_file_name_template("[name]-[hash:65].js"),
+      Err("the hash length of `[hash:65]` must be between 1 and 64".to_string())
+    );
+    assert!(check_file_name_template("../[name].js").is_err());
+  }
+
+  #[test]
+  fn test_compile_with_file_names() {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/css")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.html".to_string())]),
+        output: OutputConfig {
+          write: false,
+          source_map: true,
+          runtime: RuntimeMode::Separate,
+          entry_file_names: Some("js/[name]-[hash].[ext]".to_string()),
+          chunk_file_names: Some("js/[name].[contenthash:4].js".to_string()),
+          asset_file_names: Some("[dir]/assets/[name].[ext]".to_string()),
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let output = compiler.output();
+    let js_name = output
+      .resources
+      .values()
+      .find(|resource| resource.kind == ResourceKind::Js)
+      .unwrap()
+      .name
+      .clone();
+    let js = output.content(&js_name).unwrap();
+    let (code, source_map_url) = js.rsplit_once(SOURCE_MAPPING_URL).unwrap();
+
+    // 入口的 `[name]` 为 `config.input` 的 key，hash 不包含 sourceMappingURL 注释
+    assert_eq!(
+      js_name,
+      format!("./js/main-{}.js", &sha256_hex(code.as_bytes())[..8])
+    );
+    assert_eq!(
+      source_map_url,
+      js_name.trim_start_matches("./js/").to_string() + ".map"
+    );
+    assert!(output.resources.contains_key(&format!("{js_name}.map")));
+    assert!(output.resources.contains_key("./assets/main.css"));
+
+    let runtime_name = &output.entries["main"][0];
+    assert!(runtime_name.starts_with("./js/toy-runtime."));
+
+    let html = output.content("./index.html").unwrap();
+    assert!(html.contains(r#"href="./assets/main.css""#));
+    assert!(html.contains(&format!(r#"src="{runtime_name}""#)));
+    assert!(html.contains(&format!(r#"src="{js_name}""#)));
+  }
+
+  #[test]
+  fn test_compile_with_invalid_file_names() {
+    // 最后一个模板本身合法，但 `[name]` 渲染后在输出目录之外
+    for (name, template) in [
+      ("main", "[name]-[hash:100].js"),
+      ("main", "[name]-[chunkhash].js"),
+      ("main", "../[name].js"),
+      ("../evil", "[name].js"),
+    ] {
+      let mut compiler = Compiler::new(
+        Config {
+          root: fs::canonicalize("../../fixtures/basic")
+            .unwrap()
+            .to_string_lossy()
+            .to_string(),
+          input: BTreeMap::from([(name.to_string(), "./index.js".to_string())]),
+          output: OutputConfig {
+            write: false,
+            entry_file_names: Some(template.to_string()),
+            ..OutputConfig::default()
+          },
+          ..Config::default()
+        },
+        vec![],
+      )
+      .unwrap();
+
+      let err = compiler.compile().unwrap_err();
+      assert!(
+        matches!(&err, CompilationError::ConfigError { path, .. } if path == "output.entry_file_names"),
+        "{template}: {err}"
+      );
+    }
+  }
+}
@@ -1,68 +1,177 @@
+use std::collections::HashSet;
+
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::{error::Result, Compiler};
+use crate::{
+  error::{CompilationError, Result},
+  plugin::plugin_container::Hook,
+  reporter::{report, LogLevel},
+  resource::{
+    resource::ResourceMap,
+    resource_pot::{ResourcePotKind, ResourcePotMap},
+  },
+  Compiler,
+};
+
+use self::file_names::apply_file_names;
+
+pub use self::file_names::check_file_name_template;
+
+mod file_names;
 
 impl Compiler {
   pub(crate) fn generate(&mut self) -> Result<()> {
+    self.generate_resource_pots(None)
+  }
+
+  /// 增量生成：只重新渲染包含变化模块的 resource_pot，其余 resource_pot 及其资源直接复用
+  pub(crate) fn regenerate(&mut self, changed_module_ids: &HashSet<String>) -> Result<()> {
+    self.generate_resource_pots(Some(changed_module_ids))
+  }
+
+  fn generate_resource_pots(&mut self, changed_module_ids: Option<&HashSet<String>>) -> Result<()> {
     // generate_start
     self
       .context
       .plugin_container
       .generate_start(&self.context)?;
 
-    println!(">>> generate_start");
+    report!(self.context, LogLevel::Debug, "generate_start");
 
     // analyze_module_graph -> build module_group_map
     let mut module_graph = self.context.module_graph.write().unwrap();
 
+    // 清除上一次生成时记录的 module_group
+    for module_id in module_graph.module_ids().collect::<Vec<_>>() {
+      module_graph
+        .module_mut(&module_id)
+        .unwrap()
+        .module_groups
+        .clear();
+    }
+
     let ret_module_group_map = self
       .context
       .plugin_container
       .analyze_module_graph(&mut module_graph, &self.context)?
-      .unwrap();
+      .ok_or_else(|| CompilationError::NoPluginForHook {
+        hook: Hook::AnalyzeModuleGraph.to_string(),
+        plugins: self
+          .context
+          .plugin_container
+          .tried_plugin_names(Hook::AnalyzeModuleGraph, &*module_graph),
+      })?;
 
     let mut module_group_map = self.context.module_group_map.write().unwrap();
     *module_group_map = ret_module_group_map;
-    println!(">>> module_group_map {:#?}", module_group_map);
+    report!(
+      self.context,
+      LogLevel::Debug,
+      "module_group_map {:#?}",
+      module_group_map
+    );
 
     drop(module_graph);
 
     // merge_modules -> build resource_pot_map
-    let ret_resource_pot_map = self
+    let mut ret_resource_pot_map = self
       .context
       .plugin_container
       .merge_modules(&mut module_group_map, &self.context)?
-      .unwrap();
+      .ok_or_else(|| CompilationError::NoPluginForHook {
+        hook: Hook::MergeModules.to_string(),
+        plugins: self
+          .context
+          .plugin_container
+          .tried_plugin_names(Hook::MergeModules, &*module_group_map),
+      })?;
+
+    self
+      .context
+      .plugin_container
+      .process_resource_pots(&mut ret_resource_pot_map, &self.context)?;
 
     drop(module_group_map);
 
     let mut resource_pot_map = self.context.resource_pot_map.write().unwrap();
+    let mut resource_map = self.context.resource_map.write().unwrap();
+    let prev_resource_pot_map = std::mem::take(&mut *resource_pot_map);
+    let mut prev_resource_map = std::mem::take(&mut *resource_map);
+
+    // 复用没有变化的 resource_pot，以及它们生成的资源
+    let reused_resource_pot_ids = match changed_module_ids {
+      Some(changed_module_ids) => reusable_resource_pot_ids(
+        &ret_resource_pot_map,
+        &prev_resource_pot_map,
+        changed_module_ids,
+      ),
+      None => HashSet::new(),
+    };
+
+    for (id, mut prev_resource_pot) in prev_resource_pot_map {
+      if !reused_resource_pot_ids.contains(&id) {
+        continue;
+      }
+
+      for resource_id in &prev_resource_pot.resource_ids {
+        if let Some(resource) = prev_resource_map.remove(resource_id) {
+          resource_map.insert(resource_id.clone(), resource);
+        }
+      }
+
+      let resource_pot = ret_resource_pot_map.get_mut(&id).unwrap();
+      prev_resource_pot.module_group_id = resource_pot.module_group_id.clone();
+      *resource_pot = prev_resource_pot;
+    }
+
     *resource_pot_map = ret_resource_pot_map;
+    drop(resource_map);
 
-    println!(">>> resource_pot_map {:#?}", resource_pot_map);
+    report!(
+      self.context,
+      LogLevel::Debug,
+      "resource_pot_map {:#?}",
+      resource_pot_map
+    );
 
     // 1. render_resource_pot
     // 2. generate_resources
     resource_pot_map
       .values_mut()
+      .filter(|resource_pot| !reused_resource_pot_ids.contains(&resource_pot.id))
       .collect::<Vec<_>>()
       .into_par_iter()
-      .try_for_each(|mut resource_pot| {
+      .try_for_each(|resource_pot| {
+        // 插件通过 `emit_file` 添加的资源记录在 resource_pot 上，复用 resource_pot 时也会保留
+        let emitted_files_collector = self.context.collect_emitted_files();
+
         // render_resource_pot
         self
           .context
           .plugin_container
-          .render_resource_pot(&mut resource_pot, &self.context)?;
+          .render_resource_pot(resource_pot, &self.context)?;
 
-        println!(">>> render_resource_pot {:#?}", resource_pot);
+        report!(
+          self.context,
+          LogLevel::Debug,
+          "render_resource_pot {:#?}",
+          resource_pot
+        );
 
         // generate_resources
         let resources = self
           .context
           .plugin_container
-          .generate_resources(&mut resource_pot, &self.context)?;
+          .generate_resources(resource_pot, &self.context)?;
 
-        println!(">>> generate_resources {:#?}", resources);
+        report!(
+          self.context,
+          LogLevel::Debug,
+          "generate_resources {:#?}",
+          resources
+        );
+
+        resource_pot.emitted_files = emitted_files_collector.finish();
 
         if let Some(resources) = resources {
           let mut resource_map = self.context.resource_map.write().unwrap();
@@ -73,9 +182,20 @@ impl Compiler {
         Ok(())
       })?;
 
+    // 根据文件名模板确定资源的最终名称，html 插件在 write_resources 中引用的是最终名称
+    let mut resource_map = self.context.resource_map.write().unwrap();
+    apply_file_names(
+      &self.context,
+      &mut resource_pot_map,
+      &mut resource_map,
+      &reused_resource_pot_ids,
+    )?;
+
+    let emitted_files = self.emitted_files(&resource_pot_map);
+
     drop(resource_pot_map);
 
-    let mut resource_map = self.context.resource_map.write().unwrap();
+    merge_emitted_files(&mut resource_map, emitted_files);
 
     // write_resources -> could be emitted to filesystem
     self
@@ -83,15 +203,106 @@ impl Compiler {
       .plugin_container
       .write_resources(&mut resource_map, &self.context)?;
 
-    println!(">>> write_resources");
+    report!(self.context, LogLevel::Debug, "write_resources");
 
     drop(resource_map);
 
     // generate_end
     self.context.plugin_container.generate_end(&self.context)?;
 
-    println!(">>> generate_end");
+    report!(self.context, LogLevel::Debug, "generate_end");
+
+    // generate_end 中通过 `emit_file` 添加的资源需要再输出一次
+    let emitted_files = self.emitted_files(&self.context.resource_pot_map.read().unwrap());
+    let mut resource_map = self.context.resource_map.write().unwrap();
+
+    if merge_emitted_files(&mut resource_map, emitted_files) {
+      self
+        .context
+        .plugin_container
+        .write_resources(&mut resource_map, &self.context)?;
+    }
 
     Ok(())
   }
+
+  /// 插件通过 `emit_file` 添加的全部资源：模块上的、resource_pot 上的以及其他钩子中添加的，同名时后者优先
+  fn emitted_files(&self, resource_pot_map: &ResourcePotMap) -> ResourceMap {
+    let module_graph = self.context.module_graph.read().unwrap();
+    let mut emitted_files = ResourceMap::new();
+
+    for module_id in module_graph.module_ids() {
+      let module = module_graph.module(&module_id).unwrap();
+      emitted_files.extend(module.emitted_files.clone());
+    }
+
+    for resource_pot in resource_pot_map.values() {
+      emitted_files.extend(resource_pot.emitted_files.clone());
+    }
+
+    emitted_files.extend(self.context.emitted_files.read().unwrap().clone());
+    emitted_files
+  }
+}
+
+/// 把插件通过 `emit_file` 添加的资源合并到 resource_map，返回是否有新的资源
+fn merge_emitted_files(resource_map: &mut ResourceMap, emitted_files: ResourceMap) -> bool {
+  let mut changed = false;
+
+  for (name, resource) in emitted_files {
+    let is_same = resource_map
+      .get(&name)
+      .map(|prev| prev.content == resource.content && prev.resource_kind == resource.resource_kind)
+      .unwrap_or(false);
+
+    if !is_same {
+      resource_map.insert(name, resource);
+      changed = true;
+    }
+  }
+
+  changed
+}
+
+/// 找出可以复用的 resource_pot：
+/// - 上一次生成时已存在，且包含的模块完全一致
+/// - 包含的模块都没有发生变化
+/// - html resource_pot 的内容依赖同一个 module_group 里其他资源，
+///   所以同组内有 resource_pot 需要重新渲染时，html resource_pot 也需要重新渲染
+fn reusable_resource_pot_ids(
+  resource_pot_map: &ResourcePotMap,
+  prev_resource_pot_map: &ResourcePotMap,
+  changed_module_ids: &HashSet<String>,
+) -> HashSet<String> {
+  let is_unchanged = |id: &String| {
+    let resource_pot = &resource_pot_map[id];
+
+    prev_resource_pot_map
+      .get(id)
+      .map(|prev_resource_pot| {
+        prev_resource_pot.kind == resource_pot.kind
+          && prev_resource_pot.module_ids == resource_pot.module_ids
+      })
+      .unwrap_or(false)
+      && resource_pot
+        .module_ids
+        .iter()
+        .all(|module_id| !changed_module_ids.contains(module_id))
+  };
+
+  let dirty_module_group_ids = resource_pot_map
+    .values()
+    .filter(|resource_pot| !is_unchanged(&resource_pot.id))
+    .map(|resource_pot| resource_pot.module_group_id.clone())
+    .collect::<HashSet<_>>();
+
+  resource_pot_map
+    .values()
+    .filter(|resource_pot| {
+      is_unchanged(&resource_pot.id)
+        && !(matches!(resource_pot.kind, ResourcePotKind::Html)
+          && dirty_module_group_ids.contains(&resource_pot.module_group_id))
+    })
+    .map(|resource_pot| resource_pot.id.clone())
+    .collect()
 }
@@ -1,65 +1,364 @@
-use std::sync::Arc;
+#![allow(clippy::module_inception)]
+
+use std::{collections::HashSet, path::Path, sync::Arc, time::Instant};
 
 use config::Config;
 use context::CompilationContext;
 use error::Result;
 use plugin::Plugin;
 use plugins::{
-  css::PluginCss, html::PluginHtml, modules::PluginModules, resolve::PluginResolve,
-  resources::PluginResources, script::PluginScript,
+  asset::PluginAsset, css::PluginCss, html::PluginHtml, modules::PluginModules,
+  resolve::PluginResolve, resources::PluginResources, runtime::PluginRuntime, script::PluginScript,
 };
+use reporter::{create_reporter, report, LogLevel, Reporter};
+use utils::to_relative;
 
 mod build;
-mod config;
+mod cache;
+pub mod config;
+mod content;
 mod context;
+mod env;
 pub mod error;
 mod generate;
 mod lightningcss;
 mod module;
+pub mod output;
 mod oxc;
 mod plugin;
 mod plugins;
+mod profile;
+pub mod reporter;
 mod resource;
+mod source_map;
 mod utils;
+pub mod watch;
+
+pub use plugins::external::PluginExternal;
 
 pub struct Compiler {
   context: Arc<CompilationContext>,
 }
 
 impl Compiler {
-  pub fn new(config: Config, mut plugins: Vec<Arc<dyn Plugin>>) -> Self {
+  /// 插件的 `run_before`、`run_after` 之间存在环，或者插件的 config 钩子失败时返回错误
+  pub fn new(config: Config, plugins: Vec<Arc<dyn Plugin>>) -> Result<Self> {
+    let reporter = create_reporter(&config.log);
+    Self::new_with_reporter(config, plugins, reporter)
+  }
+
+  /// 使用自定义的 Reporter 输出日志
+  pub fn new_with_reporter(
+    config: Config,
+    mut plugins: Vec<Arc<dyn Plugin>>,
+    reporter: Box<dyn Reporter>,
+  ) -> Result<Self> {
     let mut final_plugins: Vec<Arc<dyn Plugin>> = vec![
       Arc::new(PluginResolve::new(config.resolve.clone())),
       Arc::new(PluginScript::new()),
       Arc::new(PluginHtml::new()),
       Arc::new(PluginCss::new()),
+      Arc::new(PluginAsset::new()),
       Arc::new(PluginModules::new()),
+      Arc::new(PluginRuntime::new()),
       Arc::new(PluginResources::new()),
     ];
 
     final_plugins.append(&mut plugins);
-    final_plugins.sort_by_key(|plugin| plugin.priority());
 
-    Self {
-      context: Arc::new(CompilationContext::new(config, final_plugins)),
-    }
+    Ok(Self {
+      context: Arc::new(CompilationContext::new_with_reporter(
+        config,
+        final_plugins,
+        reporter,
+      )?),
+    })
+  }
+
+  /// 插件的最终执行顺序，用于调试插件顺序
+  pub fn plugin_names(&self) -> Vec<&str> {
+    self.context.plugin_container.plugin_names()
   }
 
   pub fn compile(&mut self) -> Result<()> {
-    self.build()?;
+    let start = Instant::now();
+
+    self.context.warnings.write().unwrap().clear();
+    self.context.emitted_files.write().unwrap().clear();
+
+    let result = self.build().and_then(|_| self.generate());
 
-    self.generate()?;
+    // 编译失败时也输出 profile，便于定位问题
+    self.write_profile()?;
+    result?;
 
-    println!(">>> done");
+    report!(
+      self.context,
+      LogLevel::Info,
+      "compiled in {}ms",
+      start.elapsed().as_millis()
+    );
+    Ok(())
+  }
+
+  /// 增量编译。复用上一次编译得到的 module_graph、module_group_map 和 resource_pot_map，
+  /// 只重新构建发生变化的文件对应的模块，并只重新生成受影响的 resource_pot。
+  ///
+  /// `changed_paths` 可以是绝对路径，也可以是相对于 `config.root` 的模块 id（例如 `./index.js`），
+  /// 不在 module_graph 中的路径会被忽略。
+  pub fn update(&mut self, changed_paths: Vec<String>) -> Result<()> {
+    let root = &self.context.config.root;
+    let module_graph = self.context.module_graph.read().unwrap();
+    let changed_module_ids = changed_paths
+      .iter()
+      .map(|path| {
+        if Path::new(path).is_absolute() {
+          to_relative(path, root)
+        } else {
+          path.clone()
+        }
+      })
+      .filter(|id| module_graph.has_module(id))
+      .collect::<HashSet<_>>();
+
+    drop(module_graph);
+
+    if changed_module_ids.is_empty() {
+      return Ok(());
+    }
+
+    let start = Instant::now();
+
+    self.context.warnings.write().unwrap().clear();
+    self.context.emitted_files.write().unwrap().clear();
+
+    let result = self
+      .rebuild(&changed_module_ids)
+      .and_then(|_| self.regenerate(&changed_module_ids));
+
+    self.write_profile()?;
+    result?;
+
+    report!(
+      self.context,
+      LogLevel::Info,
+      "updated {} module(s) in {}ms",
+      changed_module_ids.len(),
+      start.elapsed().as_millis()
+    );
     Ok(())
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use std::{collections::HashMap, fs};
+  use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+  };
 
   use super::*;
+  use crate::{
+    config::OutputConfig,
+    context::CompilationContext,
+    error::CompilationError,
+    module::module::ModuleKind,
+    plugin::{LoadHookParams, LoadHookResult, ResolveHookParams, ResolveHookResult},
+    resource::resource_pot::ResourcePot,
+  };
+
+  /// 把 fixture 复制到临时目录，用于会修改源文件的测试
+  fn copy_fixture(name: &str) -> PathBuf {
+    let from = fs::canonicalize(format!("../../fixtures/{name}")).unwrap();
+    let to = std::env::temp_dir().join(format!("toy-fixture-{name}-{}", std::process::id()));
+
+    if to.exists() {
+      fs::remove_dir_all(&to).unwrap();
+    }
+
+    fs::create_dir_all(&to).unwrap();
+
+    for entry in fs::read_dir(from).unwrap() {
+      let entry = entry.unwrap();
+
+      if entry.file_type().unwrap().is_file() {
+        fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+      }
+    }
+
+    to
+  }
+
+  /// 读取目录下的所有文件，文件名 -> 内容
+  fn read_dir_files(dir: &Path) -> BTreeMap<String, Vec<u8>> {
+    fs::read_dir(dir)
+      .unwrap()
+      .map(|entry| {
+        let entry = entry.unwrap();
+        (
+          entry.file_name().to_string_lossy().to_string(),
+          fs::read(entry.path()).unwrap(),
+        )
+      })
+      .collect()
+  }
+
+  /// 记录被渲染的 resource_pot
+  struct PluginRenderRecorder {
+    rendered: Mutex<Vec<String>>,
+  }
+
+  impl Plugin for PluginRenderRecorder {
+    fn name(&self) -> &str {
+      "TestPluginRenderRecorder"
+    }
+
+    fn render_resource_pot(
+      &self,
+      resource_pot: &mut ResourcePot,
+      _context: &Arc<CompilationContext>,
+    ) -> Result<()> {
+      self.rendered.lock().unwrap().push(resource_pot.id.clone());
+      Ok(())
+    }
+  }
+
+  /// 声明了执行顺序的插件
+  struct PluginOrdered;
+
+  impl Plugin for PluginOrdered {
+    fn name(&self) -> &str {
+      "TestPluginOrdered"
+    }
+
+    fn run_after(&self) -> Vec<&str> {
+      vec!["ToyPluginResolve"]
+    }
+
+    fn run_before(&self) -> Vec<&str> {
+      vec!["ToyPluginScript"]
+    }
+  }
+
+  #[test]
+  fn test_plugin_order() {
+    let compiler = Compiler::new(Config::default(), vec![Arc::new(PluginOrdered)]).unwrap();
+    let plugin_names = compiler.plugin_names();
+    let position = |name| plugin_names.iter().position(|n| *n == name).unwrap();
+
+    assert!(position("ToyPluginResolve") < position("TestPluginOrdered"));
+    assert!(position("TestPluginOrdered") < position("ToyPluginScript"));
+  }
+
+  /// 同时要求在 `ToyPluginScript` 之前和之后执行
+  struct PluginCycle;
+
+  impl Plugin for PluginCycle {
+    fn name(&self) -> &str {
+      "TestPluginCycle"
+    }
+
+    fn run_after(&self) -> Vec<&str> {
+      vec!["ToyPluginScript"]
+    }
+
+    fn run_before(&self) -> Vec<&str> {
+      vec!["ToyPluginScript"]
+    }
+  }
+
+  #[test]
+  fn test_plugin_order_cycle() {
+    let Err(CompilationError::PluginOrderCycle { plugins }) =
+      Compiler::new(Config::default(), vec![Arc::new(PluginCycle)])
+    else {
+      panic!("expected a plugin order cycle error");
+    };
+
+    assert!(plugins.contains(&"TestPluginCycle".to_string()));
+    assert!(plugins.contains(&"ToyPluginScript".to_string()));
+  }
+
+  /// 提供 `virtual:routes` 和 `virtual:theme` 两个虚拟模块
+  struct PluginVirtual;
+
+  impl Plugin for PluginVirtual {
+    fn name(&self) -> &str {
+      "TestPluginVirtual"
+    }
+
+    fn resolve(
+      &self,
+      params: &ResolveHookParams,
+      _context: &Arc<CompilationContext>,
+    ) -> Result<Option<ResolveHookResult>> {
+      let module_kind = match params.source.as_str() {
+        "virtual:routes" => ModuleKind::Js,
+        "virtual:theme" => ModuleKind::Css,
+        _ => return Ok(None),
+      };
+
+      Ok(Some(ResolveHookResult {
+        id: params.source.clone(),
+        query: HashMap::new(),
+        external: false,
+        module_kind: Some(module_kind),
+      }))
+    }
+
+    fn load(
+      &self,
+      params: &LoadHookParams,
+      _context: &Arc<CompilationContext>,
+    ) -> Result<Option<LoadHookResult>> {
+      let content = match params.id.as_str() {
+        // 虚拟模块的依赖相对于 root 解析
+        "virtual:routes" => "export { foo as routes } from './foo.js';",
+        "virtual:theme" => "body { color: red; }",
+        _ => return Ok(None),
+      };
+
+      Ok(Some(LoadHookResult {
+        content: content.into(),
+        module_kind: params.module_kind.clone(),
+      }))
+    }
+  }
+
+  #[test]
+  fn test_virtual_modules() {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/virtual")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          write: false,
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![Arc::new(PluginVirtual)],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let output = compiler.output();
+    let js = output.content("./index.js").unwrap();
+
+    assert!(js.contains("'virtual:routes'"));
+    assert!(js.contains("'/about'"));
+    // 样式模块不会被 JS 引用
+    assert!(!js.contains("__toyRequire__('virtual:theme')"));
+    assert!(output.resources.values().any(|resource| resource
+      .content
+      .as_text()
+      .is_some_and(|text| text.contains("color: red"))));
+  }
 
   #[test]
   fn it_works() {
@@ -69,11 +368,12 @@ mod tests {
           .unwrap()
           .to_string_lossy()
           .to_string(),
-        input: HashMap::from([("main".to_string(), "./index.js".to_string())]),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
         ..Config::default()
       },
       vec![],
-    );
+    )
+    .unwrap();
     compiler.compile().unwrap();
   }
 
@@ -85,11 +385,12 @@ mod tests {
           .unwrap()
           .to_string_lossy()
           .to_string(),
-        input: HashMap::from([("main".to_string(), "./index.html".to_string())]),
+        input: BTreeMap::from([("main".to_string(), "./index.html".to_string())]),
         ..Config::default()
       },
       vec![],
-    );
+    )
+    .unwrap();
     compiler.compile().unwrap();
   }
 
@@ -101,11 +402,162 @@ mod tests {
           .unwrap()
           .to_string_lossy()
           .to_string(),
-        input: HashMap::from([("main".to_string(), "./index.html".to_string())]),
+        input: BTreeMap::from([("main".to_string(), "./index.html".to_string())]),
         ..Config::default()
       },
       vec![],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+  }
+
+  #[test]
+  fn update_rerenders_changed_resource_pots() {
+    let root = copy_fixture("css");
+    let recorder = Arc::new(PluginRenderRecorder {
+      rendered: Mutex::new(vec![]),
+    });
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.html".to_string())]),
+        ..Config::default()
+      },
+      vec![recorder.clone()],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let css_before = fs::read_to_string(root.join("dist/foz.css")).unwrap();
+    recorder.rendered.lock().unwrap().clear();
+
+    fs::write(
+      root.join("bar.js"),
+      "export function bar() {\n  return 'bar_updated';\n}",
+    )
+    .unwrap();
+    compiler
+      .update(vec![root.join("bar.js").to_string_lossy().to_string()])
+      .unwrap();
+
+    let mut rendered = recorder.rendered.lock().unwrap().clone();
+    rendered.sort();
+
+    // css resource_pot 没有变化，不需要重新渲染
+    assert_eq!(rendered, vec!["./index.html", "./index.js"]);
+    assert!(fs::read_to_string(root.join("dist/index.js"))
+      .unwrap()
+      .contains("bar_updated"));
+    assert_eq!(
+      fs::read_to_string(root.join("dist/foz.css")).unwrap(),
+      css_before
     );
+    assert!(root.join("dist/index.html").exists());
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn update_removes_orphan_modules() {
+    let root = copy_fixture("basic");
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
     compiler.compile().unwrap();
+
+    fs::write(
+      root.join("index.js"),
+      "import { foo } from './foo';\n\nconsole.log(foo());",
+    )
+    .unwrap();
+    compiler.update(vec!["./index.js".to_string()]).unwrap();
+
+    let module_graph = compiler.context.module_graph.read().unwrap();
+
+    assert!(!module_graph.has_module("./bar.js"));
+    assert!(module_graph.has_module("./foz.js"));
+
+    let code = fs::read_to_string(root.join("dist/index.js")).unwrap();
+
+    assert!(!code.contains("./bar.js"));
+    assert!(code.contains("./foz.js"));
+
+    drop(module_graph);
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn compile_is_deterministic() {
+    let root = fs::canonicalize("../../fixtures/css").unwrap();
+    let compile = |out_dir: &str| {
+      let mut compiler = Compiler::new(
+        Config {
+          root: root.to_string_lossy().to_string(),
+          input: BTreeMap::from([
+            ("main".to_string(), "./index.html".to_string()),
+            ("foz".to_string(), "./foz.js".to_string()),
+          ]),
+          output: OutputConfig {
+            dir: out_dir.to_string(),
+            ..OutputConfig::default()
+          },
+          ..Config::default()
+        },
+        vec![],
+      )
+      .unwrap();
+      compiler.compile().unwrap();
+
+      let files = read_dir_files(&root.join(out_dir));
+      fs::remove_dir_all(root.join(out_dir)).unwrap();
+      files
+    };
+
+    let first = compile("./dist-deterministic-1");
+
+    assert!(first.contains_key("index.html"));
+
+    for _ in 0..5 {
+      assert_eq!(compile("./dist-deterministic-2"), first);
+    }
+  }
+
+  #[test]
+  fn compile_rejects_duplicate_entry_modules() {
+    let root = fs::canonicalize("../../fixtures/basic").unwrap();
+    let compile = || {
+      let mut compiler = Compiler::new(
+        Config {
+          root: root.to_string_lossy().to_string(),
+          input: BTreeMap::from([
+            ("a".to_string(), "./index.js".to_string()),
+            ("b".to_string(), "./index.js".to_string()),
+          ]),
+          output: OutputConfig {
+            write: false,
+            ..OutputConfig::default()
+          },
+          ..Config::default()
+        },
+        vec![],
+      )
+      .unwrap();
+      compiler.compile().unwrap_err().to_string()
+    };
+
+    // 无论哪个入口先 resolve，结果都相同
+    let first = compile();
+
+    assert!(first.contains("`a` and `b` resolve to the same module `./index.js`"));
+
+    for _ in 0..5 {
+      assert_eq!(compile(), first);
+    }
   }
 }
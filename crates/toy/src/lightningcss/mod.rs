@@ -1,5 +1,3 @@
-use std::ops::Deref;
-
 use lightningcss::{
   rules::CssRule,
   stylesheet::{ParserOptions, StyleSheet},
@@ -15,8 +13,9 @@ pub struct LightningStyleSheet {
 }
 
 impl LightningStyleSheet {
-  pub fn build(code: String, filename: String) -> LightningStyleSheet {
-    LightningStyleSheetBuilder {
+  /// 解析 css，语法错误时返回错误信息
+  pub fn build(code: String, filename: String) -> Result<LightningStyleSheet, String> {
+    LightningStyleSheetTryBuilder {
       code,
       style_sheet_builder: |code| {
         StyleSheet::parse(
@@ -26,10 +25,10 @@ impl LightningStyleSheet {
             ..ParserOptions::default()
           },
         )
-        .unwrap()
+        .map_err(|err| err.to_string())
       },
     }
-    .build()
+    .try_build()
   }
 
   pub fn copy_css_rules(&self) -> Vec<CssRule> {
@@ -2,7 +2,18 @@ pub mod module;
 pub mod module_graph;
 pub mod module_group;
 
-#[derive(Debug, Clone, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+/// 虚拟模块 id 的前缀，例如 `virtual:routes`。
+/// 虚拟模块没有对应的文件，由插件在 resolve 和 load 钩子中提供，内置插件不会在文件系统中读取或解析它们
+pub const VIRTUAL_MODULE_PREFIX: &str = "virtual:";
+
+/// 是否是虚拟模块的 id（或 import 的 source）
+pub fn is_virtual_module(id: &str) -> bool {
+  id.starts_with(VIRTUAL_MODULE_PREFIX)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ResolveKind {
   /// entry input in the config
   Entry,
@@ -1,10 +1,19 @@
-use lightningcss::stylesheet::StyleSheet;
-use std::{any::Any, collections::HashSet, ffi::OsStr, path::Path};
+use serde::{Deserialize, Serialize};
+use std::{
+  any::Any,
+  collections::{BTreeSet, HashMap},
+  ffi::OsStr,
+  path::Path,
+  sync::atomic::AtomicBool,
+};
 use swc_html::ast::Document;
 
-use crate::{lightningcss::LightningStyleSheet, oxc::OxcProgram};
+use crate::{
+  content::Content, lightningcss::LightningStyleSheet, oxc::OxcProgram,
+  resource::resource::ResourceMap,
+};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ModuleKind {
   Html,
   Css,
@@ -21,6 +30,8 @@ impl ModuleKind {
   /// `"html"` -> `ModuleKind::Html`
   ///
   /// `"css"` -> `ModuleKind::Css`
+  ///
+  /// `"png"` -> `ModuleKind::Asset`
   pub fn from_ext(ext: &str) -> Self {
     match ext {
       "html" => Self::Html,
@@ -29,6 +40,8 @@ impl ModuleKind {
       "jsx" => Self::Jsx,
       "ts" => Self::Ts,
       "tsx" => Self::Tsx,
+      "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "avif" | "ico" | "bmp" | "woff"
+      | "woff2" | "ttf" | "otf" | "eot" | "wasm" | "mp3" | "mp4" | "webm" => Self::Asset,
       _ => Self::Custom(ext.to_string()),
     }
   }
@@ -53,12 +66,17 @@ impl ModuleKind {
   pub fn is_script(&self) -> bool {
     matches!(self, Self::Js | Self::Jsx | Self::Ts | Self::Tsx)
   }
+
+  pub fn is_asset(&self) -> bool {
+    matches!(self, Self::Asset)
+  }
 }
 
 pub enum ModuleMeta {
   Html(HtmlModuleMeta),
   Css(CssModuleMeta),
   Script(ScriptModuleMeta),
+  Asset(AssetModuleMeta),
   Custom(Box<dyn Any + Send + Sync>),
 }
 
@@ -90,6 +108,12 @@ impl ModuleMeta {
       _ => unreachable!("ModuleMeta `as_script()` failed"),
     }
   }
+  pub fn as_asset(&self) -> &AssetModuleMeta {
+    match self {
+      Self::Asset(meta) => meta,
+      _ => unreachable!("ModuleMeta `as_asset()` failed"),
+    }
+  }
 }
 
 #[derive(Debug)]
@@ -105,22 +129,42 @@ pub struct CssModuleMeta {
 pub struct ScriptModuleMeta {
   pub code: String,
   pub ast: OxcProgram,
+  /// `ast` 是否已经被渲染。渲染会原地修改 ast，再次渲染时需要根据 `code` 重新解析
+  pub ast_rendered: AtomicBool,
+}
+
+/// 图片、字体、wasm 等静态资源，内容原样输出
+#[derive(Debug)]
+pub struct AssetModuleMeta {
+  pub content: Content,
 }
 
 pub struct Module {
   pub id: String,
+  /// the query of the resolved id, e.g. `{ "foo": "bar" }` for `./a.js?foo=bar`
+  pub query: HashMap<String, String>,
   pub kind: ModuleKind,
   pub meta: ModuleMeta,
-  pub module_groups: HashSet<String>,
+  pub module_groups: BTreeSet<String>,
+  /// transform 阶段插件返回的 source map，按 transform 的顺序排列
+  pub source_map_chain: Vec<String>,
+  /// 外部依赖，不会被 load 和打包
+  pub external: bool,
+  /// 构建这个模块时插件通过 `emit_file` 添加的资源
+  pub emitted_files: ResourceMap,
 }
 
 impl Module {
   pub fn new(id: String, kind: ModuleKind, meta: Option<ModuleMeta>) -> Self {
     Self {
       id,
+      query: HashMap::new(),
       kind,
       meta: meta.unwrap_or(ModuleMeta::Custom(Box::new(()))),
-      module_groups: HashSet::new(),
+      module_groups: BTreeSet::new(),
+      source_map_chain: vec![],
+      external: false,
+      emitted_files: ResourceMap::new(),
     }
   }
 }
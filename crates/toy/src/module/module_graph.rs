@@ -1,7 +1,8 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
 use petgraph::{
   stable_graph::{NodeIndex, StableDiGraph},
+  visit::EdgeRef,
   Direction,
 };
 
@@ -26,8 +27,15 @@ pub struct ModuleGraphEdge {
 pub struct ModuleGraph {
   graph: StableDiGraph<Module, ModuleGraphEdge>,
   id_to_index: HashMap<String, NodeIndex>,
-  pub entries: HashSet<String>,
-  pub entries_in_html: HashSet<String>,
+  /// 入口模块 id -> 入口名（`config.input` 的 key）
+  pub entries: BTreeMap<String, String>,
+  pub entries_in_html: BTreeSet<String>,
+}
+
+impl Default for ModuleGraph {
+  fn default() -> Self {
+    Self::new()
+  }
 }
 
 impl ModuleGraph {
@@ -35,17 +43,40 @@ impl ModuleGraph {
     Self {
       graph: StableDiGraph::new(),
       id_to_index: HashMap::new(),
-      entries: HashSet::new(),
-      entries_in_html: HashSet::new(),
+      entries: BTreeMap::new(),
+      entries_in_html: BTreeSet::new(),
     }
   }
 
+  /// 添加模块。如果模块已存在，则原地替换模块内容，保留已有的边
   pub fn add_module(&mut self, module: Module) {
+    if let Some(index) = self.id_to_index.get(&module.id) {
+      self.graph[*index] = module;
+      return;
+    }
+
     let id = module.id.clone();
     let index = self.graph.add_node(module);
     self.id_to_index.insert(id, index);
   }
 
+  pub fn has_module(&self, id: &str) -> bool {
+    self.id_to_index.contains_key(id)
+  }
+
+  pub fn module_ids(&self) -> impl Iterator<Item = String> + '_ {
+    self.id_to_index.keys().cloned()
+  }
+
+  /// 删除模块以及与它相连的边
+  pub fn remove_module(&mut self, id: &str) -> Option<Module> {
+    let index = self.id_to_index.remove(id)?;
+
+    self.entries.remove(id);
+    self.entries_in_html.remove(id);
+    self.graph.remove_node(index)
+  }
+
   pub fn module(&self, id: &str) -> Option<&Module> {
     let index = self.id_to_index.get(id);
 
@@ -75,10 +106,7 @@ impl ModuleGraph {
     })?;
 
     let to_index = self.id_to_index.get(to).ok_or_else(|| {
-      CompilationError::GenericError(format!(
-        "from node \"{}\" is not found in module graph",
-        from
-      ))
+      CompilationError::GenericError(format!("to node \"{}\" is not found in module graph", to))
     })?;
 
     self.graph.add_edge(*from_index, *to_index, edge_info);
@@ -111,8 +139,81 @@ impl ModuleGraph {
     Ok(deps)
   }
 
+  /// 删除模块指向其依赖的所有边
+  pub fn remove_dependency_edges(&mut self, id: &str) -> Result<()> {
+    let index = self.id_to_index.get(id).ok_or_else(|| {
+      CompilationError::GenericError(format!("id \"{}\" is not found in module graph", id))
+    })?;
+
+    let edge_indexes = self
+      .graph
+      .edges_directed(*index, Direction::Outgoing)
+      .map(|edge| edge.id())
+      .collect::<Vec<_>>();
+
+    for edge_index in edge_indexes {
+      self.graph.remove_edge(edge_index);
+    }
+
+    Ok(())
+  }
+
+  /// 删除从入口出发不可达的模块，返回被删除的模块 id
+  pub fn remove_orphan_modules(&mut self) -> Vec<String> {
+    let mut reachable = HashSet::new();
+    let mut queue = self
+      .entries
+      .keys()
+      .filter_map(|id| self.id_to_index.get(id).copied())
+      .collect::<VecDeque<_>>();
+
+    while let Some(index) = queue.pop_front() {
+      if reachable.insert(index) {
+        queue.extend(self.graph.neighbors_directed(index, Direction::Outgoing));
+      }
+    }
+
+    let orphan_ids = self
+      .id_to_index
+      .iter()
+      .filter(|(_, index)| !reachable.contains(*index))
+      .map(|(id, _)| id.clone())
+      .collect::<Vec<_>>();
+
+    for id in &orphan_ids {
+      self.remove_module(id);
+    }
+
+    orphan_ids
+  }
+
+  /// 获取依赖该模块的模块（即 importer）
+  pub fn dependents(&self, id: &str) -> Result<Vec<(String, ModuleGraphEdge)>> {
+    let index = self.id_to_index.get(id).ok_or_else(|| {
+      CompilationError::GenericError(format!("id \"{}\" is not found in module graph", id))
+    })?;
+
+    let mut edges = self
+      .graph
+      .neighbors_directed(*index, Direction::Incoming)
+      .detach();
+
+    let mut dependents = Vec::new();
+
+    while let Some((edge_index, node_index)) = edges.next(&self.graph) {
+      dependents.push((
+        self.graph[node_index].id.clone(),
+        self.graph[edge_index].clone(),
+      ));
+    }
+
+    dependents.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.order.cmp(&b.1.order)));
+
+    Ok(dependents)
+  }
+
   pub fn is_entry_module(&self, id: &str, check_entries_in_html: bool) -> bool {
-    let ret = self.entries.contains(id);
+    let ret = self.entries.contains_key(id);
 
     if check_entries_in_html {
       ret || self.entries_in_html.contains(id)
@@ -228,7 +329,7 @@ mod tests {
     assert_eq!(a_deps.len(), 2);
 
     assert_eq!(
-      a_deps.get(0).unwrap(),
+      a_deps.first().unwrap(),
       &(
         "c".to_string(),
         ModuleGraphEdge {
@@ -255,7 +356,7 @@ mod tests {
 
     assert_eq!(b_deps.len(), 1);
     assert_eq!(
-      b_deps.get(0).unwrap(),
+      b_deps.first().unwrap(),
       &(
         "e".to_string(),
         ModuleGraphEdge {
@@ -270,7 +371,7 @@ mod tests {
 
     assert_eq!(c_deps.len(), 1);
     assert_eq!(
-      c_deps.get(0).unwrap(),
+      c_deps.first().unwrap(),
       &(
         "f".to_string(),
         ModuleGraphEdge {
@@ -285,7 +386,7 @@ mod tests {
 
     assert_eq!(d_deps.len(), 1);
     assert_eq!(
-      d_deps.get(0).unwrap(),
+      d_deps.first().unwrap(),
       &(
         "f".to_string(),
         ModuleGraphEdge {
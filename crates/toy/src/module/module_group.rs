@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 #[derive(Debug, PartialEq)]
 pub struct ModuleGroup {
@@ -36,4 +36,4 @@ impl ModuleGroup {
   }
 }
 
-pub type ModuleGroupMap = HashMap<String, ModuleGroup>;
+pub type ModuleGroupMap = BTreeMap<String, ModuleGroup>;
@@ -0,0 +1,392 @@
+use std::collections::BTreeMap;
+
+use crate::{
+  content::Content,
+  resource::resource::{Resource, ResourceKind},
+  Compiler,
+};
+
+/// 一个输出的资源
+#[derive(Debug, Clone)]
+pub struct OutputResource {
+  pub name: String,
+  pub content: Content,
+  pub kind: ResourceKind,
+  /// 生成该资源的 resource_pot
+  pub resource_pot_id: String,
+}
+
+impl From<&Resource> for OutputResource {
+  fn from(resource: &Resource) -> Self {
+    Self {
+      name: resource.name.clone(),
+      content: resource.content.clone(),
+      kind: resource.resource_kind.clone(),
+      resource_pot_id: resource.resource_pot_id.clone(),
+    }
+  }
+}
+
+/// 一次编译的产物
+#[derive(Debug, Default)]
+pub struct BuildOutput {
+  /// 资源名 -> 资源
+  pub resources: BTreeMap<String, OutputResource>,
+  /// 入口名（`config.input` 的 key） -> 该入口输出的资源名
+  pub entries: BTreeMap<String, Vec<String>>,
+  /// 入口名 -> 入口模块 id
+  pub entry_modules: BTreeMap<String, String>,
+  /// 模块 id -> 按 import 顺序排列的依赖模块 id，包含外部依赖
+  pub modules: BTreeMap<String, Vec<String>>,
+  pub warnings: Vec<String>,
+}
+
+impl BuildOutput {
+  /// 资源的文本内容，不存在或者不是文本时返回 `None`
+  pub fn content(&self, name: &str) -> Option<&str> {
+    self
+      .resources
+      .get(name)
+      .and_then(|resource| resource.content.as_text())
+  }
+
+  /// 资源的二进制内容，不存在时返回 `None`
+  pub fn bytes(&self, name: &str) -> Option<&[u8]> {
+    self
+      .resources
+      .get(name)
+      .map(|resource| resource.content.as_bytes())
+  }
+}
+
+impl Compiler {
+  /// 获取最近一次编译的产物。配合 `output.write = false` 使用时，可以在不写入文件系统的情况下检查产物
+  pub fn output(&self) -> BuildOutput {
+    let module_graph = self.context.module_graph.read().unwrap();
+    let module_group_map = self.context.module_group_map.read().unwrap();
+    let resource_pot_map = self.context.resource_pot_map.read().unwrap();
+    let resource_map = self.context.resource_map.read().unwrap();
+
+    let resources = resource_map
+      .values()
+      .map(|resource| (resource.name.clone(), OutputResource::from(resource)))
+      .collect();
+
+    let entries = module_graph
+      .entries
+      .iter()
+      .filter_map(|(entry_id, entry_name)| {
+        let module_group = module_group_map.get(entry_id)?;
+        let resources = module_group
+          .resource_pot_ids()
+          .iter()
+          .filter_map(|resource_pot_id| resource_pot_map.get(resource_pot_id))
+          .flat_map(|resource_pot| &resource_pot.resource_ids)
+          .filter_map(|resource_id| resource_map.get(resource_id))
+          .collect::<Vec<_>>();
+        // 单独输出的运行时需要在入口 JS 之前加载
+        let has_js = resources
+          .iter()
+          .any(|resource| resource.resource_kind == ResourceKind::Js);
+        let runtime_resources = resource_map
+          .values()
+          .filter(|resource| has_js && resource.resource_kind == ResourceKind::Runtime);
+        let resource_names = runtime_resources
+          .chain(resources)
+          .map(|resource| resource.name.clone())
+          .collect();
+
+        Some((entry_name.clone(), resource_names))
+      })
+      .collect();
+
+    let entry_modules = module_graph
+      .entries
+      .iter()
+      .map(|(entry_id, entry_name)| (entry_name.clone(), entry_id.clone()))
+      .collect();
+
+    let modules = module_graph
+      .module_ids()
+      .map(|id| {
+        let dependencies = module_graph
+          .dependencies(&id)
+          .unwrap_or_default()
+          .into_iter()
+          .map(|(dep_id, _)| dep_id)
+          .collect();
+        (id, dependencies)
+      })
+      .collect();
+
+    BuildOutput {
+      resources,
+      entries,
+      entry_modules,
+      modules,
+      warnings: self.context.warnings.read().unwrap().clone(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::BTreeMap,
+    fs,
+    sync::{
+      atomic::{AtomicUsize, Ordering},
+      Arc,
+    },
+  };
+
+  use crate::{
+    config::{CacheConfig, Config, OutputConfig},
+    context::CompilationContext,
+    error::Result,
+    plugin::{Plugin, TransformHookParams, TransformHookResult},
+    resource::resource::ResourceKind,
+    Compiler,
+  };
+
+  /// transform 时为 `./index.js` 输出一个文件并在代码中引用，generate_end 时再输出一个文件
+  struct PluginEmitFile;
+
+  impl Plugin for PluginEmitFile {
+    fn name(&self) -> &str {
+      "TestPluginEmitFile"
+    }
+
+    fn transform(
+      &self,
+      params: &TransformHookParams,
+      context: &Arc<CompilationContext>,
+    ) -> Result<Option<TransformHookResult>> {
+      if params.id != "./index.js" {
+        return Ok(None);
+      }
+
+      let name = context.emit_file(
+        "assets/data.[hash].txt",
+        "hello".to_string(),
+        ResourceKind::Asset,
+      );
+
+      Ok(Some(TransformHookResult {
+        content: format!(
+          "{}\nconsole.log('{name}');",
+          params.content.as_text().unwrap()
+        )
+        .into(),
+        module_kind: None,
+        source_map: None,
+      }))
+    }
+
+    fn generate_end(&self, context: &Arc<CompilationContext>) -> Result<()> {
+      context.emit_file("stats.txt", "done".to_string(), ResourceKind::Asset);
+      Ok(())
+    }
+  }
+
+  /// 输出一个二进制文件
+  struct PluginEmitBinary;
+
+  impl Plugin for PluginEmitBinary {
+    fn name(&self) -> &str {
+      "TestPluginEmitBinary"
+    }
+
+    fn generate_end(&self, context: &Arc<CompilationContext>) -> Result<()> {
+      context.emit_file("logo.png", PNG_HEADER.to_vec(), ResourceKind::Asset);
+      Ok(())
+    }
+  }
+
+  /// generate_end 时输出一个以编译次数命名的文件
+  #[derive(Default)]
+  struct PluginEmitBuildNumber {
+    count: AtomicUsize,
+  }
+
+  impl Plugin for PluginEmitBuildNumber {
+    fn name(&self) -> &str {
+      "TestPluginEmitBuildNumber"
+    }
+
+    fn generate_end(&self, context: &Arc<CompilationContext>) -> Result<()> {
+      let count = self.count.fetch_add(1, Ordering::SeqCst) + 1;
+      context.emit_file(
+        &format!("build-{count}.txt"),
+        String::new(),
+        ResourceKind::Asset,
+      );
+      Ok(())
+    }
+  }
+
+  /// 不是合法 UTF-8 的内容
+  const PNG_HEADER: &[u8] = &[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0xff];
+
+  #[test]
+  fn test_output_in_memory() {
+    let root = fs::canonicalize("../../fixtures/css").unwrap();
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.html".to_string())]),
+        output: OutputConfig {
+          dir: "./dist-in-memory".to_string(),
+          write: false,
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let output = compiler.output();
+
+    assert!(!root.join("dist-in-memory").exists());
+    assert_eq!(
+      output.entries["main"],
+      vec!["./index.html", "./index.js", "./foz.css"]
+    );
+    assert_eq!(output.resources["./index.html"].kind, ResourceKind::Html);
+    assert!(output
+      .content("./index.html")
+      .unwrap()
+      .contains("./index.js"));
+    assert!(output.content("./index.js").unwrap().contains("foz"));
+    assert!(output.warnings.is_empty());
+  }
+
+  #[test]
+  fn test_emit_file() {
+    let root = fs::canonicalize("../../fixtures/basic").unwrap();
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          dir: "./dist-emit-file".to_string(),
+          write: false,
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![Arc::new(PluginEmitFile)],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let output = compiler.output();
+    // sha256("hello") 的前 8 位
+    let name = "./assets/data.2cf24dba.txt";
+
+    assert_eq!(output.content(name), Some("hello"));
+    assert_eq!(output.resources[name].kind, ResourceKind::Asset);
+    assert!(output.content("./index.js").unwrap().contains(name));
+    assert_eq!(output.content("./stats.txt"), Some("done"));
+    assert_eq!(output.entry_modules["main"], "./index.js");
+    assert_eq!(output.modules["./index.js"], vec!["./foo.js", "./bar.js"]);
+  }
+
+  #[test]
+  fn test_emitted_files_on_update() {
+    let root = fs::canonicalize("../../fixtures/basic").unwrap();
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          write: false,
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![
+        Arc::new(PluginEmitFile),
+        Arc::new(PluginEmitBuildNumber::default()),
+      ],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let name = "./assets/data.2cf24dba.txt";
+    assert!(compiler.output().resources.contains_key("./build-1.txt"));
+
+    // 没有重新 transform 的 `./index.js` 添加的资源会保留，上一次 generate_end 添加的资源不会
+    compiler.update(vec!["./foo.js".to_string()]).unwrap();
+
+    let output = compiler.output();
+
+    assert_eq!(output.content(name), Some("hello"));
+    assert!(output.resources.contains_key("./build-2.txt"));
+    assert!(!output.resources.contains_key("./build-1.txt"));
+  }
+
+  #[test]
+  fn test_emit_file_with_cache() {
+    let root = fs::canonicalize("../../fixtures/basic").unwrap();
+    let cache_dir = std::env::temp_dir().join(format!("toy-emit-cache-{}", std::process::id()));
+
+    // 第二次编译命中缓存，跳过了 transform，添加的资源从缓存中恢复
+    for _ in 0..2 {
+      let mut compiler = Compiler::new(
+        Config {
+          root: root.to_string_lossy().to_string(),
+          input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+          output: OutputConfig {
+            write: false,
+            ..OutputConfig::default()
+          },
+          cache: Some(CacheConfig {
+            dir: cache_dir.to_string_lossy().to_string(),
+          }),
+          ..Config::default()
+        },
+        vec![Arc::new(PluginEmitFile)],
+      )
+      .unwrap();
+      compiler.compile().unwrap();
+
+      assert_eq!(
+        compiler.output().content("./assets/data.2cf24dba.txt"),
+        Some("hello")
+      );
+    }
+
+    fs::remove_dir_all(cache_dir).unwrap();
+  }
+
+  #[test]
+  fn test_emit_binary_file() {
+    let root = fs::canonicalize("../../fixtures/basic").unwrap();
+    let out_dir = std::env::temp_dir().join(format!("toy-binary-{}", std::process::id()));
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          dir: out_dir.to_string_lossy().to_string(),
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![Arc::new(PluginEmitBinary)],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let output = compiler.output();
+
+    assert_eq!(output.bytes("./logo.png"), Some(PNG_HEADER));
+    assert_eq!(output.content("./logo.png"), None);
+    assert_eq!(fs::read(out_dir.join("logo.png")).unwrap(), PNG_HEADER);
+
+    fs::remove_dir_all(out_dir).unwrap();
+  }
+}
@@ -50,13 +50,39 @@ impl OxcProgram {
       source_text,
       source_type,
       program_builder: |allocator, source_text, source_type| {
-        let ret = Parser::new(&allocator.0, source_text, source_type.clone()).parse();
+        let ret = Parser::new(&allocator.0, source_text, *source_type).parse();
         OxcProgramWrapper(ret.program)
       },
     }
     .build()
   }
 
+  /// 和 `build` 相同，但是源码存在语法错误时返回错误信息
+  pub fn try_build(source_text: String, source_type: SourceType) -> Result<Self, String> {
+    OxcProgramTryBuilder {
+      allocator: OxcAllocatorWrapper(Allocator::default()),
+      source_text,
+      source_type,
+      program_builder: |allocator, source_text, source_type| {
+        let ret = Parser::new(&allocator.0, source_text, *source_type).parse();
+
+        if ret.errors.is_empty() {
+          Ok(OxcProgramWrapper(ret.program))
+        } else {
+          Err(
+            ret
+              .errors
+              .iter()
+              .map(|err| err.to_string())
+              .collect::<Vec<_>>()
+              .join("\n"),
+          )
+        }
+      },
+    }
+    .try_build()
+  }
+
   pub fn copy_program(&self) -> Program {
     self.with_program(|program_wrapper| unsafe { std::mem::transmute_copy(&program_wrapper.0) })
   }
@@ -0,0 +1,166 @@
+use regex::Regex;
+
+use crate::{
+  module::{module::ModuleKind, module_graph::ModuleGraph, module_group::ModuleGroupMap},
+  resource::{
+    resource::ResourceMap,
+    resource_pot::{ResourcePot, ResourcePotMap},
+  },
+};
+
+use super::{
+  plugin_container::Hook, AnalyzeDepsHookParams, LoadHookParams, ParseHookParams,
+  ResolveHookParams, TransformHookParams,
+};
+
+/// 插件关心的钩子和模块。
+/// `PluginContainer` 会根据 `hooks` 预先计算每个钩子需要调用的插件，
+/// 并在调用模块相关的钩子（load、transform、parse、analyze_deps）前跳过不匹配的插件
+#[derive(Debug, Default)]
+pub struct PluginFilter {
+  /// 关心的钩子，例如 `Hook::Load`、`Hook::Transform`，为空时表示所有钩子
+  pub hooks: Vec<Hook>,
+  /// 模块 id（resolve 钩子中为 import 的 source）需要匹配其中一个正则，为空时不限制
+  pub include: Vec<Regex>,
+  /// 模块 id 匹配其中一个正则时跳过
+  pub exclude: Vec<Regex>,
+  /// 关心的模块类型，为空时不限制
+  pub module_kinds: Vec<ModuleKind>,
+}
+
+impl PluginFilter {
+  pub fn has_hook(&self, hook: Hook) -> bool {
+    self.hooks.is_empty() || self.hooks.contains(&hook)
+  }
+
+  /// 钩子参数对应的模块是否匹配。没有对应模块的钩子总是匹配
+  pub fn matches<T: HookTarget + ?Sized>(&self, target: &T) -> bool {
+    if let Some(module_id) = target.module_id() {
+      if !self.include.is_empty() && !self.include.iter().any(|re| re.is_match(&module_id)) {
+        return false;
+      }
+
+      if self.exclude.iter().any(|re| re.is_match(&module_id)) {
+        return false;
+      }
+    }
+
+    match target.module_kind() {
+      Some(module_kind) => self.module_kinds.is_empty() || self.module_kinds.contains(&module_kind),
+      None => true,
+    }
+  }
+}
+
+/// 钩子参数对应的模块，用于插件过滤、profile 事件和 panic 错误
+pub trait HookTarget {
+  fn module_id(&self) -> Option<String> {
+    None
+  }
+
+  fn module_kind(&self) -> Option<ModuleKind> {
+    None
+  }
+}
+
+impl HookTarget for ResolveHookParams {
+  fn module_id(&self) -> Option<String> {
+    Some(self.source.clone())
+  }
+}
+
+impl HookTarget for LoadHookParams {
+  fn module_id(&self) -> Option<String> {
+    Some(self.id.clone())
+  }
+
+  fn module_kind(&self) -> Option<ModuleKind> {
+    Some(self.module_kind.clone())
+  }
+}
+
+impl HookTarget for TransformHookParams {
+  fn module_id(&self) -> Option<String> {
+    Some(self.id.clone())
+  }
+
+  fn module_kind(&self) -> Option<ModuleKind> {
+    Some(self.module_kind.clone())
+  }
+}
+
+impl HookTarget for ParseHookParams {
+  fn module_id(&self) -> Option<String> {
+    Some(self.id.clone())
+  }
+
+  fn module_kind(&self) -> Option<ModuleKind> {
+    Some(self.module_kind.clone())
+  }
+}
+
+impl HookTarget for AnalyzeDepsHookParams<'_> {
+  fn module_id(&self) -> Option<String> {
+    Some(self.module.id.clone())
+  }
+
+  fn module_kind(&self) -> Option<ModuleKind> {
+    Some(self.module.kind.clone())
+  }
+}
+
+impl HookTarget for ResourcePot {
+  fn module_id(&self) -> Option<String> {
+    Some(self.id.clone())
+  }
+}
+
+impl HookTarget for ModuleGraph {}
+
+impl HookTarget for ModuleGroupMap {}
+
+impl HookTarget for ResourceMap {}
+
+impl HookTarget for ResourcePotMap {}
+
+impl HookTarget for [String] {}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use super::*;
+
+  fn transform_params(id: &str, module_kind: ModuleKind) -> TransformHookParams {
+    TransformHookParams {
+      id: id.to_string(),
+      query: HashMap::new(),
+      content: "".into(),
+      module_kind,
+    }
+  }
+
+  #[test]
+  fn test_plugin_filter() {
+    let filter = PluginFilter {
+      hooks: vec![Hook::Transform],
+      include: vec![Regex::new(r"^\./src/").unwrap()],
+      exclude: vec![Regex::new(r"\.test\.ts$").unwrap()],
+      module_kinds: vec![ModuleKind::Ts],
+    };
+
+    assert!(filter.has_hook(Hook::Transform));
+    assert!(!filter.has_hook(Hook::Load));
+    assert!(filter.matches(&transform_params("./src/a.ts", ModuleKind::Ts)));
+    assert!(!filter.matches(&transform_params("./lib/a.ts", ModuleKind::Ts)));
+    assert!(!filter.matches(&transform_params("./src/a.test.ts", ModuleKind::Ts)));
+    assert!(!filter.matches(&transform_params("./src/a.js", ModuleKind::Js)));
+    // 没有对应模块的钩子总是匹配
+    assert!(filter.matches(&ModuleGraph::new()));
+
+    let filter = PluginFilter::default();
+
+    assert!(filter.has_hook(Hook::Load));
+    assert!(filter.matches(&transform_params("./a.js", ModuleKind::Js)));
+  }
+}
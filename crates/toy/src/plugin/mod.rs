@@ -1,7 +1,9 @@
+use serde::{Deserialize, Serialize};
 use std::{any::Any, collections::HashMap, sync::Arc};
 
 use crate::{
   config::Config,
+  content::Content,
   context::CompilationContext,
   error::Result,
   module::{
@@ -16,8 +18,13 @@ use crate::{
   },
 };
 
+pub mod filter;
+pub mod order;
 pub mod plugin_container;
 
+use filter::PluginFilter;
+use order::PluginEnforce;
+
 pub const DEFAULT_PRIORITY: i32 = 100;
 
 #[derive(Debug)]
@@ -32,17 +39,21 @@ pub struct ResolveHookResult {
   pub id: String,
   pub query: HashMap<String, String>,
   pub external: bool,
+  /// 模块的类型，为 `None` 时根据 id 的扩展名推断。虚拟模块可以通过它指定类型
+  pub module_kind: Option<ModuleKind>,
 }
 
 #[derive(Debug)]
 pub struct LoadHookParams {
   pub id: String,
   pub query: HashMap<String, String>,
+  /// resolve 阶段确定的模块类型
+  pub module_kind: ModuleKind,
 }
 
 #[derive(Debug)]
 pub struct LoadHookResult {
-  pub content: String,
+  pub content: Content,
   pub module_kind: ModuleKind,
 }
 
@@ -50,13 +61,13 @@ pub struct LoadHookResult {
 pub struct TransformHookParams {
   pub id: String,
   pub query: HashMap<String, String>,
-  pub content: String,
+  pub content: Content,
   pub module_kind: ModuleKind,
 }
 
 #[derive(Debug)]
 pub struct TransformHookResult {
-  pub content: String,
+  pub content: Content,
   pub module_kind: Option<ModuleKind>,
   pub source_map: Option<String>,
 }
@@ -65,7 +76,7 @@ pub struct TransformHookResult {
 pub struct ParseHookParams {
   pub id: String,
   pub query: HashMap<String, String>,
-  pub content: String,
+  pub content: Content,
   pub module_kind: ModuleKind,
 }
 
@@ -74,7 +85,7 @@ pub struct AnalyzeDepsHookParams<'a> {
   pub deps: Vec<AnalyzeDep>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalyzeDep {
   pub source: String,
   pub resolve_kind: ResolveKind,
@@ -83,10 +94,36 @@ pub struct AnalyzeDep {
 pub trait Plugin: Any + Send + Sync {
   fn name(&self) -> &str;
 
+  /// 同一个 `enforce` 阶段中，`priority` 越小的插件越先执行
   fn priority(&self) -> i32 {
     DEFAULT_PRIORITY
   }
 
+  fn enforce(&self) -> PluginEnforce {
+    PluginEnforce::Normal
+  }
+
+  /// 需要在这些插件（按插件名）之前执行
+  fn run_before(&self) -> Vec<&str> {
+    vec![]
+  }
+
+  /// 需要在这些插件（按插件名）之后执行
+  fn run_after(&self) -> Vec<&str> {
+    vec![]
+  }
+
+  /// 插件关心的钩子和模块，默认关心所有钩子和模块。只会在创建 `PluginContainer` 时调用一次
+  fn filter(&self) -> PluginFilter {
+    PluginFilter::default()
+  }
+
+  /// 影响 transform、analyze_deps 结果的插件配置（例如插件的选项），它变化时持久化缓存会全部失效。
+  /// 默认为空，只使用插件名
+  fn cache_fingerprint(&self) -> String {
+    String::new()
+  }
+
   fn config(&self, _config: &mut Config) -> Result<()> {
     Ok(())
   }
@@ -159,6 +196,15 @@ pub trait Plugin: Any + Send + Sync {
     Ok(None)
   }
 
+  /// merge_modules 之后调用，可以增加、删除或修改 resource_pot，例如添加运行时的 resource_pot
+  fn process_resource_pots(
+    &self,
+    _resource_pot_map: &mut ResourcePotMap,
+    _context: &Arc<CompilationContext>,
+  ) -> Result<()> {
+    Ok(())
+  }
+
   fn render_resource_pot(
     &self,
     _resource_pot: &mut ResourcePot,
@@ -186,4 +232,9 @@ pub trait Plugin: Any + Send + Sync {
   fn generate_end(&self, _context: &Arc<CompilationContext>) -> Result<()> {
     Ok(())
   }
+
+  /// watch 模式下文件发生变化、重新编译之前调用，插件可以在这里清除自己的缓存状态
+  fn watch_change(&self, _paths: &[String], _context: &Arc<CompilationContext>) -> Result<()> {
+    Ok(())
+  }
 }
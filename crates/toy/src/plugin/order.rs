@@ -0,0 +1,214 @@
+use std::{
+  collections::{BTreeSet, HashMap},
+  sync::Arc,
+};
+
+use crate::error::{CompilationError, Result};
+
+use super::Plugin;
+
+/// 插件所在的阶段，`Pre` 阶段的插件最先执行，`Post` 阶段的插件最后执行
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PluginEnforce {
+  Pre,
+  #[default]
+  Normal,
+  Post,
+}
+
+/// 计算插件的执行顺序：
+/// 1. 先按 `enforce`、`priority` 排序，相同时保持传入的顺序
+/// 2. 再满足 `run_before` / `run_after` 声明的依赖，依赖的优先级高于 `enforce`，找不到的插件名会被忽略
+///
+/// 依赖之间存在环时返回 `CompilationError::PluginOrderCycle`
+pub fn sort_plugins(mut plugins: Vec<Arc<dyn Plugin>>) -> Result<Vec<Arc<dyn Plugin>>> {
+  plugins.sort_by_key(|plugin| (plugin.enforce(), plugin.priority()));
+
+  let mut name_to_indexes: HashMap<&str, Vec<usize>> = HashMap::new();
+
+  for (index, plugin) in plugins.iter().enumerate() {
+    name_to_indexes
+      .entry(plugin.name())
+      .or_default()
+      .push(index);
+  }
+
+  // edges[a] 包含 b 表示 a 需要在 b 之前执行
+  let mut edges = vec![BTreeSet::new(); plugins.len()];
+
+  for (index, plugin) in plugins.iter().enumerate() {
+    for name in plugin.run_before() {
+      for &other in name_to_indexes.get(name).into_iter().flatten() {
+        edges[index].insert(other);
+      }
+    }
+
+    for name in plugin.run_after() {
+      for &other in name_to_indexes.get(name).into_iter().flatten() {
+        edges[other].insert(index);
+      }
+    }
+  }
+
+  // 拓扑排序，每次选出可以执行的插件中排序最靠前的一个，保证结果稳定
+  let mut in_degrees = vec![0; plugins.len()];
+
+  for targets in &edges {
+    for &target in targets {
+      in_degrees[target] += 1;
+    }
+  }
+
+  let mut ready = (0..plugins.len())
+    .filter(|index| in_degrees[*index] == 0)
+    .collect::<BTreeSet<_>>();
+  let mut order = Vec::with_capacity(plugins.len());
+
+  while let Some(index) = ready.pop_first() {
+    order.push(index);
+
+    for &target in &edges[index] {
+      in_degrees[target] -= 1;
+
+      if in_degrees[target] == 0 {
+        ready.insert(target);
+      }
+    }
+  }
+
+  if order.len() < plugins.len() {
+    let cycle = find_cycle(&edges, &in_degrees)
+      .into_iter()
+      .map(|index| plugins[index].name().to_string())
+      .collect();
+
+    return Err(CompilationError::PluginOrderCycle { plugins: cycle });
+  }
+
+  Ok(
+    order
+      .into_iter()
+      .map(|index| plugins[index].clone())
+      .collect(),
+  )
+}
+
+/// 从拓扑排序后剩下的插件中找出一个环，返回的环首尾是同一个插件
+fn find_cycle(edges: &[BTreeSet<usize>], in_degrees: &[usize]) -> Vec<usize> {
+  let remaining = |index: &usize| in_degrees[*index] > 0;
+  let mut path: Vec<usize> = vec![];
+  let mut current = (0..edges.len()).find(remaining).unwrap();
+
+  // 剩下的每个插件都至少有一个前驱也在剩下的插件中，沿着前驱一定能走回已经访问过的插件
+  loop {
+    if let Some(start) = path.iter().position(|index| *index == current) {
+      let mut cycle = path[start..].to_vec();
+      cycle.push(current);
+      cycle.reverse();
+      return cycle;
+    }
+
+    path.push(current);
+    current = (0..edges.len())
+      .find(|index| remaining(index) && edges[*index].contains(&current))
+      .unwrap();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct TestPlugin {
+    name: &'static str,
+    enforce: PluginEnforce,
+    run_before: Vec<&'static str>,
+    run_after: Vec<&'static str>,
+  }
+
+  impl Plugin for TestPlugin {
+    fn name(&self) -> &str {
+      self.name
+    }
+
+    fn enforce(&self) -> PluginEnforce {
+      self.enforce
+    }
+
+    fn run_before(&self) -> Vec<&str> {
+      self.run_before.clone()
+    }
+
+    fn run_after(&self) -> Vec<&str> {
+      self.run_after.clone()
+    }
+  }
+
+  fn plugin(name: &'static str, enforce: PluginEnforce) -> TestPlugin {
+    TestPlugin {
+      name,
+      enforce,
+      run_before: vec![],
+      run_after: vec![],
+    }
+  }
+
+  fn names(plugins: &[Arc<dyn Plugin>]) -> Vec<&str> {
+    plugins.iter().map(|plugin| plugin.name()).collect()
+  }
+
+  #[test]
+  fn test_sort_plugins() {
+    let plugins: Vec<Arc<dyn Plugin>> = vec![
+      Arc::new(plugin("resolve", PluginEnforce::Normal)),
+      Arc::new(plugin("script", PluginEnforce::Normal)),
+      Arc::new(plugin("resources", PluginEnforce::Normal)),
+      Arc::new(plugin("post", PluginEnforce::Post)),
+      Arc::new(plugin("pre", PluginEnforce::Pre)),
+      Arc::new(TestPlugin {
+        run_after: vec!["resolve", "missing"],
+        run_before: vec!["script"],
+        ..plugin("between", PluginEnforce::Normal)
+      }),
+      Arc::new(TestPlugin {
+        run_after: vec!["post"],
+        ..plugin("last", PluginEnforce::Pre)
+      }),
+    ];
+
+    assert_eq!(
+      names(&sort_plugins(plugins).unwrap()),
+      vec![
+        "pre",
+        "resolve",
+        "resources",
+        "between",
+        "script",
+        "post",
+        "last"
+      ]
+    );
+  }
+
+  #[test]
+  fn test_sort_plugins_cycle() {
+    let plugins: Vec<Arc<dyn Plugin>> = vec![
+      Arc::new(TestPlugin {
+        run_after: vec!["b"],
+        ..plugin("a", PluginEnforce::Normal)
+      }),
+      Arc::new(TestPlugin {
+        run_after: vec!["c"],
+        ..plugin("b", PluginEnforce::Normal)
+      }),
+      Arc::new(TestPlugin {
+        run_after: vec!["b"],
+        ..plugin("c", PluginEnforce::Normal)
+      }),
+    ];
+
+    let err = sort_plugins(plugins).err().unwrap();
+
+    assert_eq!(err.to_string(), "Plugin order has a cycle: b -> c -> b");
+  }
+}
@@ -1,45 +1,113 @@
 use crate::{
   config::Config,
+  content::Content,
   context::CompilationContext,
-  error::Result,
+  error::{panic_message, CompilationError, Result},
   module::{
     module::{Module, ModuleKind},
     module_graph::ModuleGraph,
     module_group::ModuleGroupMap,
   },
   resource::{
-    resource::{Resource, ResourceMap},
+    resource::ResourceMap,
     resource_pot::{ResourcePot, ResourcePotMap},
   },
 };
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use std::{collections::HashMap, sync::Arc};
+use std::{
+  collections::HashMap,
+  fmt,
+  panic::{self, AssertUnwindSafe},
+  sync::Arc,
+  thread,
+  time::Instant,
+};
 
 use super::{
+  filter::{HookTarget, PluginFilter},
+  order::sort_plugins,
   AnalyzeDepsHookParams, LoadHookParams, LoadHookResult, ParseHookParams, Plugin,
   ResolveHookParams, ResolveHookResult, TransformHookParams,
 };
 
 #[derive(Debug)]
 pub struct PluginContainerTransformHookResult {
-  pub content: String,
+  pub content: Content,
   pub module_kind: ModuleKind,
   pub source_map_chain: Vec<String>,
 }
 
+macro_rules! hooks {
+  ($($hook:ident => $name:ident),+ $(,)?) => {
+    /// 所有会被 `PluginContainer` 调用的钩子，用于 `PluginFilter::hooks`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum Hook {
+      $($hook),+
+    }
+
+    impl Hook {
+      pub const ALL: &'static [Hook] = &[$(Hook::$hook),+];
+
+      /// 钩子名，与 `Plugin` 中对应的方法名相同
+      pub fn as_str(&self) -> &'static str {
+        match self {
+          $(Hook::$hook => stringify!($name)),+
+        }
+      }
+    }
+  };
+}
+
+hooks! {
+  BuildStart => build_start,
+  Resolve => resolve,
+  Load => load,
+  Transform => transform,
+  Parse => parse,
+  AnalyzeDeps => analyze_deps,
+  BuildEnd => build_end,
+  GenerateStart => generate_start,
+  AnalyzeModuleGraph => analyze_module_graph,
+  MergeModules => merge_modules,
+  ProcessResourcePots => process_resource_pots,
+  RenderResourcePot => render_resource_pot,
+  GenerateResources => generate_resources,
+  WriteResources => write_resources,
+  GenerateEnd => generate_end,
+  WatchChange => watch_change,
+}
+
+impl fmt::Display for Hook {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
 pub struct PluginContainer {
   plugins: Vec<Arc<dyn Plugin>>,
+  filters: Vec<PluginFilter>,
+  /// 钩子 -> 关心该钩子的插件在 `plugins` 中的下标，按插件顺序排列
+  hook_plugins: HashMap<Hook, Vec<usize>>,
 }
 
 macro_rules! hook_first {
-  ($func:ident, $params:ident: $ty:ty, $ret_ty:ty) => {
+  ($hook:ident, $func:ident, $params:ident: $ty:ty, $ret_ty:ty) => {
     pub fn $func(
       &self,
       $params: $ty,
       context: &Arc<CompilationContext>,
     ) -> Result<Option<$ret_ty>> {
-      for plugin in &self.plugins {
-        let ret = plugin.$func($params, context)?;
+      for (plugin, filter) in self.hook_plugins(Hook::$hook) {
+        if !filter.matches(&*$params) {
+          continue;
+        }
+
+        let ret = call_hook(|| plugin.$func($params, context)).finish(
+          context,
+          plugin,
+          Hook::$hook,
+          || $params.module_id(),
+        )?;
 
         if ret.is_some() {
           return Ok(ret);
@@ -51,10 +119,15 @@ macro_rules! hook_first {
 }
 
 macro_rules! hook_serial {
-  ($func:ident, $param:ident: $ty:ty) => {
+  ($hook:ident, $func:ident, $param:ident: $ty:ty) => {
     pub fn $func(&self, $param: $ty, context: &Arc<CompilationContext>) -> Result<()> {
-      for plugin in &self.plugins {
-        plugin.$func($param, context)?;
+      for (plugin, filter) in self.hook_plugins(Hook::$hook) {
+        if !filter.matches(&*$param) {
+          continue;
+        }
+
+        call_hook(|| plugin.$func($param, context))
+          .finish(context, plugin, Hook::$hook, || $param.module_id())?;
       }
       Ok(())
     }
@@ -62,27 +135,141 @@ macro_rules! hook_serial {
 }
 
 macro_rules! hook_parallel {
-  ($func:ident) => {
+  ($hook:ident, $func:ident) => {
     pub fn $func(&self, context: &Arc<CompilationContext>) -> Result<()> {
       self
-        .plugins
+        .hook_plugins(Hook::$hook)
+        .collect::<Vec<_>>()
         .par_iter()
-        .try_for_each(|plugin| plugin.$func(context).map(|_| ()))
+        .try_for_each(|(plugin, _)| {
+          call_hook(|| plugin.$func(context)).finish(context, plugin, Hook::$hook, || None)
+        })
     }
   };
-  ($func:ident, $($params:ident: $ty:ty),+) => {
+  ($hook:ident, $func:ident, $($params:ident: $ty:ty),+) => {
     pub fn $func(&self, $($params: $ty),+, context: &Arc<CompilationContext>) -> Result<()> {
       self
-        .plugins
+        .hook_plugins(Hook::$hook)
+        .collect::<Vec<_>>()
         .par_iter()
-        .try_for_each(|plugin| plugin.$func($($params),+, context).map(|_| ()))
+        .try_for_each(|(plugin, _)| {
+          call_hook(|| plugin.$func($($params),+, context))
+            .finish(context, plugin, Hook::$hook, || None)
+        })
     }
   };
 }
 
+/// 调用插件的钩子，钩子中的 panic 会被捕获，不会导致进程退出。
+/// 需要再调用 `HookCall::finish` 得到钩子的结果
+fn call_hook<T>(call: impl FnOnce() -> Result<T>) -> HookCall<T> {
+  HookCall {
+    start: Instant::now(),
+    ret: panic::catch_unwind(AssertUnwindSafe(call)),
+  }
+}
+
+/// 一次已经结束的钩子调用
+struct HookCall<T> {
+  start: Instant,
+  ret: thread::Result<Result<T>>,
+}
+
+impl<T> HookCall<T> {
+  /// - 捕获到的 panic 转换为 `CompilationError::PluginPanic`
+  /// - 开启 profile 时记录耗时
+  ///
+  /// `module_id` 只在开启 profile 或者捕获到 panic 时才会调用，避免每次调用钩子都分配字符串。
+  /// 钩子返回之后才需要它，所以钩子可以接收可变的参数
+  fn finish(
+    self,
+    context: &Arc<CompilationContext>,
+    plugin: &Arc<dyn Plugin>,
+    hook: Hook,
+    module_id: impl FnOnce() -> Option<String>,
+  ) -> Result<T> {
+    let module_id = (context.profiler.is_some() || self.ret.is_err())
+      .then(module_id)
+      .flatten();
+
+    if let Some(profiler) = &context.profiler {
+      profiler.record(plugin.name(), hook.as_str(), module_id.clone(), self.start);
+    }
+
+    self.ret.unwrap_or_else(|payload| {
+      Err(CompilationError::PluginPanic {
+        plugin: plugin.name().to_string(),
+        hook: hook.to_string(),
+        module_id,
+        message: panic_message(payload.as_ref()),
+      })
+    })
+  }
+}
+
 impl PluginContainer {
-  pub fn new(plugins: Vec<Arc<dyn Plugin>>) -> Self {
-    Self { plugins }
+  /// 按照插件的 `enforce`、`priority`、`run_before` 和 `run_after` 确定执行顺序，
+  /// 依赖之间存在环时返回错误
+  pub fn new(plugins: Vec<Arc<dyn Plugin>>) -> Result<Self> {
+    let plugins = sort_plugins(plugins)?;
+    let filters = plugins
+      .iter()
+      .map(|plugin| plugin.filter())
+      .collect::<Vec<_>>();
+    let hook_plugins = Hook::ALL
+      .iter()
+      .map(|hook| {
+        let indexes = filters
+          .iter()
+          .enumerate()
+          .filter(|(_, filter)| filter.has_hook(*hook))
+          .map(|(index, _)| index)
+          .collect();
+
+        (*hook, indexes)
+      })
+      .collect();
+
+    Ok(Self {
+      plugins,
+      filters,
+      hook_plugins,
+    })
+  }
+
+  /// 关心 `hook` 的插件及其过滤条件
+  fn hook_plugins(&self, hook: Hook) -> impl Iterator<Item = (&Arc<dyn Plugin>, &PluginFilter)> {
+    self
+      .hook_plugins
+      .get(&hook)
+      .map(|indexes| indexes.as_slice())
+      .unwrap_or_default()
+      .iter()
+      .map(|index| (&self.plugins[*index], &self.filters[*index]))
+  }
+
+  /// 所有插件名，按执行顺序排列
+  pub fn plugin_names(&self) -> Vec<&str> {
+    self.plugins.iter().map(|plugin| plugin.name()).collect()
+  }
+
+  /// 所有插件的名称和 `cache_fingerprint`，用于持久化缓存的 key
+  pub fn cache_fingerprint(&self) -> String {
+    self
+      .plugins
+      .iter()
+      .map(|plugin| format!("{}\0{}", plugin.name(), plugin.cache_fingerprint()))
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+
+  /// 调用 `hook` 时会尝试的插件名，用于错误信息
+  pub fn tried_plugin_names<T: HookTarget + ?Sized>(&self, hook: Hook, target: &T) -> Vec<String> {
+    self
+      .hook_plugins(hook)
+      .filter(|(_, filter)| filter.matches(target))
+      .map(|(plugin, _)| plugin.name().to_string())
+      .collect()
   }
 
   pub fn config(&self, config: &mut Config) -> Result<()> {
@@ -92,11 +279,11 @@ impl PluginContainer {
     Ok(())
   }
 
-  hook_parallel!(build_start);
+  hook_parallel!(BuildStart, build_start);
 
-  hook_first!(resolve, params: &ResolveHookParams, ResolveHookResult);
+  hook_first!(Resolve, resolve, params: &ResolveHookParams, ResolveHookResult);
 
-  hook_first!(load, params: &LoadHookParams, LoadHookResult);
+  hook_first!(Load, load, params: &LoadHookParams, LoadHookResult);
 
   pub fn transform(
     &self,
@@ -109,8 +296,19 @@ impl PluginContainer {
       source_map_chain: vec![],
     };
 
-    for plugin in &self.plugins {
-      if let Some(plugin_ret) = plugin.transform(&params, context)? {
+    for (plugin, filter) in self.hook_plugins(Hook::Transform) {
+      if !filter.matches(&params) {
+        continue;
+      }
+
+      let plugin_ret = call_hook(|| plugin.transform(&params, context)).finish(
+        context,
+        plugin,
+        Hook::Transform,
+        || params.module_id(),
+      )?;
+
+      if let Some(plugin_ret) = plugin_ret {
         params.content = plugin_ret.content;
 
         if let Some(module_kind) = plugin_ret.module_kind {
@@ -129,23 +327,27 @@ impl PluginContainer {
     Ok(ret)
   }
 
-  hook_first!(parse, params: &ParseHookParams, Module);
+  hook_first!(Parse, parse, params: &ParseHookParams, Module);
+
+  hook_serial!(AnalyzeDeps, analyze_deps, params: &mut AnalyzeDepsHookParams);
+
+  hook_parallel!(BuildEnd, build_end);
 
-  hook_serial!(analyze_deps, params: &mut AnalyzeDepsHookParams);
+  hook_parallel!(GenerateStart, generate_start);
 
-  hook_parallel!(build_end);
+  hook_first!(AnalyzeModuleGraph, analyze_module_graph, module_graph: &mut ModuleGraph, ModuleGroupMap);
 
-  hook_parallel!(generate_start);
+  hook_first!(MergeModules, merge_modules, module_group_map: &mut ModuleGroupMap, ResourcePotMap);
 
-  hook_first!(analyze_module_graph, module_graph: &mut ModuleGraph, ModuleGroupMap);
+  hook_serial!(ProcessResourcePots, process_resource_pots, resource_pot_map: &mut ResourcePotMap);
 
-  hook_first!(merge_modules, module_group_map: &mut ModuleGroupMap, ResourcePotMap);
+  hook_serial!(RenderResourcePot, render_resource_pot, resource_pot: &mut ResourcePot);
 
-  hook_serial!(render_resource_pot, resource_pot: &mut ResourcePot);
+  hook_first!(GenerateResources, generate_resources, resource_pot: &mut ResourcePot, ResourceMap);
 
-  hook_first!(generate_resources, resource_pot: &mut ResourcePot, HashMap<String, Resource>);
+  hook_serial!(WriteResources, write_resources, resources: &mut ResourceMap);
 
-  hook_serial!(write_resources, resources: &mut ResourceMap);
+  hook_parallel!(GenerateEnd, generate_end);
 
-  hook_parallel!(generate_end);
+  hook_serial!(WatchChange, watch_change, paths: &[String]);
 }
@@ -0,0 +1,226 @@
+use std::{collections::BTreeMap, fs::read, sync::Arc};
+
+use crate::{
+  context::CompilationContext,
+  error::{CompilationError, Result},
+  module::{
+    is_virtual_module,
+    module::{AssetModuleMeta, Module, ModuleKind, ModuleMeta},
+  },
+  plugin::{
+    filter::PluginFilter, plugin_container::Hook, LoadHookParams, LoadHookResult, ParseHookParams,
+    Plugin,
+  },
+  resource::{
+    resource::{Resource, ResourceKind, ResourceMap},
+    resource_pot::{AssetResourcePotMeta, ResourcePot, ResourcePotKind, ResourcePotMeta},
+  },
+  utils::fulfill_root_prefix,
+};
+
+/// 图片、字体、wasm 等静态资源（`ModuleKind::Asset`）。
+/// 以 `Content::Bytes` 读取，经过 transform 之后每个模块原样输出为一个 `ResourceKind::Asset` 资源。
+///
+/// JS 中的 `import './logo.png'` 只会把文件输出到产物中，不会导出资源的路径
+pub struct PluginAsset {}
+
+impl PluginAsset {
+  pub fn new() -> Self {
+    Self {}
+  }
+}
+
+impl Plugin for PluginAsset {
+  fn name(&self) -> &str {
+    "ToyPluginAsset"
+  }
+
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: vec![
+        Hook::Load,
+        Hook::Parse,
+        Hook::RenderResourcePot,
+        Hook::GenerateResources,
+      ],
+      module_kinds: vec![ModuleKind::Asset],
+      ..PluginFilter::default()
+    }
+  }
+
+  fn load(
+    &self,
+    params: &LoadHookParams,
+    context: &Arc<CompilationContext>,
+  ) -> Result<Option<LoadHookResult>> {
+    // 虚拟模块没有对应的文件，由提供它的插件负责 load
+    if params.module_kind.is_asset() && !is_virtual_module(&params.id) {
+      let path = fulfill_root_prefix(&params.id, &context.config.root);
+
+      let content = read(&path).map_err(|err| CompilationError::LoadError {
+        id: params.id.to_string(),
+        source: Some(Box::new(err)),
+      })?;
+
+      context.add_watch_file(&path);
+
+      return Ok(Some(LoadHookResult {
+        content: content.into(),
+        module_kind: params.module_kind.clone(),
+      }));
+    }
+
+    Ok(None)
+  }
+
+  fn parse(
+    &self,
+    params: &ParseHookParams,
+    _context: &Arc<CompilationContext>,
+  ) -> Result<Option<Module>> {
+    if params.module_kind.is_asset() {
+      let module = Module::new(
+        params.id.to_string(),
+        params.module_kind.clone(),
+        Some(ModuleMeta::Asset(AssetModuleMeta {
+          content: params.content.clone(),
+        })),
+      );
+
+      return Ok(Some(module));
+    }
+
+    Ok(None)
+  }
+
+  fn render_resource_pot(
+    &self,
+    resource_pot: &mut ResourcePot,
+    context: &Arc<CompilationContext>,
+  ) -> Result<()> {
+    if matches!(resource_pot.kind, ResourcePotKind::Asset) {
+      let module_graph = context.module_graph.read().unwrap();
+      let assets = resource_pot
+        .module_ids
+        .iter()
+        .map(|module_id| {
+          let module = module_graph.module(module_id).unwrap();
+          (module_id.clone(), module.meta.as_asset().content.clone())
+        })
+        .collect::<BTreeMap<_, _>>();
+
+      resource_pot.meta = ResourcePotMeta::Asset(AssetResourcePotMeta { assets });
+    }
+
+    Ok(())
+  }
+
+  fn generate_resources(
+    &self,
+    resource_pot: &mut ResourcePot,
+    _context: &Arc<CompilationContext>,
+  ) -> Result<Option<ResourceMap>> {
+    if let ResourcePotMeta::Asset(meta) = &resource_pot.meta {
+      let mut resource_map = ResourceMap::new();
+
+      for (module_id, content) in &meta.assets {
+        resource_map.insert(
+          module_id.clone(),
+          Resource {
+            name: module_id.clone(),
+            content: content.clone(),
+            resource_kind: ResourceKind::Asset,
+            resource_pot_id: resource_pot.id.clone(),
+            emitted: false,
+          },
+        );
+        resource_pot.resource_ids.push(module_id.clone());
+      }
+
+      return Ok(Some(resource_map));
+    }
+
+    Ok(None)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+
+  use crate::{
+    config::{Config, OutputConfig},
+    content::Content,
+    plugin::{TransformHookParams, TransformHookResult},
+    Compiler,
+  };
+
+  use super::*;
+
+  /// 在静态资源末尾追加一个字节，检查 transform 收到的是原始的二进制内容
+  struct PluginAppendByte;
+
+  impl Plugin for PluginAppendByte {
+    fn name(&self) -> &str {
+      "TestPluginAppendByte"
+    }
+
+    fn transform(
+      &self,
+      params: &TransformHookParams,
+      _context: &Arc<CompilationContext>,
+    ) -> Result<Option<TransformHookResult>> {
+      let Content::Bytes(bytes) = &params.content else {
+        return Ok(None);
+      };
+
+      let mut bytes = bytes.clone();
+      bytes.push(0xff);
+
+      Ok(Some(TransformHookResult {
+        content: bytes.into(),
+        module_kind: None,
+        source_map: None,
+      }))
+    }
+  }
+
+  #[test]
+  fn test_asset() {
+    let root = fs::canonicalize("../../fixtures/asset").unwrap();
+    let original = fs::read(root.join("logo.png")).unwrap();
+
+    assert!(std::str::from_utf8(&original).is_err());
+
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          dir: "./dist-asset".to_string(),
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![Arc::new(PluginAppendByte)],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let out_dir = root.join("dist-asset");
+    let mut expected = original.clone();
+    expected.push(0xff);
+
+    assert_eq!(fs::read(out_dir.join("logo.png")).unwrap(), expected);
+    assert_eq!(
+      compiler.output().resources["./logo.png"].kind,
+      ResourceKind::Asset
+    );
+    // JS 中不会 require 静态资源
+    assert!(!fs::read_to_string(out_dir.join("index.js"))
+      .unwrap()
+      .contains("./logo.png"));
+
+    fs::remove_dir_all(out_dir).unwrap();
+  }
+}
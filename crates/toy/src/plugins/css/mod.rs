@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs::read_to_string, sync::Arc};
+use std::{fs::read_to_string, sync::Arc};
 
 use lightningcss::{
   printer::PrinterOptions,
@@ -12,8 +12,14 @@ use crate::{
   context::CompilationContext,
   error::{CompilationError, Result},
   lightningcss::LightningStyleSheet,
-  module::module::{CssModuleMeta, Module, ModuleKind, ModuleMeta},
-  plugin::{AnalyzeDepsHookParams, LoadHookParams, LoadHookResult, Plugin},
+  module::{
+    is_virtual_module,
+    module::{CssModuleMeta, Module, ModuleKind, ModuleMeta},
+  },
+  plugin::{
+    filter::PluginFilter, plugin_container::Hook, AnalyzeDepsHookParams, LoadHookParams,
+    LoadHookResult, Plugin,
+  },
   resource::{
     resource::{Resource, ResourceKind, ResourceMap},
     resource_pot::{CssResourcePotMeta, ResourcePot, ResourcePotKind, ResourcePotMeta},
@@ -38,23 +44,40 @@ impl Plugin for PluginCss {
     "ToyPluginCss"
   }
 
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: vec![
+        Hook::Load,
+        Hook::Parse,
+        Hook::AnalyzeDeps,
+        Hook::RenderResourcePot,
+        Hook::GenerateResources,
+      ],
+      module_kinds: vec![ModuleKind::Css],
+      ..PluginFilter::default()
+    }
+  }
+
   fn load(
     &self,
     params: &LoadHookParams,
     context: &Arc<CompilationContext>,
   ) -> Result<Option<LoadHookResult>> {
-    let module_kind = ModuleKind::from_file_path(&params.id);
+    let module_kind = params.module_kind.clone();
 
-    if module_kind.is_style() {
+    // 虚拟模块没有对应的文件，由提供它的插件负责 load
+    if module_kind.is_style() && !is_virtual_module(&params.id) {
       let path = fulfill_root_prefix(&params.id, &context.config.root);
 
-      let content = read_to_string(path).map_err(|err| CompilationError::LoadError {
+      let content = read_to_string(&path).map_err(|err| CompilationError::LoadError {
         id: params.id.to_string(),
         source: Some(Box::new(err)),
       })?;
 
+      context.add_watch_file(&path);
+
       return Ok(Some(LoadHookResult {
-        content,
+        content: content.into(),
         module_kind,
       }));
     }
@@ -68,7 +91,14 @@ impl Plugin for PluginCss {
     _context: &Arc<CompilationContext>,
   ) -> Result<Option<crate::module::module::Module>> {
     if params.module_kind.is_style() {
-      let style_sheet = LightningStyleSheet::build(params.content.clone(), params.id.clone());
+      let style_sheet = LightningStyleSheet::build(
+        params.content.try_as_text(&params.id)?.to_string(),
+        params.id.clone(),
+      )
+      .map_err(|message| CompilationError::ParseError {
+        id: params.id.clone(),
+        message,
+      })?;
 
       let module = Module::new(
         params.id.to_string(),
@@ -154,14 +184,14 @@ impl Plugin for PluginCss {
     _context: &Arc<CompilationContext>,
   ) -> Result<Option<ResourceMap>> {
     if let ResourcePotMeta::Css(css_resource_pot_meta) = &resource_pot.meta {
-      let mut resource_map: ResourceMap = HashMap::new();
+      let mut resource_map = ResourceMap::new();
       let resource_id = resource_pot.id.clone();
 
       resource_map.insert(
         resource_id.clone(),
         Resource {
           name: resource_id.clone(),
-          content: css_resource_pot_meta.code.clone(),
+          content: css_resource_pot_meta.code.clone().into(),
           resource_kind: ResourceKind::Css,
           resource_pot_id: resource_id.clone(),
           emitted: false,
@@ -0,0 +1,560 @@
+use std::{
+  collections::HashMap,
+  io::{self, BufRead, BufReader, Write},
+  process::{Child, ChildStdin, Command, Stdio},
+  sync::{
+    mpsc::{self, Receiver, RecvTimeoutError},
+    Arc, Mutex, PoisonError,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+  context::CompilationContext,
+  error::{CompilationError, Result},
+  module::{module::ModuleKind, ResolveKind},
+  plugin::{
+    filter::PluginFilter, order::PluginEnforce, plugin_container::Hook, LoadHookParams,
+    LoadHookResult, Plugin, ResolveHookParams, ResolveHookResult, TransformHookParams,
+    TransformHookResult, DEFAULT_PRIORITY,
+  },
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct InitializeResult {
+  name: String,
+  hooks: Vec<String>,
+  #[serde(default)]
+  enforce: Option<String>,
+  #[serde(default)]
+  priority: Option<i32>,
+  #[serde(default)]
+  cache_key: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalResolveParams<'a> {
+  source: &'a str,
+  importer: Option<&'a str>,
+  kind: &'a ResolveKind,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalResolveResult {
+  id: String,
+  #[serde(default)]
+  query: HashMap<String, String>,
+  #[serde(default)]
+  external: bool,
+  #[serde(default)]
+  module_kind: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalLoadParams<'a> {
+  id: &'a str,
+  query: &'a HashMap<String, String>,
+  module_kind: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalLoadResult {
+  content: String,
+  module_kind: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalTransformParams<'a> {
+  id: &'a str,
+  query: &'a HashMap<String, String>,
+  content: &'a str,
+  module_kind: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExternalTransformResult {
+  content: String,
+  #[serde(default)]
+  module_kind: Option<String>,
+  #[serde(default)]
+  source_map: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+  id: u64,
+  #[serde(default)]
+  result: Value,
+  #[serde(default)]
+  error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+  message: String,
+}
+
+/// 关闭 stdin 之后等待子进程自行退出的时间，超过后结束子进程
+const EXIT_GRACE_PERIOD: Duration = Duration::from_millis(500);
+
+/// 子进程及其标准输入输出
+struct ExternalProcess {
+  child: Child,
+  stdin: Option<ChildStdin>,
+  /// 由单独的线程读取 stdout，每次收到一行，子进程退出后断开
+  lines: Receiver<io::Result<String>>,
+  next_id: u64,
+}
+
+/// 进程外插件：启动一个子进程，通过 stdin / stdout 以 JSON-RPC 2.0 协议通信，
+/// 每条消息是一行 JSON。子进程的 stderr 会直接输出到当前进程的 stderr，可以用来打印日志。
+///
+/// 启动后 toy 会先发送 `initialize` 请求，子进程返回插件信息：
+///
+/// ```text
+/// --> {"jsonrpc":"2.0","id":0,"method":"initialize","params":{"root":"/path/to/project"}}
+/// <-- {"jsonrpc":"2.0","id":0,"result":{"name":"my-plugin","hooks":["resolve","load","transform"],"enforce":"pre","priority":100}}
+/// ```
+///
+/// `hooks` 只支持 `resolve`、`load`、`transform`，`enforce`（`pre` / `normal` / `post`）和 `priority` 可以省略。
+/// 可选的 `cacheKey` 与启动命令一起作为插件的 `cache_fingerprint`，插件的逻辑或选项变化时应该修改它。
+/// 之后每次调用钩子发送一个请求，`result` 为 `null` 表示不处理：
+///
+/// ```text
+/// --> {"jsonrpc":"2.0","id":1,"method":"resolve","params":{"source":"./a","importer":"./index.js","kind":"Import"}}
+/// <-- {"jsonrpc":"2.0","id":1,"result":{"id":"./a.js","query":{},"external":false,"moduleKind":null}}
+/// --> {"jsonrpc":"2.0","id":2,"method":"load","params":{"id":"./a.js","query":{},"moduleKind":"js"}}
+/// <-- {"jsonrpc":"2.0","id":2,"result":{"content":"export default 1","moduleKind":"js"}}
+/// --> {"jsonrpc":"2.0","id":3,"method":"transform","params":{"id":"./a.js","query":{},"content":"export default 1","moduleKind":"js"}}
+/// <-- {"jsonrpc":"2.0","id":3,"result":{"content":"export default 2","moduleKind":"js","sourceMap":null}}
+/// ```
+///
+/// 模块类型使用文件扩展名表示，例如 `js`、`ts`、`css`、`html`，`asset` 表示静态资源。
+/// 处理失败时返回 `{"jsonrpc":"2.0","id":1,"error":{"code":1,"message":"..."}}`。
+/// 编译结束、插件被释放时会关闭子进程的 stdin，子进程没有及时退出时结束子进程。
+///
+/// 同一时间只会有一个请求在处理中，子进程不需要处理并发。
+/// 超过 `request_timeout` 没有响应时请求失败，子进程会被结束，之后的请求都会失败。
+pub struct PluginExternal {
+  name: String,
+  hooks: Vec<Hook>,
+  enforce: PluginEnforce,
+  priority: i32,
+  /// 启动命令、参数以及 `initialize` 返回的 `cacheKey`
+  cache_fingerprint: String,
+  request_timeout: Duration,
+  process: Mutex<ExternalProcess>,
+}
+
+impl PluginExternal {
+  /// 等待一个请求响应的默认最长时间
+  pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+  /// 启动插件进程并完成 `initialize` 握手
+  pub fn new(command: &str, args: &[&str], root: &str) -> Result<Self> {
+    Self::new_with_timeout(command, args, root, Self::DEFAULT_REQUEST_TIMEOUT)
+  }
+
+  /// 指定等待每个请求（包括 `initialize`）响应的最长时间
+  pub fn new_with_timeout(
+    command: &str,
+    args: &[&str],
+    root: &str,
+    request_timeout: Duration,
+  ) -> Result<Self> {
+    let mut child = Command::new(command)
+      .args(args)
+      .current_dir(root)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::inherit())
+      .spawn()
+      .map_err(|err| {
+        CompilationError::GenericError(format!("Start external plugin `{command}` failed: {err}"))
+      })?;
+
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let (sender, lines) = mpsc::channel();
+
+    thread::spawn(move || {
+      for line in stdout.lines() {
+        let is_err = line.is_err();
+
+        if sender.send(line).is_err() || is_err {
+          break;
+        }
+      }
+    });
+
+    let process = ExternalProcess {
+      stdin: child.stdin.take(),
+      lines,
+      child,
+      next_id: 0,
+    };
+    let mut plugin = Self {
+      name: command.to_string(),
+      hooks: vec![],
+      enforce: PluginEnforce::Normal,
+      priority: DEFAULT_PRIORITY,
+      cache_fingerprint: String::new(),
+      request_timeout,
+      process: Mutex::new(process),
+    };
+
+    let info: InitializeResult = plugin
+      .request("initialize", json!({ "root": root }))?
+      .ok_or_else(|| plugin.error("initialize", "empty initialize result".to_string()))?;
+
+    plugin.name = info.name;
+    plugin.hooks = info
+      .hooks
+      .iter()
+      .filter_map(|hook| match hook.as_str() {
+        "resolve" => Some(Hook::Resolve),
+        "load" => Some(Hook::Load),
+        "transform" => Some(Hook::Transform),
+        _ => None,
+      })
+      .collect();
+    plugin.enforce = match info.enforce.as_deref() {
+      Some("pre") => PluginEnforce::Pre,
+      Some("post") => PluginEnforce::Post,
+      _ => PluginEnforce::Normal,
+    };
+    plugin.priority = info.priority.unwrap_or(DEFAULT_PRIORITY);
+    plugin.cache_fingerprint = format!("{command} {args:?} {}", info.cache_key.unwrap_or_default());
+
+    Ok(plugin)
+  }
+
+  /// 发送一个请求并等待响应，`result` 为 `null` 时返回 `None`
+  fn request<T: DeserializeOwned>(&self, method: &'static str, params: Value) -> Result<Option<T>> {
+    // 持有锁时不会 panic，被污染的锁中的状态仍然可用
+    let mut process = self.process.lock().unwrap_or_else(PoisonError::into_inner);
+    let id = process.next_id;
+    process.next_id += 1;
+
+    let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+    let stdin = process
+      .stdin
+      .as_mut()
+      .ok_or_else(|| self.error(method, "process is closed".to_string()))?;
+
+    writeln!(stdin, "{message}")
+      .and_then(|_| stdin.flush())
+      .map_err(|err| self.error(method, format!("write request failed: {err}")))?;
+
+    let deadline = Instant::now() + self.request_timeout;
+
+    loop {
+      let timeout = deadline.saturating_duration_since(Instant::now());
+      let line = match process.lines.recv_timeout(timeout) {
+        Ok(line) => {
+          line.map_err(|err| self.error(method, format!("read response failed: {err}")))?
+        }
+        Err(RecvTimeoutError::Timeout) => {
+          // 子进程可能已经卡住，结束它，避免之后的请求也要等到超时
+          let _ = process.child.kill();
+          return Err(self.error(
+            method,
+            format!(
+              "no response in {}ms, the process is killed",
+              self.request_timeout.as_millis()
+            ),
+          ));
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+          return Err(self.error(method, "process exited unexpectedly".to_string()));
+        }
+      };
+
+      let response: RpcResponse = serde_json::from_str(&line)
+        .map_err(|err| self.error(method, format!("invalid response `{}`: {err}", line.trim())))?;
+
+      // 忽略不是当前请求的响应，例如之前超时的请求的响应
+      if response.id != id {
+        continue;
+      }
+
+      if let Some(error) = response.error {
+        return Err(self.error(method, error.message));
+      }
+
+      if response.result.is_null() {
+        return Ok(None);
+      }
+
+      return serde_json::from_value(response.result)
+        .map(Some)
+        .map_err(|err| self.error(method, format!("invalid result: {err}")));
+    }
+  }
+
+  fn error(&self, hook: &str, message: String) -> CompilationError {
+    CompilationError::ExternalPluginError {
+      plugin: self.name.clone(),
+      hook: hook.to_string(),
+      message,
+    }
+  }
+}
+
+impl Drop for PluginExternal {
+  fn drop(&mut self) {
+    let process = self
+      .process
+      .get_mut()
+      .unwrap_or_else(PoisonError::into_inner);
+
+    // 关闭 stdin，通知子进程退出，给它一点时间清理
+    process.stdin.take();
+
+    let deadline = Instant::now() + EXIT_GRACE_PERIOD;
+
+    while Instant::now() < deadline {
+      if !matches!(process.child.try_wait(), Ok(None)) {
+        return;
+      }
+
+      thread::sleep(Duration::from_millis(10));
+    }
+
+    let _ = process.child.kill();
+    let _ = process.child.wait();
+  }
+}
+
+impl Plugin for PluginExternal {
+  fn name(&self) -> &str {
+    &self.name
+  }
+
+  fn priority(&self) -> i32 {
+    self.priority
+  }
+
+  fn enforce(&self) -> PluginEnforce {
+    self.enforce
+  }
+
+  fn cache_fingerprint(&self) -> String {
+    self.cache_fingerprint.clone()
+  }
+
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: self.hooks.clone(),
+      ..PluginFilter::default()
+    }
+  }
+
+  fn resolve(
+    &self,
+    params: &ResolveHookParams,
+    _context: &Arc<CompilationContext>,
+  ) -> Result<Option<ResolveHookResult>> {
+    let result: Option<ExternalResolveResult> = self.request(
+      "resolve",
+      json!(ExternalResolveParams {
+        source: &params.source,
+        importer: params.importer.as_deref(),
+        kind: &params.kind,
+      }),
+    )?;
+
+    Ok(result.map(|result| ResolveHookResult {
+      id: result.id,
+      query: result.query,
+      external: result.external,
+      module_kind: result.module_kind.as_deref().map(module_kind_from_str),
+    }))
+  }
+
+  fn load(
+    &self,
+    params: &LoadHookParams,
+    _context: &Arc<CompilationContext>,
+  ) -> Result<Option<LoadHookResult>> {
+    let result: Option<ExternalLoadResult> = self.request(
+      "load",
+      json!(ExternalLoadParams {
+        id: &params.id,
+        query: &params.query,
+        module_kind: module_kind_to_string(&params.module_kind),
+      }),
+    )?;
+
+    Ok(result.map(|result| LoadHookResult {
+      content: result.content.into(),
+      module_kind: module_kind_from_str(&result.module_kind),
+    }))
+  }
+
+  fn transform(
+    &self,
+    params: &TransformHookParams,
+    _context: &Arc<CompilationContext>,
+  ) -> Result<Option<TransformHookResult>> {
+    // 协议中的内容都是文本，二进制内容不会发送给进程外插件
+    let Some(content) = params.content.as_text() else {
+      return Ok(None);
+    };
+
+    let result: Option<ExternalTransformResult> = self.request(
+      "transform",
+      json!(ExternalTransformParams {
+        id: &params.id,
+        query: &params.query,
+        content,
+        module_kind: module_kind_to_string(&params.module_kind),
+      }),
+    )?;
+
+    Ok(result.map(|result| TransformHookResult {
+      content: result.content.into(),
+      module_kind: result.module_kind.as_deref().map(module_kind_from_str),
+      source_map: result.source_map,
+    }))
+  }
+}
+
+/// 协议中的模块类型，例如 `ModuleKind::Js` -> `"js"`
+fn module_kind_to_string(module_kind: &ModuleKind) -> String {
+  match module_kind {
+    ModuleKind::Html => "html".to_string(),
+    ModuleKind::Css => "css".to_string(),
+    ModuleKind::Js => "js".to_string(),
+    ModuleKind::Jsx => "jsx".to_string(),
+    ModuleKind::Ts => "ts".to_string(),
+    ModuleKind::Tsx => "tsx".to_string(),
+    ModuleKind::Asset => "asset".to_string(),
+    ModuleKind::Custom(kind) => kind.clone(),
+  }
+}
+
+fn module_kind_from_str(module_kind: &str) -> ModuleKind {
+  match module_kind {
+    "asset" => ModuleKind::Asset,
+    _ => ModuleKind::from_ext(module_kind),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::BTreeMap, fs};
+
+  use crate::{
+    config::{Config, OutputConfig},
+    utils::node_available,
+    Compiler,
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_plugin_external() {
+    if !node_available() {
+      return;
+    }
+
+    let root = fs::canonicalize("../../fixtures/external").unwrap();
+    let root = root.to_string_lossy().to_string();
+    let plugin = PluginExternal::new("node", &["./plugin.js"], &root).unwrap();
+
+    assert_eq!(plugin.name(), "test-external-plugin");
+    assert_eq!(plugin.enforce(), PluginEnforce::Pre);
+
+    let mut compiler = Compiler::new(
+      Config {
+        root,
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          write: false,
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![Arc::new(plugin)],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let code = compiler.output().content("./index.js").unwrap().to_string();
+
+    // `@/foo` 由插件 resolve，`./foo.js` 由插件 load，然后再经过插件 transform
+    assert!(code.contains("__toyRequire__('./foo.js')"));
+    assert!(code.contains("'transformed'"));
+    assert!(!code.contains("'original'"));
+  }
+
+  #[test]
+  fn test_plugin_external_error() {
+    if !node_available() {
+      return;
+    }
+
+    let root = fs::canonicalize("../../fixtures/external").unwrap();
+    let root = root.to_string_lossy().to_string();
+    let plugin = PluginExternal::new("node", &["./plugin.js"], &root).unwrap();
+    let context = Arc::new(CompilationContext::new(Config::default(), vec![]).unwrap());
+
+    let err = plugin
+      .load(
+        &LoadHookParams {
+          id: "./fail.js".to_string(),
+          query: HashMap::new(),
+          module_kind: ModuleKind::Js,
+        },
+        &context,
+      )
+      .unwrap_err();
+
+    assert_eq!(
+      err.to_string(),
+      "External plugin `test-external-plugin` failed in hook `load`: can not load ./fail.js"
+    );
+  }
+
+  #[test]
+  fn test_plugin_external_timeout() {
+    if !node_available() {
+      return;
+    }
+
+    let root = fs::canonicalize("../../fixtures/external").unwrap();
+    let root = root.to_string_lossy().to_string();
+    let plugin =
+      PluginExternal::new_with_timeout("node", &["./plugin.js"], &root, Duration::from_millis(500))
+        .unwrap();
+    let context = Arc::new(CompilationContext::new(Config::default(), vec![]).unwrap());
+    let load = |id: &str| {
+      plugin.load(
+        &LoadHookParams {
+          id: id.to_string(),
+          query: HashMap::new(),
+          module_kind: ModuleKind::Js,
+        },
+        &context,
+      )
+    };
+
+    assert_eq!(
+      load("./hang.js").unwrap_err().to_string(),
+      "External plugin `test-external-plugin` failed in hook `load`: no response in 500ms, the process is killed"
+    );
+    // 子进程已经被结束，之后的请求立即失败
+    assert!(load("./foo.js").is_err());
+  }
+}
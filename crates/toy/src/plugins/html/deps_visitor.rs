@@ -48,11 +48,8 @@ impl Visit for DepsVisitor {
 }
 
 pub fn get_script_src(el: &Element) -> Option<String> {
-  if el.tag_name.to_string() == "script" {
-    let src_attr = el
-      .attributes
-      .iter()
-      .find(|attr| attr.name.to_string() == "src");
+  if &*el.tag_name == "script" {
+    let src_attr = el.attributes.iter().find(|attr| &*attr.name == "src");
 
     if let Some(src_attr) = src_attr {
       if let Some(value) = &src_attr.value {
@@ -65,11 +62,8 @@ pub fn get_script_src(el: &Element) -> Option<String> {
 }
 
 pub fn get_link_href(el: &Element) -> Option<String> {
-  if el.tag_name.to_string() == "link" {
-    let href_attr = el
-      .attributes
-      .iter()
-      .find(|attr| attr.name.to_string() == "href");
+  if &*el.tag_name == "link" {
+    let href_attr = el.attributes.iter().find(|attr| &*attr.name == "href");
 
     if let Some(href_attr) = href_attr {
       if let Some(value) = &href_attr.value {
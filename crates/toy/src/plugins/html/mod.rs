@@ -1,4 +1,10 @@
-use std::{collections::HashMap, fs::read_to_string, path::PathBuf, sync::Arc};
+use regex::{Captures, Regex};
+use std::{
+  collections::BTreeMap,
+  fs::read_to_string,
+  path::PathBuf,
+  sync::{Arc, OnceLock},
+};
 use swc_common::{BytePos, FileName, SourceFile};
 use swc_html::{
   codegen::{
@@ -9,10 +15,18 @@ use swc_html::{
 };
 
 use crate::{
+  config::Mode,
+  content::Content,
   context::CompilationContext,
   error::{CompilationError, Result},
-  module::module::{HtmlModuleMeta, Module, ModuleKind, ModuleMeta},
-  plugin::{LoadHookParams, LoadHookResult, ParseHookParams, Plugin},
+  module::{
+    is_virtual_module,
+    module::{HtmlModuleMeta, Module, ModuleKind, ModuleMeta},
+  },
+  plugin::{
+    filter::PluginFilter, plugin_container::Hook, LoadHookParams, LoadHookResult, ParseHookParams,
+    Plugin, TransformHookParams, TransformHookResult,
+  },
   resource::{
     self,
     resource::{Resource, ResourceKind, ResourceMap},
@@ -39,23 +53,42 @@ impl Plugin for PluginHtml {
     "ToyPluginHtml"
   }
 
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: vec![
+        Hook::Load,
+        Hook::Transform,
+        Hook::Parse,
+        Hook::AnalyzeDeps,
+        Hook::RenderResourcePot,
+        Hook::GenerateResources,
+        Hook::WriteResources,
+      ],
+      module_kinds: vec![ModuleKind::Html],
+      ..PluginFilter::default()
+    }
+  }
+
   fn load(
     &self,
     params: &LoadHookParams,
     context: &Arc<CompilationContext>,
   ) -> Result<Option<LoadHookResult>> {
-    let module_kind = ModuleKind::from_file_path(&params.id);
+    let module_kind = params.module_kind.clone();
 
-    if module_kind.is_html() {
+    // 虚拟模块没有对应的文件，由提供它的插件负责 load
+    if module_kind.is_html() && !is_virtual_module(&params.id) {
       let path = fulfill_root_prefix(&params.id, &context.config.root);
 
-      let content = read_to_string(path).map_err(|err| CompilationError::LoadError {
+      let content = read_to_string(&path).map_err(|err| CompilationError::LoadError {
         id: params.id.to_string(),
         source: Some(Box::new(err)),
       })?;
 
+      context.add_watch_file(&path);
+
       return Ok(Some(LoadHookResult {
-        content,
+        content: content.into(),
         module_kind,
       }));
     }
@@ -63,6 +96,48 @@ impl Plugin for PluginHtml {
     Ok(None)
   }
 
+  /// 把 `%ENV_NAME%` 替换为 `context.env` 中的变量，另外支持 `%MODE%`、`%DEV%`、`%PROD%`，
+  /// 未定义的变量保持不变
+  fn transform(
+    &self,
+    params: &TransformHookParams,
+    context: &Arc<CompilationContext>,
+  ) -> Result<Option<TransformHookResult>> {
+    let Some(html) = params.content.as_text() else {
+      return Ok(None);
+    };
+
+    if !params.module_kind.is_html() || !html.contains('%') {
+      return Ok(None);
+    }
+
+    let mode = context.config.mode;
+    let replaced = env_variable_regex().replace_all(html, |captures: &Captures| {
+      let name = &captures[1];
+
+      match name {
+        "MODE" => mode.as_str().to_string(),
+        "DEV" => (mode == Mode::Development).to_string(),
+        "PROD" => (mode == Mode::Production).to_string(),
+        _ => context
+          .env
+          .get(name)
+          .cloned()
+          .unwrap_or_else(|| captures[0].to_string()),
+      }
+    });
+
+    if replaced == html {
+      return Ok(None);
+    }
+
+    Ok(Some(TransformHookResult {
+      content: replaced.to_string().into(),
+      module_kind: None,
+      source_map: None,
+    }))
+  }
+
   fn parse(
     &self,
     params: &ParseHookParams,
@@ -73,11 +148,15 @@ impl Plugin for PluginHtml {
         FileName::Real(PathBuf::from(&params.id)),
         false,
         FileName::Real(PathBuf::from(&params.id)),
-        params.content.clone(),
+        params.content.try_as_text(&params.id)?.to_string(),
         BytePos(1),
       );
 
-      let ast = parse_file_as_document(&source_file, ParserConfig::default(), &mut vec![]).unwrap();
+      let ast = parse_file_as_document(&source_file, ParserConfig::default(), &mut vec![])
+        .map_err(|err| CompilationError::ParseError {
+          id: params.id.clone(),
+          message: err.message().to_string(),
+        })?;
 
       let module = Module::new(
         params.id.to_string(),
@@ -137,14 +216,14 @@ impl Plugin for PluginHtml {
     _context: &Arc<CompilationContext>,
   ) -> Result<Option<ResourceMap>> {
     if let ResourcePotMeta::Html(_) = &resource_pot.meta {
-      let mut resource_map: ResourceMap = HashMap::new();
+      let mut resource_map = ResourceMap::new();
       let resource_id = resource_pot.id.clone();
 
       resource_map.insert(
         resource_id.clone(),
         Resource {
           name: resource_id.clone(),
-          content: "".to_string(), // html 资源内容会在 write_resources 时才生成
+          content: Content::default(), // html 资源内容会在 write_resources 时才生成
           resource_kind: ResourceKind::Html,
           resource_pot_id: resource_id.clone(),
           emitted: false,
@@ -166,10 +245,15 @@ impl Plugin for PluginHtml {
   ) -> Result<()> {
     let mut resource_pot_map = context.resource_pot_map.write().unwrap();
     let module_graph = context.module_graph.read().unwrap();
-    let mut html_to_dep_resource_ids = HashMap::new();
+    let mut html_to_dep_resource_ids = BTreeMap::new();
 
     for (html_resource_id, html_resource) in resources.iter() {
-      if matches!(html_resource.resource_kind, ResourceKind::Html) {
+      // 已经输出过的 html 资源（增量编译时被复用）不需要重新生成，
+      // 插件通过 `emit_file` 添加的 html 资源不属于任何 resource_pot，也不需要处理
+      if matches!(html_resource.resource_kind, ResourceKind::Html)
+        && !html_resource.emitted
+        && resource_pot_map.contains_key(&html_resource.resource_pot_id)
+      {
         let module_group_map = context.module_group_map.read().unwrap();
         let html_resource_pot = resource_pot_map
           .get(&html_resource.resource_pot_id)
@@ -203,12 +287,29 @@ impl Plugin for PluginHtml {
         let dep_resource = resources.get(&dep_resource_id).unwrap();
 
         match dep_resource.resource_kind {
-          ResourceKind::Js => js_resources.push(dep_resource.name.clone()),
+          ResourceKind::Js => {
+            let esm = resource_pot_map
+              .get(&dep_resource.resource_pot_id)
+              .is_some_and(|pot| matches!(&pot.meta, ResourcePotMeta::Js(meta) if meta.esm));
+
+            js_resources.push((dep_resource.name.clone(), esm));
+          }
           ResourceKind::Css => css_resources.push(dep_resource.name.clone()),
           _ => {}
         }
       }
 
+      // 单独输出的运行时需要在入口 JS 之前加载
+      if !js_resources.is_empty() {
+        let runtime_resources = resources
+          .values()
+          .filter(|resource| matches!(resource.resource_kind, ResourceKind::Runtime))
+          .map(|resource| (resource.name.clone(), false))
+          .collect::<Vec<_>>();
+
+        js_resources.splice(0..0, runtime_resources);
+      }
+
       let html_resource = resources.get_mut(&html_resource_id).unwrap();
       let html_resource_pot = resource_pot_map
         .get_mut(&html_resource.resource_pot_id)
@@ -242,9 +343,15 @@ impl Plugin for PluginHtml {
       html_gen.emit(document).unwrap();
 
       // 修改 html resource 的 content 字段，以让 resources 插件把内容输出到文件系统
-      html_resource.content = html_code;
+      html_resource.content = html_code.into();
     }
 
     Ok(())
   }
 }
+
+/// html 中的环境变量，例如 `%MODE%`
+fn env_variable_regex() -> &'static Regex {
+  static REGEX: OnceLock<Regex> = OnceLock::new();
+  REGEX.get_or_init(|| Regex::new(r"%([A-Za-z_][A-Za-z0-9_]*)%").unwrap())
+}
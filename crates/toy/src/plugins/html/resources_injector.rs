@@ -11,12 +11,16 @@ pub struct ResourcesInjector {
   deps: Vec<String>,
   /// 需要注入的 css 资源
   css_resources: Vec<String>,
-  /// 需要注入的 js 资源
-  js_resources: Vec<String>,
+  /// 需要注入的 js 资源，以及是否需要以 `<script type="module">` 加载
+  js_resources: Vec<(String, bool)>,
 }
 
 impl ResourcesInjector {
-  pub fn new(deps: Vec<String>, css_resources: Vec<String>, js_resources: Vec<String>) -> Self {
+  pub fn new(
+    deps: Vec<String>,
+    css_resources: Vec<String>,
+    js_resources: Vec<(String, bool)>,
+  ) -> Self {
     ResourcesInjector {
       deps,
       css_resources,
@@ -53,7 +57,7 @@ impl VisitMut for ResourcesInjector {
   fn visit_mut_element(&mut self, el: &mut Element) {
     self.visit_mut_children(&mut el.children);
 
-    if el.tag_name.to_string() == "head" {
+    if &*el.tag_name == "head" {
       // 注入 css 资源
       for css in &self.css_resources {
         el.children.push(Child::Element(create_element(
@@ -64,12 +68,16 @@ impl VisitMut for ResourcesInjector {
       }
 
       // 注入 js 资源
-      for js in &self.js_resources {
-        el.children.push(Child::Element(create_element(
-          "script",
-          Some(vec![("defer", ""), ("src", js)]),
-          None,
-        )));
+      for (js, esm) in &self.js_resources {
+        // module 脚本默认就是 defer 的
+        let attrs = if *esm {
+          vec![("type", "module"), ("src", js.as_str())]
+        } else {
+          vec![("defer", ""), ("src", js.as_str())]
+        };
+
+        el.children
+          .push(Child::Element(create_element("script", Some(attrs), None)));
       }
     }
   }
@@ -1,4 +1,6 @@
+pub mod asset;
 pub mod css;
+pub mod external;
 pub mod html;
 pub mod modules;
 pub mod resolve;
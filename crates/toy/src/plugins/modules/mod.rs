@@ -7,11 +7,11 @@ use crate::{
   context::CompilationContext,
   error::Result,
   module::{
-    module_graph::{ModuleGraph, ModuleGraphEdge},
+    module_graph::ModuleGraph,
     module_group::{ModuleGroup, ModuleGroupMap},
     ResolveKind,
   },
-  plugin::Plugin,
+  plugin::{filter::PluginFilter, plugin_container::Hook, Plugin},
   resource::resource_pot::{ResourcePot, ResourcePotKind, ResourcePotMap},
 };
 
@@ -28,6 +28,13 @@ impl Plugin for PluginModules {
     "ToyPluginModules"
   }
 
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: vec![Hook::AnalyzeModuleGraph, Hook::MergeModules],
+      ..PluginFilter::default()
+    }
+  }
+
   fn analyze_module_graph(
     &self,
     module_graph: &mut ModuleGraph,
@@ -37,14 +44,14 @@ impl Plugin for PluginModules {
 
     // 从入口开始遍历模块，分析依赖，把静态依赖分组为同一个 module_group
     // (动态依赖也会被当作入口去遍历分析)
-    for entry_id in module_graph.entries.clone() {
+    for entry_id in module_graph.entries.keys().cloned().collect::<Vec<_>>() {
       let (module_group, dynamic_deps) = module_group_from_entry(entry_id, module_graph)?;
       module_group_map.insert(module_group.id.clone(), module_group);
 
       let mut queue = VecDeque::from(dynamic_deps);
       let mut seen = HashSet::new();
 
-      while queue.len() > 0 {
+      while !queue.is_empty() {
         let head = queue.pop_front().unwrap();
 
         if seen.contains(&head) {
@@ -72,7 +79,7 @@ impl Plugin for PluginModules {
     // 一个 module_group 里可能既有 JS 模块，也有 CSS 模块，
     // 需要把它们分别合并成一个 JS resource_pot 和一个 CSS resource_pot。
     let mut module_graph = context.module_graph.write().unwrap();
-    let mut resource_pot_map = HashMap::new();
+    let mut resource_pot_map = ResourcePotMap::new();
 
     for module_group in module_group_map.values_mut() {
       let mut resource_pot_by_kind = HashMap::<ResourcePotKind, ResourcePot>::new();
@@ -193,10 +200,16 @@ fn collect_module_deps(
 
   seen.insert(module_id.to_string());
 
+  // 按照 import 的顺序遍历依赖，保证模块在 resource_pot 中的顺序稳定
   for (dep_id, edge) in module_graph.dependencies(module_id)? {
+    // 外部依赖不会被打包
+    if module_graph.module(&dep_id).is_some_and(|dep| dep.external) {
+      continue;
+    }
+
     if matches!(edge.kind, ResolveKind::DynamicImport) {
       dynamic_deps.push(dep_id);
-    } else {
+    } else if !seen.contains(&dep_id) {
       static_deps.push(dep_id.clone());
       collect_module_deps(module_graph, &dep_id, static_deps, dynamic_deps, seen)?;
     }
@@ -1,16 +1,15 @@
-use std::{
-  collections::HashMap,
-  path::{Path, PathBuf},
-  sync::Arc,
-};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 use oxc_resolver::{ResolveOptions, Resolver};
 
 use crate::{
   context::CompilationContext,
   error::{CompilationError, Result},
-  plugin::{Plugin, ResolveHookParams, ResolveHookResult},
-  utils::fulfill_root_prefix,
+  module::is_virtual_module,
+  plugin::{
+    filter::PluginFilter, plugin_container::Hook, Plugin, ResolveHookParams, ResolveHookResult,
+  },
+  utils::{fulfill_root_prefix, to_relative},
 };
 
 pub struct PluginResolve {
@@ -30,16 +29,40 @@ impl Plugin for PluginResolve {
     "ToyPluginResolve"
   }
 
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: vec![Hook::Resolve, Hook::WatchChange],
+      ..PluginFilter::default()
+    }
+  }
+
   fn resolve(
     &self,
     params: &ResolveHookParams,
     context: &Arc<CompilationContext>,
   ) -> Result<Option<ResolveHookResult>> {
+    // 虚拟模块需要由其他插件 resolve
+    if is_virtual_module(&params.source) {
+      return Ok(None);
+    }
+
+    // 外部依赖保留原来的 source 作为 id
+    if context.config.external_target(&params.source).is_some() {
+      return Ok(Some(ResolveHookResult {
+        id: params.source.clone(),
+        query: HashMap::new(),
+        external: true,
+        module_kind: None,
+      }));
+    }
+
     let root = &context.config.root;
 
+    // 虚拟模块没有所在的目录，它的依赖相对于 root 解析
     let base = params
       .importer
       .as_ref()
+      .filter(|importer| !is_virtual_module(importer))
       .map(|importer| {
         PathBuf::from(fulfill_root_prefix(importer, root))
           .parent()
@@ -49,13 +72,27 @@ impl Plugin for PluginResolve {
       })
       .unwrap_or(context.config.root.clone());
 
-    resolve_id(&self.resolver, &params.source, &base, &context.config.root).map(|v| Some(v))
+    resolve_id(
+      &self.resolver,
+      &params.source,
+      params.importer.as_deref(),
+      &base,
+      &context.config.root,
+    )
+    .map(Some)
+  }
+
+  /// resolver 会缓存文件系统的查询结果，文件新建或删除之后需要清除，否则仍然无法 resolve 新建的文件
+  fn watch_change(&self, _paths: &[String], _context: &Arc<CompilationContext>) -> Result<()> {
+    self.resolver.clear_cache();
+    Ok(())
   }
 }
 
 fn resolve_id(
   resolver: &Resolver,
   source: &str,
+  importer: Option<&str>,
   base: &str,
   root: &str,
 ) -> Result<ResolveHookResult> {
@@ -65,10 +102,11 @@ fn resolve_id(
 
   let resolution =
     resolver
-      .resolve(base.to_string(), source)
+      .resolve(base, source)
       .map_err(|err| CompilationError::ResolveError {
         src: source.to_string(),
         base: base.to_string(),
+        importer: importer.map(|importer| importer.to_string()),
         source: Some(Box::new(err)),
       })?;
 
@@ -78,6 +116,7 @@ fn resolve_id(
     id,
     query,
     external: false,
+    module_kind: None,
   })
 }
 
@@ -96,27 +135,6 @@ fn parse_query(query_str: &str) -> HashMap<String, String> {
   query
 }
 
-/// Convert a path string into a relative path, if it's inside the root.
-/// If it's outside of the root or it's not an absolute path, return it as is.
-fn to_relative(path: &str, root: &str) -> String {
-  let path = Path::new(path);
-  let root = Path::new(root);
-
-  // If the path is not a child of the root, or not absolute, return it as is.
-  if !path.is_absolute() || path == root || !path.starts_with(root) {
-    return path.to_string_lossy().to_string();
-  }
-
-  // Otherwise, it's a child of the root. Convert it into a relative path of the root.
-  let relative = path
-    .strip_prefix(root)
-    .unwrap()
-    .to_string_lossy()
-    .to_string();
-
-  "./".to_string() + &relative
-}
-
 #[cfg(test)]
 mod tests {
   use std::{env, fs};
@@ -159,6 +177,7 @@ mod tests {
     let res = resolve_id(
       &resolver,
       "../../fixtures/basic/index?foo=bar",
+      None,
       env::current_dir().unwrap().to_str().unwrap(),
       &root,
     )
@@ -174,6 +193,7 @@ mod tests {
     let res = resolve_id(
       &resolver,
       source.parent().unwrap().to_str().unwrap(),
+      None,
       env::current_dir().unwrap().to_str().unwrap(),
       &root,
     )
@@ -1,14 +1,27 @@
-use std::{fs, path::PathBuf, sync::Arc};
+use std::{
+  collections::HashSet,
+  fs, io,
+  path::{Path, PathBuf},
+  sync::{Arc, Mutex},
+};
 
 use crate::{
-  context::CompilationContext, error::Result, plugin::Plugin, resource::resource::ResourceMap,
+  context::CompilationContext,
+  error::{CompilationError, Result},
+  plugin::{filter::PluginFilter, order::PluginEnforce, plugin_container::Hook, Plugin},
+  resource::resource::ResourceMap,
 };
 
-pub struct PluginResources {}
+pub struct PluginResources {
+  /// 上一次输出到文件系统的资源名
+  written_names: Mutex<HashSet<String>>,
+}
 
 impl PluginResources {
   pub fn new() -> Self {
-    Self {}
+    Self {
+      written_names: Mutex::new(HashSet::new()),
+    }
   }
 }
 
@@ -17,28 +30,116 @@ impl Plugin for PluginResources {
     "ToyPluginResources"
   }
 
+  /// 在其他插件的 write_resources 之后输出资源，`Post` 阶段的用户插件可以在资源输出之后执行
+  fn enforce(&self) -> PluginEnforce {
+    PluginEnforce::Post
+  }
+
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: vec![Hook::WriteResources],
+      ..PluginFilter::default()
+    }
+  }
+
   fn write_resources(
     &self,
     resources: &mut ResourceMap,
     context: &Arc<CompilationContext>,
   ) -> Result<()> {
+    if !context.config.output.write {
+      // 只在内存中生成资源，同样标记为已输出，增量编译时可以直接复用
+      for resource in resources.values_mut() {
+        resource.emitted = true;
+      }
+
+      return Ok(());
+    }
+
     let root = PathBuf::from(&context.config.root);
     let out_dir = root.join(&context.config.output.dir);
+    let mut written_names = self.written_names.lock().unwrap();
+
+    if written_names.is_empty() {
+      // 首次输出，清空输出目录
+      if out_dir.exists() {
+        fs::remove_dir_all(&out_dir).map_err(|err| write_error(&out_dir, err))?;
+      }
+    } else {
+      // 增量输出，只删除本次不再存在的资源
+      let names = resources
+        .values()
+        .map(|resource| &resource.name)
+        .collect::<HashSet<_>>();
 
-    if out_dir.exists() {
-      fs::remove_dir_all(out_dir.clone()).unwrap();
+      for name in written_names.iter().filter(|name| !names.contains(name)) {
+        let _ = fs::remove_file(out_dir.join(name));
+      }
     }
 
     for resource in resources.values_mut() {
       if !resource.emitted {
         let out_path = out_dir.join(&resource.name);
 
-        fs::create_dir_all(out_path.parent().unwrap()).unwrap();
-        fs::write(out_path, &resource.content).unwrap();
+        let dir = out_path.parent().unwrap();
+
+        fs::create_dir_all(dir).map_err(|err| write_error(dir, err))?;
+        fs::write(&out_path, resource.content.as_bytes())
+          .map_err(|err| write_error(&out_path, err))?;
         resource.emitted = true;
       }
     }
 
+    *written_names = resources
+      .values()
+      .map(|resource| resource.name.clone())
+      .collect();
+
     Ok(())
   }
 }
+
+fn write_error(path: &Path, source: io::Error) -> CompilationError {
+  CompilationError::WriteError {
+    path: path.to_string_lossy().to_string(),
+    source,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use crate::{
+    config::{Config, OutputConfig},
+    Compiler,
+  };
+
+  use super::*;
+
+  #[test]
+  fn test_write_resources_error() {
+    let root = fs::canonicalize("../../fixtures/basic").unwrap();
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          // 输出目录的父路径是一个文件，无法创建目录
+          dir: "./index.js/dist".to_string(),
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+
+    let err = compiler.compile().unwrap_err();
+    let CompilationError::WriteError { path, .. } = &err else {
+      panic!("expect write error, got: {err}");
+    };
+
+    assert!(path.contains("index.js"), "{path}");
+  }
+}
@@ -1,4 +1,4 @@
-(function bootstrap(modules, entryId) {
+(function () {
   const globalObject =
     typeof window !== 'undefined' && window ||
     typeof self !== 'undefined' && self ||
@@ -67,10 +67,4 @@
       dynamicRequire,
     }
   })();
-
-  globalObject.__toyModuleSystem__.register(modules);
-
-  if (entryId) {
-    globalObject.__toyModuleSystem__.require(entryId);
-  }
-})(modules, entryId);
\ No newline at end of file
+})();
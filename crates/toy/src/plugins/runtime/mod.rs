@@ -1,10 +1,47 @@
-use crate::plugin::Plugin;
+use std::sync::{Arc, RwLock};
 
-pub struct PluginRuntime {}
+use oxc::{
+  codegen::{Codegen, CodegenOptions},
+  span::SourceType,
+};
+
+use crate::{
+  config::RuntimeMode,
+  context::CompilationContext,
+  error::{CompilationError, Result},
+  oxc::OxcProgram,
+  plugin::{filter::PluginFilter, plugin_container::Hook, Plugin},
+  resource::{
+    resource::{Resource, ResourceKind, ResourceMap},
+    resource_pot::{
+      ResourcePot, ResourcePotKind, ResourcePotMap, ResourcePotMeta, RuntimeResourcePotMeta,
+    },
+  },
+  source_map::offset_lines,
+};
+
+pub const RUNTIME_RESOURCE_POT_ID: &str = "toy-runtime";
+/// `RuntimeMode::Separate` 时输出的运行时资源名
+pub const RUNTIME_RESOURCE_NAME: &str = "./toy-runtime.js";
+
+/// 模块系统运行时：`js-runtime/module-system.js` 加上插件通过
+/// `CompilationContext::add_runtime_module` 添加的运行时模块。
+///
+/// 运行时作为一个 `ResourcePotKind::Runtime` 的 resource_pot 生成，根据 `config.output.runtime`
+/// 内联到每个入口 JS 资源的开头，或者输出为单独的 `ResourceKind::Runtime` 资源。
+///
+/// 模块系统挂载在全局对象上，其他代码（例如 `PluginScript` 的 bootstrap）直接通过全局变量
+/// `__toyModuleSystem__` 访问，不需要再判断当前环境的全局对象
+pub struct PluginRuntime {
+  /// 本次生成的运行时代码
+  code: RwLock<String>,
+}
 
 impl PluginRuntime {
   pub fn new() -> Self {
-    Self {}
+    Self {
+      code: RwLock::new(String::new()),
+    }
   }
 }
 
@@ -12,4 +49,239 @@ impl Plugin for PluginRuntime {
   fn name(&self) -> &str {
     "ToyPluginRuntime"
   }
+
+  /// 内联时需要在入口 JS 渲染完成之后插入运行时
+  fn run_after(&self) -> Vec<&str> {
+    vec!["ToyPluginScript"]
+  }
+
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: vec![
+        Hook::ProcessResourcePots,
+        Hook::RenderResourcePot,
+        Hook::GenerateResources,
+      ],
+      ..PluginFilter::default()
+    }
+  }
+
+  fn process_resource_pots(
+    &self,
+    resource_pot_map: &mut ResourcePotMap,
+    context: &Arc<CompilationContext>,
+  ) -> Result<()> {
+    // 入口 JS 资源并行渲染，内联时需要提前生成运行时代码
+    *self.code.write().unwrap() = runtime_code(context)?;
+
+    // 运行时不属于任何 module_group，也不包含模块
+    let mut resource_pot = ResourcePot::new(
+      RUNTIME_RESOURCE_POT_ID.to_string(),
+      ResourcePotKind::Runtime,
+      String::new(),
+    );
+    resource_pot.module_ids.clear();
+    resource_pot_map.insert(resource_pot.id.clone(), resource_pot);
+
+    Ok(())
+  }
+
+  fn render_resource_pot(
+    &self,
+    resource_pot: &mut ResourcePot,
+    context: &Arc<CompilationContext>,
+  ) -> Result<()> {
+    let code = self.code.read().unwrap();
+
+    match (&resource_pot.kind, &mut resource_pot.meta) {
+      (ResourcePotKind::Runtime, meta) => {
+        *meta = ResourcePotMeta::Runtime(RuntimeResourcePotMeta { code: code.clone() });
+      }
+      // 只有入口 JS 资源会被渲染成 `ResourcePotMeta::Js`
+      (ResourcePotKind::Js, ResourcePotMeta::Js(meta))
+        if context.config.output.runtime == RuntimeMode::Inline =>
+      {
+        meta.code = format!("{code}{}", meta.code);
+
+        if let Some(source_map) = &meta.source_map {
+          let lines = code.matches('\n').count() as u32;
+
+          meta.source_map = Some(
+            offset_lines(&context.config.root, source_map, lines).map_err(|message| {
+              CompilationError::GenericError(format!(
+                "Generate source map for `{}` failed: {message}",
+                resource_pot.id
+              ))
+            })?,
+          );
+        }
+      }
+      _ => {}
+    }
+
+    Ok(())
+  }
+
+  fn generate_resources(
+    &self,
+    resource_pot: &mut ResourcePot,
+    context: &Arc<CompilationContext>,
+  ) -> Result<Option<ResourceMap>> {
+    let ResourcePotMeta::Runtime(meta) = &resource_pot.meta else {
+      return Ok(None);
+    };
+
+    let mut resource_map = ResourceMap::new();
+
+    // 内联时运行时已经包含在入口 JS 资源中
+    if context.config.output.runtime == RuntimeMode::Separate {
+      let name = RUNTIME_RESOURCE_NAME.to_string();
+
+      resource_map.insert(
+        name.clone(),
+        Resource {
+          name: name.clone(),
+          content: meta.code.clone().into(),
+          resource_kind: ResourceKind::Runtime,
+          resource_pot_id: resource_pot.id.clone(),
+          emitted: false,
+        },
+      );
+      resource_pot.resource_ids = vec![name];
+    }
+
+    Ok(Some(resource_map))
+  }
+}
+
+/// 生成运行时代码，每个运行时模块包裹在一个函数中，通过参数 `__toyModuleSystem__` 访问模块系统
+fn runtime_code(context: &Arc<CompilationContext>) -> Result<String> {
+  let mut code = codegen(
+    RUNTIME_RESOURCE_POT_ID,
+    include_str!("./js-runtime/module-system.js"),
+  )?;
+
+  for (name, module_code) in context.runtime_modules.read().unwrap().iter() {
+    code.push_str(&codegen(
+      &format!("{RUNTIME_RESOURCE_POT_ID}:{name}"),
+      &format!("(function (__toyModuleSystem__) {{\n{module_code}\n}})(__toyModuleSystem__);"),
+    )?);
+  }
+
+  Ok(code)
+}
+
+fn codegen(id: &str, code: &str) -> Result<String> {
+  let program =
+    OxcProgram::try_build(code.to_string(), SourceType::default()).map_err(|message| {
+      CompilationError::ParseError {
+        id: id.to_string(),
+        message,
+      }
+    })?;
+
+  Ok(
+    program
+      .with_program(|program| Codegen::<false>::new(code.len(), CodegenOptions).build(&program.0)),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::BTreeMap, fs, process::Command};
+
+  use crate::{
+    config::{Config, OutputConfig},
+    utils::node_available,
+    Compiler,
+  };
+
+  use super::*;
+
+  /// 添加一个运行时模块，记录模块系统已经创建
+  struct PluginRuntimeModule;
+
+  impl Plugin for PluginRuntimeModule {
+    fn name(&self) -> &str {
+      "TestPluginRuntimeModule"
+    }
+
+    fn build_start(&self, context: &Arc<CompilationContext>) -> Result<()> {
+      context.add_runtime_module(
+        "ready",
+        "globalThis.runtimeReady = typeof __toyModuleSystem__.require === 'function';".to_string(),
+      );
+      Ok(())
+    }
+  }
+
+  fn compile(runtime: RuntimeMode) -> Compiler {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/basic")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          write: false,
+          runtime,
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![Arc::new(PluginRuntimeModule)],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+    compiler
+  }
+
+  /// 用 node 依次执行代码，返回 `globalThis.runtimeReady`，没有安装 node 时返回 `None`
+  fn run(codes: &[&str]) -> Option<String> {
+    if !node_available() {
+      return None;
+    }
+
+    let script = format!(
+      "{}\nconsole.log(globalThis.runtimeReady);",
+      codes.join("\n")
+    );
+    let output = Command::new("node").arg("-e").arg(script).output().unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    Some(String::from_utf8(output.stdout).unwrap())
+  }
+
+  #[test]
+  fn test_runtime_inline() {
+    let output = compile(RuntimeMode::Inline).output();
+    let code = output.content("./index.js").unwrap();
+
+    assert!(code.contains("__toyModuleSystem__ = globalObject.__toyModuleSystem__"));
+    assert!(!output.resources.contains_key(RUNTIME_RESOURCE_NAME));
+    if let Some(stdout) = run(&[code]) {
+      assert!(stdout.ends_with("true\n"));
+    }
+  }
+
+  #[test]
+  fn test_runtime_separate() {
+    let output = compile(RuntimeMode::Separate).output();
+    let runtime = output.content(RUNTIME_RESOURCE_NAME).unwrap();
+    let code = output.content("./index.js").unwrap();
+
+    assert_eq!(
+      output.resources[RUNTIME_RESOURCE_NAME].kind,
+      ResourceKind::Runtime
+    );
+    assert!(!code.contains("__toyModuleSystem__ = globalObject.__toyModuleSystem__"));
+    assert_eq!(
+      output.entries["main"],
+      vec![RUNTIME_RESOURCE_NAME, "./index.js"]
+    );
+    if let Some(stdout) = run(&[runtime, code]) {
+      assert!(stdout.ends_with("true\n"));
+    }
+  }
 }
@@ -0,0 +1,654 @@
+use std::{
+  collections::{BTreeMap, HashSet},
+  ops::Range,
+};
+
+use parcel_sourcemap::{OriginalLocation, SourceMap};
+
+use oxc::{
+  ast::{
+    ast::{
+      BindingPattern, BindingPatternKind, ConditionalExpression, Declaration,
+      ExportDefaultDeclarationKind, Expression, ForStatementInit, ForStatementLeft,
+      FormalParameters, IfStatement, ImportDeclarationSpecifier, LogicalExpression,
+      MemberExpression, ModuleDeclaration, ObjectProperty, Statement, VariableDeclaration,
+    },
+    AstKind, Visit,
+  },
+  span::{GetSpan, Span},
+  syntax::{
+    operator::{BinaryOperator, LogicalOperator, UnaryOperator},
+    scope::ScopeFlags,
+  },
+};
+
+use crate::source_map::offsets_to_positions;
+
+/// 构建时替换 `Config::defines` 中的标识符，并移除因此变成死代码的分支：
+///
+/// ```js
+/// if (process.env.NODE_ENV !== 'production') {
+///   console.log('dev')
+/// }
+/// ```
+///
+/// ↓↓↓ (production)
+///
+/// ```js
+/// ;
+///
+///
+/// ```
+///
+/// oxc 的 codegen 不支持生成 source map，这里只记录对原始代码的修改 `edits`，
+/// 由 `apply_edits` 应用到原始代码上并生成对应的 source map。
+///
+/// 在作用域中声明过的同名变量不会被替换，例如 `function f(__DEV__) { return __DEV__ }`，
+/// `eval` 和 `with` 引入的变量无法在构建时确定，仍然会被替换
+pub struct DefineVisitor<'a> {
+  defines: &'a BTreeMap<String, String>,
+  /// 按遍历顺序记录的修改，互不重叠
+  pub edits: Vec<(Span, String)>,
+  /// 当前作用域链中每个作用域声明的变量
+  scopes: Vec<HashSet<String>>,
+}
+
+impl<'a> DefineVisitor<'a> {
+  pub fn new(defines: &'a BTreeMap<String, String>) -> Self {
+    Self {
+      defines,
+      edits: vec![],
+      scopes: vec![],
+    }
+  }
+
+  /// 表达式对应的 define 值，根标识符是局部变量时不替换
+  fn define_value(&self, expr: &Expression) -> Option<&'a String> {
+    let key = expression_key(expr)?;
+    let root = key.split('.').next().unwrap_or_default();
+
+    if self.is_declared(root) {
+      return None;
+    }
+
+    self.defines.get(&key)
+  }
+
+  fn is_declared(&self, name: &str) -> bool {
+    self.scopes.iter().any(|scope| scope.contains(name))
+  }
+
+  /// 只保留 `span` 中的 `kept` 部分，`wrap` 为 `true` 时用括号包裹保留的部分
+  fn keep(&mut self, span: Span, kept: Span, wrap: bool) {
+    let (open, close) = if wrap { ("(", ")") } else { ("", "") };
+
+    self
+      .edits
+      .push((Span::new(span.start, kept.start), open.to_string()));
+    self
+      .edits
+      .push((Span::new(kept.end, span.end), close.to_string()));
+  }
+
+  /// 在构建时对表达式求值，无法确定结果时返回 `None`
+  fn evaluate(&self, expr: &Expression) -> Option<StaticValue> {
+    match expr {
+      Expression::StringLiteral(lit) => Some(StaticValue::String(lit.value.to_string())),
+      Expression::NumberLiteral(lit) => Some(StaticValue::Number(lit.value)),
+      Expression::BooleanLiteral(lit) => Some(StaticValue::Boolean(lit.value)),
+      Expression::NullLiteral(_) => Some(StaticValue::Null),
+      Expression::ParenthesizedExpression(expr) => self.evaluate(&expr.expression),
+      Expression::UnaryExpression(expr) if expr.operator == UnaryOperator::LogicalNot => self
+        .evaluate(&expr.argument)
+        .map(|value| StaticValue::Boolean(!value.is_truthy())),
+      Expression::BinaryExpression(expr) => {
+        let left = self.evaluate(&expr.left)?;
+        let right = self.evaluate(&expr.right)?;
+
+        match expr.operator {
+          BinaryOperator::StrictEquality => Some(StaticValue::Boolean(left == right)),
+          BinaryOperator::StrictInequality => Some(StaticValue::Boolean(left != right)),
+          BinaryOperator::Equality => left.loose_equals(&right).map(StaticValue::Boolean),
+          BinaryOperator::Inequality => left
+            .loose_equals(&right)
+            .map(|equals| StaticValue::Boolean(!equals)),
+          _ => None,
+        }
+      }
+      Expression::LogicalExpression(expr) => {
+        let left = self.evaluate(&expr.left)?;
+
+        match (expr.operator, left.is_truthy(), left.is_nullish()) {
+          (LogicalOperator::And, false, _) | (LogicalOperator::Or, true, _) => Some(left),
+          (LogicalOperator::Coalesce, _, false) => Some(left),
+          _ => self.evaluate(&expr.right),
+        }
+      }
+      Expression::Identifier(ident)
+        if ident.name == "undefined" && !self.is_declared("undefined") =>
+      {
+        self
+          .define_value(expr)
+          .map_or(Some(StaticValue::Undefined), |value| {
+            StaticValue::parse(value)
+          })
+      }
+      _ => self
+        .define_value(expr)
+        .and_then(|value| StaticValue::parse(value)),
+    }
+  }
+}
+
+impl<'a, 'b> Visit<'b> for DefineVisitor<'a> {
+  fn enter_scope(&mut self, _flags: ScopeFlags) {
+    self.scopes.push(HashSet::new());
+  }
+
+  fn leave_scope(&mut self) {
+    self.scopes.pop();
+  }
+
+  /// `enter_scope` 之后紧接着进入创建作用域的节点，在这里收集作用域中声明的变量。
+  /// 变量可以在声明之前使用（提升），所以需要在遍历作用域内的代码之前收集
+  fn enter_node(&mut self, kind: AstKind<'b>) {
+    let Some(scope) = self.scopes.last_mut() else {
+      return;
+    };
+
+    match kind {
+      AstKind::Program(program) => {
+        declare_lexical(&program.body, scope);
+        declare_var(&program.body, scope);
+      }
+      AstKind::Function(func) => {
+        if let Some(id) = &func.id {
+          scope.insert(id.name.to_string());
+        }
+        declare_params(&func.params, scope);
+        if let Some(body) = &func.body {
+          declare_lexical(&body.statements, scope);
+          declare_var(&body.statements, scope);
+        }
+      }
+      AstKind::ArrowExpression(expr) => {
+        declare_params(&expr.params, scope);
+        declare_lexical(&expr.body.statements, scope);
+        declare_var(&expr.body.statements, scope);
+      }
+      AstKind::StaticBlock(block) => {
+        declare_lexical(&block.body, scope);
+        declare_var(&block.body, scope);
+      }
+      AstKind::BlockStatement(block) => declare_lexical(&block.body, scope),
+      AstKind::SwitchStatement(stmt) => {
+        for case in &stmt.cases {
+          declare_lexical(&case.consequent, scope);
+        }
+      }
+      AstKind::CatchClause(clause) => {
+        if let Some(param) = &clause.param {
+          declare_pattern(param, scope);
+        }
+        declare_lexical(&clause.body.body, scope);
+      }
+      // 只有 let、const 声明会创建作用域，var 声明已经提升到函数作用域中，重复收集不影响结果
+      AstKind::ForStatement(stmt) => {
+        if let Some(ForStatementInit::VariableDeclaration(decl)) = &stmt.init {
+          declare_variables(decl, scope);
+        }
+      }
+      AstKind::ForInStatement(stmt) => {
+        if let ForStatementLeft::VariableDeclaration(decl) = &stmt.left {
+          declare_variables(decl, scope);
+        }
+      }
+      AstKind::ForOfStatement(stmt) => {
+        if let ForStatementLeft::VariableDeclaration(decl) = &stmt.left {
+          declare_variables(decl, scope);
+        }
+      }
+      AstKind::Class(class) => {
+        if let Some(id) = &class.id {
+          scope.insert(id.name.to_string());
+        }
+      }
+      _ => {}
+    }
+  }
+
+  fn visit_expression(&mut self, expr: &Expression<'b>) {
+    if let Some(value) = self.define_value(expr) {
+      self.edits.push((expr.span(), replacement(value)));
+      return;
+    }
+
+    self.visit_expression_match(expr);
+  }
+
+  fn visit_object_property(&mut self, prop: &ObjectProperty<'b>) {
+    // { __DEV__ } -> { __DEV__: true }
+    if prop.shorthand {
+      if let Some(value) = self.define_value(&prop.value) {
+        let key = expression_key(&prop.value).unwrap_or_default();
+        self
+          .edits
+          .push((prop.span, format!("{key}: {}", replacement(value))));
+        return;
+      }
+    }
+
+    self.visit_property_key(&prop.key);
+    self.visit_expression(&prop.value);
+  }
+
+  fn visit_if_statement(&mut self, stmt: &IfStatement<'b>) {
+    let Some(test) = self.evaluate(&stmt.test) else {
+      self.visit_expression(&stmt.test);
+      self.visit_statement(&stmt.consequent);
+      if let Some(alternate) = &stmt.alternate {
+        self.visit_statement(alternate);
+      }
+      return;
+    };
+
+    let kept = if test.is_truthy() {
+      Some(&stmt.consequent)
+    } else {
+      stmt.alternate.as_ref()
+    };
+
+    match kept {
+      Some(kept) => {
+        self.keep(stmt.span, kept.span(), false);
+        self.visit_statement(kept);
+      }
+      // 保留一个空语句，`if (a) if (false) b()` 这样的位置必须有语句
+      None => self.edits.push((stmt.span, ";".to_string())),
+    }
+  }
+
+  fn visit_conditional_expression(&mut self, expr: &ConditionalExpression<'b>) {
+    let Some(test) = self.evaluate(&expr.test) else {
+      self.visit_expression(&expr.test);
+      self.visit_expression(&expr.consequent);
+      self.visit_expression(&expr.alternate);
+      return;
+    };
+
+    let kept = if test.is_truthy() {
+      &expr.consequent
+    } else {
+      &expr.alternate
+    };
+
+    self.keep(expr.span, kept.span(), true);
+    self.visit_expression(kept);
+  }
+
+  fn visit_logical_expression(&mut self, expr: &LogicalExpression<'b>) {
+    let Some(left) = self.evaluate(&expr.left) else {
+      self.visit_expression(&expr.left);
+      self.visit_expression(&expr.right);
+      return;
+    };
+
+    let short_circuit = match expr.operator {
+      LogicalOperator::And => !left.is_truthy(),
+      LogicalOperator::Or => left.is_truthy(),
+      LogicalOperator::Coalesce => !left.is_nullish(),
+    };
+
+    if short_circuit {
+      // 'production' !== 'production' && warn() -> false
+      self.edits.push((expr.span, left.to_code()));
+    } else {
+      self.keep(expr.span, expr.right.span(), true);
+      self.visit_expression(&expr.right);
+    }
+  }
+}
+
+/// 标识符或者静态成员表达式对应的 define key，例如 `process.env.NODE_ENV`、`import.meta.env.MODE`
+fn expression_key(expr: &Expression) -> Option<String> {
+  match expr {
+    Expression::Identifier(ident) => Some(ident.name.to_string()),
+    Expression::MetaProperty(meta) => Some(format!("{}.{}", meta.meta.name, meta.property.name)),
+    Expression::MemberExpression(member) => match &**member {
+      MemberExpression::StaticMemberExpression(member) if !member.optional => {
+        expression_key(&member.object).map(|object| format!("{object}.{}", member.property.name))
+      }
+      _ => None,
+    },
+    _ => None,
+  }
+}
+
+/// 替换表达式的代码，字面量和标识符之外的值需要用括号包裹，例如 `__X__ * 2` 中的 `1 + 1`，
+/// 以及出现在语句开头时会被解析为代码块的对象
+fn replacement(value: &str) -> String {
+  let code = value.trim();
+  let is_literal = !code.starts_with('-') && StaticValue::parse(code).is_some();
+  let is_identifier = !code.is_empty()
+    && !code.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+    && code
+      .chars()
+      .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '.'));
+
+  if is_literal || is_identifier {
+    value.to_string()
+  } else {
+    format!("({value})")
+  }
+}
+
+/// 收集语句列表中直接声明的 let、const、class、function 以及 import 的变量
+fn declare_lexical(stmts: &[Statement], names: &mut HashSet<String>) {
+  for stmt in stmts {
+    match stmt {
+      Statement::Declaration(decl) => declare_declaration(decl, names),
+      Statement::ModuleDeclaration(decl) => match &**decl {
+        ModuleDeclaration::ImportDeclaration(decl) => {
+          for specifier in decl.specifiers.iter().flatten() {
+            let local = match specifier {
+              ImportDeclarationSpecifier::ImportSpecifier(specifier) => &specifier.local,
+              ImportDeclarationSpecifier::ImportDefaultSpecifier(specifier) => &specifier.local,
+              ImportDeclarationSpecifier::ImportNamespaceSpecifier(specifier) => &specifier.local,
+            };
+            names.insert(local.name.to_string());
+          }
+        }
+        ModuleDeclaration::ExportNamedDeclaration(decl) => {
+          if let Some(decl) = &decl.declaration {
+            declare_declaration(decl, names);
+          }
+        }
+        ModuleDeclaration::ExportDefaultDeclaration(decl) => {
+          let id = match &decl.declaration {
+            ExportDefaultDeclarationKind::FunctionDeclaration(func) => func.id.as_ref(),
+            ExportDefaultDeclarationKind::ClassDeclaration(class) => class.id.as_ref(),
+            _ => None,
+          };
+          if let Some(id) = id {
+            names.insert(id.name.to_string());
+          }
+        }
+        _ => {}
+      },
+      _ => {}
+    }
+  }
+}
+
+/// 收集 let、const、class、function 声明，var 声明由 `declare_var` 收集
+fn declare_declaration(decl: &Declaration, names: &mut HashSet<String>) {
+  match decl {
+    Declaration::VariableDeclaration(decl) if decl.kind.is_lexical() => {
+      declare_variables(decl, names);
+    }
+    Declaration::FunctionDeclaration(func) => {
+      if let Some(id) = &func.id {
+        names.insert(id.name.to_string());
+      }
+    }
+    Declaration::ClassDeclaration(class) => {
+      if let Some(id) = &class.id {
+        names.insert(id.name.to_string());
+      }
+    }
+    _ => {}
+  }
+}
+
+/// 收集会提升到函数作用域的 var 声明，不进入嵌套的函数
+fn declare_var(stmts: &[Statement], names: &mut HashSet<String>) {
+  for stmt in stmts {
+    match stmt {
+      Statement::Declaration(Declaration::VariableDeclaration(decl)) if !decl.kind.is_lexical() => {
+        declare_variables(decl, names);
+      }
+      Statement::ModuleDeclaration(decl) => {
+        if let ModuleDeclaration::ExportNamedDeclaration(decl) = &**decl {
+          if let Some(Declaration::VariableDeclaration(decl)) = &decl.declaration {
+            if !decl.kind.is_lexical() {
+              declare_variables(decl, names);
+            }
+          }
+        }
+      }
+      Statement::BlockStatement(block) => declare_var(&block.body, names),
+      Statement::IfStatement(stmt) => {
+        declare_var(std::slice::from_ref(&stmt.consequent), names);
+        if let Some(alternate) = &stmt.alternate {
+          declare_var(std::slice::from_ref(alternate), names);
+        }
+      }
+      Statement::ForStatement(stmt) => {
+        if let Some(ForStatementInit::VariableDeclaration(decl)) = &stmt.init {
+          if !decl.kind.is_lexical() {
+            declare_variables(decl, names);
+          }
+        }
+        declare_var(std::slice::from_ref(&stmt.body), names);
+      }
+      Statement::ForInStatement(stmt) => {
+        if let ForStatementLeft::VariableDeclaration(decl) = &stmt.left {
+          if !decl.kind.is_lexical() {
+            declare_variables(decl, names);
+          }
+        }
+        declare_var(std::slice::from_ref(&stmt.body), names);
+      }
+      Statement::ForOfStatement(stmt) => {
+        if let ForStatementLeft::VariableDeclaration(decl) = &stmt.left {
+          if !decl.kind.is_lexical() {
+            declare_variables(decl, names);
+          }
+        }
+        declare_var(std::slice::from_ref(&stmt.body), names);
+      }
+      Statement::WhileStatement(stmt) => declare_var(std::slice::from_ref(&stmt.body), names),
+      Statement::DoWhileStatement(stmt) => declare_var(std::slice::from_ref(&stmt.body), names),
+      Statement::LabeledStatement(stmt) => declare_var(std::slice::from_ref(&stmt.body), names),
+      Statement::WithStatement(stmt) => declare_var(std::slice::from_ref(&stmt.body), names),
+      Statement::TryStatement(stmt) => {
+        declare_var(&stmt.block.body, names);
+        if let Some(handler) = &stmt.handler {
+          declare_var(&handler.body.body, names);
+        }
+        if let Some(finalizer) = &stmt.finalizer {
+          declare_var(&finalizer.body, names);
+        }
+      }
+      Statement::SwitchStatement(stmt) => {
+        for case in &stmt.cases {
+          declare_var(&case.consequent, names);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+fn declare_variables(decl: &VariableDeclaration, names: &mut HashSet<String>) {
+  for declarator in &decl.declarations {
+    declare_pattern(&declarator.id, names);
+  }
+}
+
+fn declare_params(params: &FormalParameters, names: &mut HashSet<String>) {
+  for param in &params.items {
+    declare_pattern(&param.pattern, names);
+  }
+  if let Some(rest) = &params.rest {
+    declare_pattern(&rest.argument, names);
+  }
+}
+
+fn declare_pattern(pattern: &BindingPattern, names: &mut HashSet<String>) {
+  match &pattern.kind {
+    BindingPatternKind::BindingIdentifier(ident) => {
+      names.insert(ident.name.to_string());
+    }
+    BindingPatternKind::ObjectPattern(pattern) => {
+      for property in &pattern.properties {
+        declare_pattern(&property.value, names);
+      }
+      if let Some(rest) = &pattern.rest {
+        declare_pattern(&rest.argument, names);
+      }
+    }
+    BindingPatternKind::ArrayPattern(pattern) => {
+      for element in pattern.elements.iter().flatten() {
+        declare_pattern(element, names);
+      }
+      if let Some(rest) = &pattern.rest {
+        declare_pattern(&rest.argument, names);
+      }
+    }
+    BindingPatternKind::AssignmentPattern(pattern) => declare_pattern(&pattern.left, names),
+  }
+}
+
+/// 把 `edits` 应用到 `code` 上，同时生成从结果映射回 `code` 的 source map。
+/// 被删除的代码中的换行会被保留，保证每一行代码仍然在原来的行；替换会使同一行后面的代码发生列偏移，
+/// 所以在每一行的开头以及每一处修改的两端各记录一个映射
+pub fn apply_edits(
+  root: &str,
+  id: &str,
+  code: &str,
+  edits: Vec<(Span, String)>,
+) -> Result<(String, String), String> {
+  let (result, offsets) = apply(code, edits);
+  let (generated_offsets, original_offsets): (Vec<_>, Vec<_>) = offsets.into_iter().unzip();
+
+  let mut source_map = SourceMap::new(root);
+  let source = source_map.add_source(id);
+  source_map
+    .set_source_content(source as usize, code)
+    .map_err(|err| err.to_string())?;
+
+  for ((generated_line, generated_column), (original_line, original_column)) in
+    offsets_to_positions(&result, &generated_offsets)
+      .into_iter()
+      .zip(offsets_to_positions(code, &original_offsets))
+  {
+    source_map.add_mapping(
+      generated_line,
+      generated_column,
+      Some(OriginalLocation::new(
+        original_line,
+        original_column,
+        source,
+        None,
+      )),
+    );
+  }
+
+  let source_map = source_map.to_json(None).map_err(|err| err.to_string())?;
+  Ok((result, source_map))
+}
+
+/// 应用修改，返回结果以及结果中的位置与原始代码中的位置的对应关系 `(结果偏移, 原始偏移)`，按偏移升序排列
+fn apply(code: &str, mut edits: Vec<(Span, String)>) -> (String, Vec<(usize, usize)>) {
+  edits.sort_by_key(|(span, _)| (span.start, span.end));
+
+  let mut result = String::with_capacity(code.len());
+  let mut offsets = vec![(0, 0)];
+  let mut last = 0;
+
+  for (span, replacement) in edits {
+    let (start, end) = (span.start as usize, span.end as usize);
+    let removed_lines = code[start..end].matches('\n').count();
+    let inserted_lines = replacement.matches('\n').count();
+
+    push_unchanged(code, last..start, &mut result, &mut offsets);
+    offsets.push((result.len(), start));
+    result.push_str(&replacement);
+    result.push_str(&"\n".repeat(removed_lines.saturating_sub(inserted_lines)));
+    offsets.push((result.len(), end));
+    last = end;
+  }
+
+  push_unchanged(code, last..code.len(), &mut result, &mut offsets);
+  (result, offsets)
+}
+
+/// 原样保留 `code[range]`，并记录其中每一行的开头
+fn push_unchanged(
+  code: &str,
+  range: Range<usize>,
+  result: &mut String,
+  offsets: &mut Vec<(usize, usize)>,
+) {
+  for (index, _) in code[range.clone()].match_indices('\n') {
+    offsets.push((result.len() + index + 1, range.start + index + 1));
+  }
+  result.push_str(&code[range]);
+}
+
+/// 构建时可以确定的值
+#[derive(Debug, Clone, PartialEq)]
+enum StaticValue {
+  String(String),
+  Number(f64),
+  Boolean(bool),
+  Null,
+  Undefined,
+}
+
+impl StaticValue {
+  /// 解析 define 的值，只支持字面量
+  fn parse(code: &str) -> Option<Self> {
+    let code = code.trim();
+
+    match code {
+      "true" => Some(Self::Boolean(true)),
+      "false" => Some(Self::Boolean(false)),
+      "null" => Some(Self::Null),
+      "undefined" => Some(Self::Undefined),
+      _ if code.starts_with('"') => serde_json::from_str(code).ok().map(Self::String),
+      _ if code.len() >= 2
+        && code.starts_with('\'')
+        && code.ends_with('\'')
+        && !code[1..code.len() - 1].contains('\'') =>
+      {
+        Some(Self::String(code[1..code.len() - 1].to_string()))
+      }
+      _ => code.parse().ok().map(Self::Number),
+    }
+  }
+
+  fn is_truthy(&self) -> bool {
+    match self {
+      Self::String(value) => !value.is_empty(),
+      Self::Number(value) => *value != 0.0 && !value.is_nan(),
+      Self::Boolean(value) => *value,
+      Self::Null | Self::Undefined => false,
+    }
+  }
+
+  fn is_nullish(&self) -> bool {
+    matches!(self, Self::Null | Self::Undefined)
+  }
+
+  /// `==` 只处理类型相同以及 `null == undefined` 的情况，其他情况需要类型转换，不在构建时求值
+  fn loose_equals(&self, other: &Self) -> Option<bool> {
+    match (self, other) {
+      (a, b) if a.is_nullish() && b.is_nullish() => Some(true),
+      (a, b) if a.is_nullish() || b.is_nullish() => Some(false),
+      (a, b) if std::mem::discriminant(a) == std::mem::discriminant(b) => Some(a == b),
+      _ => None,
+    }
+  }
+
+  fn to_code(&self) -> String {
+    match self {
+      Self::String(value) => serde_json::to_string(value).unwrap(),
+      Self::Number(value) => value.to_string(),
+      Self::Boolean(value) => value.to_string(),
+      Self::Null => "null".to_string(),
+      Self::Undefined => "undefined".to_string(),
+    }
+  }
+}
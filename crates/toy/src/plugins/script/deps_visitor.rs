@@ -50,16 +50,15 @@ impl<'a> Visit<'a> for DepsVisitor {
           _ => {}
         }
       }
-      AstKind::ImportExpression(import_expr) => match &import_expr.source {
-        Expression::StringLiteral(source) => {
+      AstKind::ImportExpression(import_expr) => {
+        if let Expression::StringLiteral(source) = &import_expr.source {
           // import('a')
           self.deps.push(AnalyzeDep {
             source: source.value.to_string(),
             resolve_kind: ResolveKind::Import,
           });
         }
-        _ => {}
-      },
+      }
       _ => {}
     }
   }
@@ -1,12 +1,16 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+  collections::{BTreeMap, HashMap, HashSet},
+  path::Path,
+};
 
 use oxc::{
   ast::{
     ast::{
-      Argument, BindingIdentifier, BindingPatternKind, Declaration, ExportDefaultDeclarationKind,
-      Expression, IdentifierName, IdentifierReference, ImportDeclarationSpecifier,
-      ImportOrExportKind, Modifiers, ModuleDeclaration, ObjectPropertyKind, Program, PropertyKind,
-      Statement, StringLiteral, VariableDeclarationKind,
+      Argument, BindingIdentifier, BindingPattern, BindingPatternKind, Declaration,
+      ExportDefaultDeclarationKind, Expression, IdentifierName, IdentifierReference,
+      ImportDeclarationSpecifier, ImportNamespaceSpecifier, ImportOrExportKind, Modifiers,
+      ModuleDeclaration, ObjectPropertyKind, Program, PropertyKind, Statement, StringLiteral,
+      VariableDeclarationKind, VariableDeclarator,
     },
     AstBuilder, VisitMut,
   },
@@ -14,16 +18,22 @@ use oxc::{
   syntax::operator::AssignmentOperator,
 };
 
-use crate::module::module_graph::ModuleGraph;
+use crate::{
+  config::{Config, ExternalTarget},
+  module::module_graph::ModuleGraph,
+};
 
 struct ToyImport {
   source: String,
-  kv: HashMap<String, String>,
+  /// 原来的 import / export 语句的位置，生成的 `__toyRequire__` 语句沿用这个位置，用于 source map
+  span: Span,
+  /// 导入名 -> 本地变量名，有序以保证生成代码稳定
+  kv: BTreeMap<String, String>,
 }
 
 struct ToyExport {
   spread: bool,
-  kv: HashMap<String, String>,
+  kv: BTreeMap<String, String>,
 }
 
 /// 把 `esm` 模块转换成像 `commonjs` 的模块形式
@@ -50,6 +60,13 @@ pub struct EsmVisitor<'a> {
   imports: Vec<ToyImport>,
   exports: Vec<ToyExport>,
   dep_source_to_module_id: HashMap<String, String>,
+  /// 依赖是样式或者静态资源模块的 source，它们不会被 JS 引用。按模块类型判断，虚拟模块也适用
+  style_sources: HashSet<String>,
+  /// 外部依赖的 source -> (外部依赖的 id, 引用方式)
+  external_sources: HashMap<String, (String, ExternalTarget)>,
+  /// 需要保留为原生 import 的外部依赖，id -> 本地变量名，
+  /// 这些 import 需要放到 bundle 的顶层，见 `external_import_stmts`
+  pub external_imports: BTreeMap<String, String>,
 }
 
 impl<'a> EsmVisitor<'a> {
@@ -57,10 +74,30 @@ impl<'a> EsmVisitor<'a> {
     ast_builder: &'a AstBuilder<'a>,
     module_id: &'a str,
     module_graph: &ModuleGraph,
+    config: &Config,
   ) -> Self {
     let mut dep_source_to_module_id = HashMap::new();
+    let mut style_sources = HashSet::new();
+    let mut external_sources = HashMap::new();
 
     for (dep_id, edge) in module_graph.dependencies(module_id).unwrap() {
+      let Some(dep) = module_graph.module(&dep_id) else {
+        continue;
+      };
+
+      if dep.kind.is_style() || dep.kind.is_asset() {
+        style_sources.insert(edge.source.clone());
+      }
+
+      if dep.external {
+        // 插件标记的外部依赖没有对应的配置，保留为原生 import
+        let target = config
+          .external_target(&dep_id)
+          .cloned()
+          .unwrap_or(ExternalTarget::Import);
+        external_sources.insert(edge.source.clone(), (dep_id.clone(), target));
+      }
+
       dep_source_to_module_id.insert(edge.source, dep_id);
     }
 
@@ -71,12 +108,19 @@ impl<'a> EsmVisitor<'a> {
       imports: vec![],
       exports: vec![],
       dep_source_to_module_id,
+      style_sources,
+      external_sources,
+      external_imports: BTreeMap::new(),
     }
   }
 
   fn id_to_js_var(&mut self, id: &str) -> String {
     let path = Path::new(id);
-    let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+    // 虚拟模块的 id 不一定是合法的文件路径
+    let name = path
+      .file_stem()
+      .and_then(|stem| stem.to_str())
+      .unwrap_or("module");
 
     let js_var: String = name
       .chars()
@@ -91,6 +135,10 @@ impl<'a> EsmVisitor<'a> {
     "_".to_string() + js_var.as_str() + "$toy" + &self.js_var_index.to_string()
   }
 
+  fn is_style_import(&self, source: &StringLiteral) -> bool {
+    self.style_sources.contains(source.value.as_str())
+  }
+
   fn match_module_decl(
     &mut self,
     module_decl: &mut ModuleDeclaration<'a>,
@@ -102,14 +150,15 @@ impl<'a> EsmVisitor<'a> {
           return None;
         }
 
-        // 忽略样式 import
-        if is_style_import(&import_decl.source) {
+        // 忽略样式和静态资源 import
+        if self.is_style_import(&import_decl.source) {
           return None;
         }
 
         let mut toy_import = ToyImport {
           source: import_decl.source.value.to_string(),
-          kv: HashMap::new(),
+          span: import_decl.span,
+          kv: BTreeMap::new(),
         };
 
         // 遍历导入的 specifiers，构建 import key-value
@@ -166,7 +215,7 @@ impl<'a> EsmVisitor<'a> {
 
             self.exports.push(ToyExport {
               spread: false,
-              kv: HashMap::from([("default".to_string(), export_name)]),
+              kv: BTreeMap::from([("default".to_string(), export_name)]),
             });
           }
           // 类
@@ -179,7 +228,7 @@ impl<'a> EsmVisitor<'a> {
 
             self.exports.push(ToyExport {
               spread: false,
-              kv: HashMap::from([("default".to_string(), export_name)]),
+              kv: BTreeMap::from([("default".to_string(), export_name)]),
             });
           }
           // 表达式
@@ -190,7 +239,7 @@ impl<'a> EsmVisitor<'a> {
             Expression::Identifier(id_ref) => {
               self.exports.push(ToyExport {
                 spread: false,
-                kv: HashMap::from([("default".to_string(), id_ref.name.to_string())]),
+                kv: BTreeMap::from([("default".to_string(), id_ref.name.to_string())]),
               });
             }
             // 其他表达式。例如：
@@ -242,20 +291,21 @@ impl<'a> EsmVisitor<'a> {
         let mut toy_import = None;
         let mut toy_export = ToyExport {
           spread: false,
-          kv: HashMap::new(),
+          kv: BTreeMap::new(),
         };
 
         // 如果是 reexport，则新增 toy_import，
         // 例如：export { foo } from 'mod'
         if let Some(source) = &export_decl.source {
-          // 忽略样式 import
-          if is_style_import(source) {
+          // 忽略样式和静态资源 import
+          if self.is_style_import(source) {
             return None;
           }
 
           toy_import = Some(ToyImport {
             source: source.value.to_string(),
-            kv: HashMap::new(),
+            span: export_decl.span,
+            kv: BTreeMap::new(),
           });
         }
 
@@ -318,13 +368,11 @@ impl<'a> EsmVisitor<'a> {
                     }
                   }
                   BindingPatternKind::ArrayPattern(array_pattern) => {
-                    for element in &array_pattern.elements {
-                      if let Some(element) = element {
-                        if let BindingPatternKind::BindingIdentifier(id) = &element.kind {
-                          toy_export
-                            .kv
-                            .insert(id.name.to_string(), id.name.to_string());
-                        }
+                    for element in array_pattern.elements.iter().flatten() {
+                      if let BindingPatternKind::BindingIdentifier(id) = &element.kind {
+                        toy_export
+                          .kv
+                          .insert(id.name.to_string(), id.name.to_string());
                       }
                     }
                   }
@@ -351,8 +399,8 @@ impl<'a> EsmVisitor<'a> {
           return None;
         }
 
-        // 忽略样式 import
-        if is_style_import(&export_decl.source) {
+        // 忽略样式和静态资源 import
+        if self.is_style_import(&export_decl.source) {
           return None;
         }
 
@@ -360,11 +408,12 @@ impl<'a> EsmVisitor<'a> {
         let local = self.id_to_js_var(&source);
         let toy_import = ToyImport {
           source,
-          kv: HashMap::from([("*".to_string(), local.clone())]),
+          span: export_decl.span,
+          kv: BTreeMap::from([("*".to_string(), local.clone())]),
         };
         let mut toy_export = ToyExport {
           spread: false,
-          kv: HashMap::new(),
+          kv: BTreeMap::new(),
         };
 
         if let Some(export_name) = &export_decl.exported {
@@ -385,7 +434,11 @@ impl<'a> EsmVisitor<'a> {
     }
   }
 
-  fn build_toy_import_stmt(&self, import: &ToyImport) -> Statement<'a> {
+  fn build_toy_import_stmt(&mut self, import: &ToyImport) -> Statement<'a> {
+    if let Some((id, target)) = self.external_sources.get(&import.source).cloned() {
+      return self.build_external_import_stmt(import, &id, &target);
+    }
+
     let call_expr = self.ast_builder.call_expression(
       Span::default(),
       self
@@ -437,7 +490,7 @@ impl<'a> EsmVisitor<'a> {
     }
 
     let var_decl = self.ast_builder.variable_declaration(
-      Span::default(),
+      import.span,
       VariableDeclarationKind::Const,
       self.ast_builder.new_vec_single(
         self.ast_builder.variable_declarator(
@@ -460,6 +513,140 @@ impl<'a> EsmVisitor<'a> {
     Statement::Declaration(Declaration::VariableDeclaration(var_decl))
   }
 
+  /// 外部依赖的 import 转换成读取全局变量或者原生 import 的命名空间：
+  ///
+  /// ```js
+  /// import React, { useState } from 'react';
+  /// ```
+  ///
+  /// ↓↓↓
+  ///
+  /// ```js
+  /// // 全局变量 `React`，默认导出和命名空间都是全局变量本身
+  /// const React = globalThis.React, { useState } = globalThis.React;
+  /// // 原生 import，`import * as _react$toyExternal from 'react'` 会被放到 bundle 的顶层
+  /// const { default: React, useState } = _react$toyExternal;
+  /// ```
+  fn build_external_import_stmt(
+    &mut self,
+    import: &ToyImport,
+    id: &str,
+    target: &ExternalTarget,
+  ) -> Statement<'a> {
+    let namespace = match target {
+      ExternalTarget::Global(name) => name
+        .split('.')
+        .fold("globalThis".to_string(), |expr, name| {
+          format!("{expr}.{name}")
+        }),
+      ExternalTarget::Import => self
+        .external_imports
+        .entry(id.to_string())
+        .or_insert_with(|| external_namespace_var(id))
+        .clone(),
+    };
+    let is_global = matches!(target, ExternalTarget::Global(_));
+
+    let mut declarations = self.ast_builder.new_vec();
+    let mut properties = self.ast_builder.new_vec();
+
+    for (key, value) in &import.kv {
+      if key == "*" || (is_global && key == "default") {
+        declarations.push(
+          self.build_var_declarator(
+            self.ast_builder.binding_pattern(
+              self
+                .ast_builder
+                .binding_pattern_identifier(BindingIdentifier::new(
+                  Span::default(),
+                  value.to_string().into(),
+                )),
+              None,
+              false,
+            ),
+            &namespace,
+          ),
+        );
+      } else {
+        properties.push(
+          self.ast_builder.binding_property(
+            Span::default(),
+            self
+              .ast_builder
+              .property_key_identifier(IdentifierName::new(
+                Span::default(),
+                key.to_string().into(),
+              )),
+            self.ast_builder.binding_pattern(
+              self
+                .ast_builder
+                .binding_pattern_identifier(BindingIdentifier::new(
+                  Span::default(),
+                  value.to_string().into(),
+                )),
+              None,
+              false,
+            ),
+            key == value,
+            false,
+          ),
+        );
+      }
+    }
+
+    // 只有副作用的 import 也保留一个空的解构，保证原生 import 被执行
+    if !properties.is_empty() || declarations.is_empty() {
+      declarations.push(
+        self.build_var_declarator(
+          self.ast_builder.binding_pattern(
+            self
+              .ast_builder
+              .object_pattern(Span::default(), properties, None),
+            None,
+            false,
+          ),
+          &namespace,
+        ),
+      );
+    }
+
+    Statement::Declaration(Declaration::VariableDeclaration(
+      self.ast_builder.variable_declaration(
+        import.span,
+        VariableDeclarationKind::Const,
+        declarations,
+        Modifiers::empty(),
+      ),
+    ))
+  }
+
+  /// `<id> = <expr>`，`expr` 是以 `.` 分隔的成员访问，例如 `globalThis.React`
+  fn build_var_declarator(&self, id: BindingPattern<'a>, expr: &str) -> VariableDeclarator<'a> {
+    let mut names = expr.split('.');
+    let object = self
+      .ast_builder
+      .identifier_reference_expression(IdentifierReference::new(
+        Span::default(),
+        names.next().unwrap().into(),
+      ));
+    let init = names.fold(object, |object, name| {
+      self.ast_builder.static_member_expression(
+        Span::default(),
+        object,
+        IdentifierName::new(Span::default(), name.into()),
+        false,
+      )
+    });
+
+    self.ast_builder.variable_declarator(
+      Span::default(),
+      VariableDeclarationKind::Const,
+      id,
+      Some(init),
+      false,
+    )
+  }
+
   fn build_toy_export_stmt(&self, exports: &Vec<ToyExport>) -> Statement<'a> {
     let mut properties = self.ast_builder.new_vec();
 
@@ -534,23 +721,20 @@ impl<'a> EsmVisitor<'a> {
 impl<'a> VisitMut<'a> for EsmVisitor<'a> {
   fn visit_program(&mut self, program: &mut Program<'a>) {
     for stmt in program.body.iter_mut() {
-      match stmt {
-        Statement::ModuleDeclaration(module_decl) => {
-          // 如果返回了新的语句，则替换原来的语句，
-          // 否则删除原来的语句
-          if let Some(new_stmt) = self.match_module_decl(module_decl) {
-            *stmt = new_stmt;
-          } else {
-            // *stmt = Statement::Empty(AstKind::Empty);
-            self.ast_builder.move_statement(stmt);
-          }
+      if let Statement::ModuleDeclaration(module_decl) = stmt {
+        // 如果返回了新的语句，则替换原来的语句，
+        // 否则删除原来的语句
+        if let Some(new_stmt) = self.match_module_decl(module_decl) {
+          *stmt = new_stmt;
+        } else {
+          // *stmt = Statement::Empty(AstKind::Empty);
+          self.ast_builder.move_statement(stmt);
         }
-        _ => (),
-      };
+      }
     }
 
-    for toy_import in &self.imports {
-      let toy_import_stmt = self.build_toy_import_stmt(toy_import);
+    for toy_import in std::mem::take(&mut self.imports) {
+      let toy_import_stmt = self.build_toy_import_stmt(&toy_import);
       program.body.insert(0, toy_import_stmt);
     }
 
@@ -561,6 +745,42 @@ impl<'a> VisitMut<'a> for EsmVisitor<'a> {
   }
 }
 
-fn is_style_import(source: &StringLiteral) -> bool {
-  source.value.to_string().ends_with(".css")
+/// 保留为原生 import 的外部依赖在 bundle 顶层的命名空间变量名，同一个外部依赖在所有模块中相同
+fn external_namespace_var(id: &str) -> String {
+  let name: String = id
+    .chars()
+    .map(|c| match c {
+      'a'..='z' | 'A'..='Z' | '0'..='9' | '_' => c,
+      _ => '_',
+    })
+    .collect();
+
+  format!("_{name}$toyExternal")
+}
+
+/// 把外部依赖的原生 import 放到 bundle 的顶层：`import * as <var> from '<id>'`
+pub fn external_import_stmts<'a>(
+  ast_builder: &'a AstBuilder<'a>,
+  external_imports: &BTreeMap<String, String>,
+) -> Vec<Statement<'a>> {
+  external_imports
+    .iter()
+    .map(|(id, var)| {
+      let specifier =
+        ImportDeclarationSpecifier::ImportNamespaceSpecifier(ImportNamespaceSpecifier {
+          span: Span::default(),
+          local: BindingIdentifier::new(Span::default(), var.to_string().into()),
+        });
+
+      ast_builder.module_declaration(ModuleDeclaration::ImportDeclaration(
+        ast_builder.import_declaration(
+          Span::default(),
+          Some(ast_builder.new_vec_single(specifier)),
+          StringLiteral::new(Span::default(), id.to_string().into()),
+          None,
+          ImportOrExportKind::Value,
+        ),
+      ))
+    })
+    .collect()
 }
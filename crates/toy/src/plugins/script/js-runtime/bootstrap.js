@@ -0,0 +1,8 @@
+(function bootstrap(modules, entryId) {
+  // 模块系统由运行时（ToyPluginRuntime）挂载到全局变量 `__toyModuleSystem__` 上，需要在入口之前加载
+  __toyModuleSystem__.register(modules);
+
+  if (entryId) {
+    __toyModuleSystem__.require(entryId);
+  }
+})(modules, entryId);
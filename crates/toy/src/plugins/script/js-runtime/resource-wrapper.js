@@ -1,9 +1,3 @@
 (function (modules) {
-  var globalObject =
-    typeof window !== 'undefined' && window ||
-    typeof self !== 'undefined' && self ||
-    typeof global !== 'undefined' && global ||
-    Function("return this")();
-  
-  globalObject.__toyModuleSystem__.register(modules);
+  __toyModuleSystem__.register(modules);
 })(modules);
\ No newline at end of file
@@ -1,4 +1,13 @@
-use std::{boxed::Box, collections::HashMap, fs::read_to_string, path::PathBuf, sync::Arc};
+use std::{
+  boxed::Box,
+  collections::BTreeMap,
+  fs::read_to_string,
+  path::Path,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+};
 
 use oxc::{
   ast::{
@@ -15,9 +24,15 @@ use oxc::{
 use crate::{
   context::CompilationContext,
   error::{CompilationError, Result},
-  module::module::{Module, ModuleKind, ModuleMeta, ScriptModuleMeta},
+  module::{
+    is_virtual_module,
+    module::{Module, ModuleKind, ModuleMeta, ScriptModuleMeta},
+  },
   oxc::OxcProgram,
-  plugin::{AnalyzeDepsHookParams, LoadHookParams, LoadHookResult, ParseHookParams, Plugin},
+  plugin::{
+    filter::PluginFilter, plugin_container::Hook, AnalyzeDepsHookParams, LoadHookParams,
+    LoadHookResult, ParseHookParams, Plugin, TransformHookParams, TransformHookResult,
+  },
   resource::{
     resource::{Resource, ResourceKind, ResourceMap},
     resource_pot::{JsResourcePotMeta, ResourcePot, ResourcePotKind, ResourcePotMeta},
@@ -25,11 +40,19 @@ use crate::{
   utils::fulfill_root_prefix,
 };
 
-use self::{deps_visitor::DepsVisitor, esm_visitor::EsmVisitor, runtime_visitor::RuntimeVisitor};
+use self::{
+  define_visitor::{apply_edits, DefineVisitor},
+  deps_visitor::DepsVisitor,
+  esm_visitor::{external_import_stmts, EsmVisitor},
+  runtime_visitor::RuntimeVisitor,
+  source_map::build_source_map,
+};
 
+mod define_visitor;
 mod deps_visitor;
 mod esm_visitor;
 mod runtime_visitor;
+mod source_map;
 
 pub struct PluginScript {}
 
@@ -54,7 +77,7 @@ impl PluginScript {
   fn wrap_module<'a>(
     &self,
     ast_builder: &'a AstBuilder<'a>,
-    program: Program<'a>,
+    program: &Program<'a>,
   ) -> Expression<'a> {
     let fn_params_item = ["__toyModule__", "__toyRequire__", "__toyDynamicRequire__"].map(|name| {
       ast_builder.formal_parameter(
@@ -103,14 +126,12 @@ impl PluginScript {
     ))
   }
 
-  /// 把 `./js-runtime/module-system.js` 的代码解析成 ast
-  fn get_module_system_ast(&self) -> OxcProgram {
-    let module_system_str = include_str!("./js-runtime/module-system.js");
+  /// 把 `./js-runtime/bootstrap.js` 的代码解析成 ast，它把模块注册到模块系统并执行入口模块。
+  /// 模块系统本身由 `PluginRuntime` 提供
+  fn get_bootstrap_ast(&self) -> OxcProgram {
+    let bootstrap_str = include_str!("./js-runtime/bootstrap.js");
 
-    OxcProgram::build(
-      module_system_str.to_string(),
-      SourceType::from_path("./js-runtime/module-system.js").unwrap(),
-    )
+    OxcProgram::build(bootstrap_str.to_string(), source_type(&ModuleKind::Js))
   }
 }
 
@@ -119,23 +140,46 @@ impl Plugin for PluginScript {
     "ToyPluginScript"
   }
 
+  fn filter(&self) -> PluginFilter {
+    PluginFilter {
+      hooks: vec![
+        Hook::Load,
+        Hook::Transform,
+        Hook::Parse,
+        Hook::AnalyzeDeps,
+        Hook::RenderResourcePot,
+        Hook::GenerateResources,
+      ],
+      module_kinds: vec![
+        ModuleKind::Js,
+        ModuleKind::Jsx,
+        ModuleKind::Ts,
+        ModuleKind::Tsx,
+      ],
+      ..PluginFilter::default()
+    }
+  }
+
   fn load(
     &self,
     params: &LoadHookParams,
     context: &Arc<CompilationContext>,
   ) -> Result<Option<LoadHookResult>> {
-    let module_kind = ModuleKind::from_file_path(&params.id);
+    let module_kind = params.module_kind.clone();
 
-    if module_kind.is_script() {
+    // 虚拟模块没有对应的文件，由提供它的插件负责 load
+    if module_kind.is_script() && !is_virtual_module(&params.id) {
       let path = fulfill_root_prefix(&params.id, &context.config.root);
 
-      let content = read_to_string(path).map_err(|err| CompilationError::LoadError {
+      let content = read_to_string(&path).map_err(|err| CompilationError::LoadError {
         id: params.id.to_string(),
         source: Some(Box::new(err)),
       })?;
 
+      context.add_watch_file(&path);
+
       return Ok(Some(LoadHookResult {
-        content,
+        content: content.into(),
         module_kind,
       }));
     }
@@ -143,21 +187,84 @@ impl Plugin for PluginScript {
     Ok(None)
   }
 
+  /// 替换 `context.defines` 中的标识符，并移除变成死代码的分支
+  fn transform(
+    &self,
+    params: &TransformHookParams,
+    context: &Arc<CompilationContext>,
+  ) -> Result<Option<TransformHookResult>> {
+    let Some(code) = params.content.as_text() else {
+      return Ok(None);
+    };
+
+    let defines = &context.defines;
+
+    // 代码中没有出现任何 define 的标识符时不需要解析，`import` 几乎总是会出现，需要检查 `import.meta`
+    let maybe_defined = defines.keys().any(|key| {
+      let name = if key.starts_with("import.meta") {
+        "import.meta"
+      } else {
+        key.split('.').next().unwrap_or_default()
+      };
+      code.contains(name)
+    });
+
+    if !params.module_kind.is_script() || !maybe_defined {
+      return Ok(None);
+    }
+
+    let program = OxcProgram::try_build(code.to_string(), source_type(&params.module_kind))
+      .map_err(|message| CompilationError::ParseError {
+        id: params.id.clone(),
+        message,
+      })?;
+
+    let mut define_visitor = DefineVisitor::new(defines);
+    program.with_program(|program| define_visitor.visit_program(&program.0));
+
+    if define_visitor.edits.is_empty() {
+      return Ok(None);
+    }
+
+    let (content, source_map) =
+      apply_edits(&context.config.root, &params.id, code, define_visitor.edits).map_err(
+        |message| {
+          CompilationError::GenericError(format!(
+            "Generate source map for `{}` failed: {message}",
+            params.id
+          ))
+        },
+      )?;
+
+    Ok(Some(TransformHookResult {
+      content: content.into(),
+      module_kind: None,
+      source_map: Some(source_map),
+    }))
+  }
+
   fn parse(
     &self,
     params: &ParseHookParams,
     _context: &Arc<CompilationContext>,
   ) -> Result<Option<Module>> {
     if params.module_kind.is_script() {
-      let source_type = SourceType::from_path(params.id.clone()).unwrap();
-      let ast = OxcProgram::build(params.content.clone(), source_type);
+      let code = params.content.try_as_text(&params.id)?.to_string();
+      let source_type = source_type(&params.module_kind);
+      let ast = OxcProgram::try_build(code.clone(), source_type).map_err(|message| {
+        CompilationError::ParseError {
+          id: params.id.clone(),
+          message,
+        }
+      })?;
 
       let module = Module::new(
         params.id.to_string(),
         params.module_kind.clone(),
         Some(ModuleMeta::Script(ScriptModuleMeta {
-          code: params.content.clone(),
+          code,
           ast,
+          ast_rendered: AtomicBool::new(false),
         })),
       );
 
@@ -199,32 +306,65 @@ impl Plugin for PluginScript {
         // 遍历 resource_pot 里的每一个模块，用 wrapper 函数包裹模块
         let ast_builder = context.ast_builder.get_ast_builder();
 
-        for module_id in resource_pot.module_ids.iter() {
-          let module = module_graph.module(module_id).unwrap();
-          let mut program = module.meta.as_script().ast.copy_program();
-          let mut esm_visitor = EsmVisitor::new(ast_builder, module_id, &module_graph);
+        // 首次渲染直接使用模块上的 ast。`copy_program` 是浅拷贝，EsmVisitor 会原地修改 ast，
+        // 所以再次渲染同一个模块（例如增量编译时复用的模块）需要根据模块代码重新解析
+        let reparsed_programs = resource_pot
+          .module_ids
+          .iter()
+          .map(|module_id| {
+            let module = module_graph.module(module_id).unwrap();
+            let meta = module.meta.as_script();
+
+            meta
+              .ast_rendered
+              .swap(true, Ordering::SeqCst)
+              .then(|| OxcProgram::build(meta.code.clone(), source_type(&module.kind)))
+          })
+          .collect::<Vec<_>>();
+        let oxc_programs = resource_pot
+          .module_ids
+          .iter()
+          .zip(&reparsed_programs)
+          .map(|(module_id, reparsed_program)| {
+            reparsed_program
+              .as_ref()
+              .unwrap_or_else(|| &module_graph.module(module_id).unwrap().meta.as_script().ast)
+          })
+          .collect::<Vec<_>>();
+
+        let bootstrap_oxc_program = self.get_bootstrap_ast();
+        // 经过 EsmVisitor 转换后的模块 ast，用于生成 source map
+        let mut programs = vec![];
+        // 所有模块中保留为原生 import 的外部依赖
+        let mut external_imports = BTreeMap::new();
+
+        for (module_id, oxc_program) in resource_pot.module_ids.iter().zip(&oxc_programs) {
+          let mut program = oxc_program.copy_program();
+          let mut esm_visitor =
+            EsmVisitor::new(ast_builder, module_id, &module_graph, &context.config);
 
           esm_visitor.visit_program(&mut program);
+          external_imports.append(&mut esm_visitor.external_imports);
 
           modules_object_properties_vec.push(ObjectPropertyKind::ObjectProperty(
             ast_builder.object_property(
               Span::default(),
               PropertyKind::Init,
               ast_builder.property_key_expression(ast_builder.literal_string_expression(
-                StringLiteral::new(Span::default(), module.id.clone().into()),
+                StringLiteral::new(Span::default(), module_id.clone().into()),
               )),
-              self.wrap_module(&ast_builder, program),
+              self.wrap_module(ast_builder, &program),
               None,
               false,
               false,
               false,
             ),
           ));
+          programs.push(program);
         }
 
-        // 注入模块系统运行时
-        let runtime_oxc_program = self.get_module_system_ast();
-        let mut runtime_program = runtime_oxc_program.copy_program();
+        // 用启动代码包裹所有模块
+        let mut bootstrap_program = bootstrap_oxc_program.copy_program();
 
         let mut modules_object_properties = ast_builder.new_vec();
         modules_object_properties.extend(modules_object_properties_vec);
@@ -234,21 +374,56 @@ impl Plugin for PluginScript {
         let entry_id_expr = ast_builder
           .literal_string_expression(StringLiteral::new(Span::default(), resource_id.into()));
         let mut runtime_visitor =
-          RuntimeVisitor::new(&ast_builder, modules_object_expr, entry_id_expr);
+          RuntimeVisitor::new(ast_builder, modules_object_expr, entry_id_expr);
+
+        runtime_visitor.visit_program(&mut bootstrap_program);
 
-        runtime_visitor.visit_program(&mut runtime_program);
+        for (index, stmt) in external_import_stmts(ast_builder, &external_imports)
+          .into_iter()
+          .enumerate()
+        {
+          bootstrap_program.body.insert(index, stmt);
+        }
 
         let source_len = resource_pot.module_ids.iter().fold(0, |acc, module_id| {
           let module = module_graph.module(module_id).unwrap();
           acc + module.meta.as_script().code.len()
         });
 
-        let code = Codegen::<false>::new(source_len, CodegenOptions).build(&runtime_program);
-
-        resource_pot.meta = ResourcePotMeta::Js(JsResourcePotMeta { ast: None, code });
+        let code = Codegen::<false>::new(source_len, CodegenOptions).build(&bootstrap_program);
+
+        let source_map = if context.config.output.source_map {
+          let modules = resource_pot
+            .module_ids
+            .iter()
+            .map(|module_id| module_graph.module(module_id).unwrap())
+            .zip(&programs)
+            .collect::<Vec<_>>();
+
+          Some(build_source_map(
+            ast_builder,
+            &context.config.root,
+            &resource_pot.id,
+            &code,
+            &modules,
+          )?)
+        } else {
+          None
+        };
+
+        resource_pot.meta = ResourcePotMeta::Js(JsResourcePotMeta {
+          ast: None,
+          code,
+          source_map,
+          esm: !external_imports.is_empty(),
+        });
       } else {
         // 动态加载的 JS 模块，注入模块注册运行时
         // TODO
+        context.add_warning(format!(
+          "Dynamic imported module `{}` is not supported yet and will not be rendered",
+          resource_pot.id
+        ));
       }
     }
 
@@ -261,21 +436,46 @@ impl Plugin for PluginScript {
     _context: &Arc<CompilationContext>,
   ) -> Result<Option<ResourceMap>> {
     if let ResourcePotMeta::Js(js_resource_pot_meta) = &resource_pot.meta {
-      let mut resource_map: ResourceMap = HashMap::new();
+      let mut resource_map = ResourceMap::new();
       let resource_id = resource_pot.id.clone();
 
+      let mut content = js_resource_pot_meta.code.clone();
+      let source_map_id = format!("{resource_id}.map");
+
+      if js_resource_pot_meta.source_map.is_some() {
+        // sourceMappingURL 相对于 js 资源所在的目录
+        let source_map_file_name = Path::new(&source_map_id).file_name().unwrap();
+        content.push_str(&format!(
+          "\n//# sourceMappingURL={}",
+          source_map_file_name.to_string_lossy()
+        ));
+      }
+
       resource_map.insert(
         resource_id.clone(),
         Resource {
           name: resource_id.clone(),
-          content: js_resource_pot_meta.code.clone(),
+          content: content.into(),
           resource_kind: ResourceKind::Js,
           resource_pot_id: resource_id.clone(),
           emitted: false,
         },
       );
-
-      resource_pot.resource_ids.push(resource_id);
+      resource_pot.resource_ids.push(resource_id.clone());
+
+      if let Some(source_map) = &js_resource_pot_meta.source_map {
+        resource_map.insert(
+          source_map_id.clone(),
+          Resource {
+            name: source_map_id.clone(),
+            content: source_map.clone().into(),
+            resource_kind: ResourceKind::SourceMap,
+            resource_pot_id: resource_id,
+            emitted: false,
+          },
+        );
+        resource_pot.resource_ids.push(source_map_id);
+      }
 
       return Ok(Some(resource_map));
     }
@@ -284,17 +484,76 @@ impl Plugin for PluginScript {
   }
 }
 
+/// 根据模块类型得到解析用的 `SourceType`，与 `SourceType::from_path` 的结果一致，
+/// 但不依赖 id 的扩展名，虚拟模块也可以使用
+fn source_type(module_kind: &ModuleKind) -> SourceType {
+  SourceType::default()
+    .with_module(true)
+    .with_typescript(matches!(module_kind, ModuleKind::Ts | ModuleKind::Tsx))
+    .with_jsx(!matches!(module_kind, ModuleKind::Ts))
+}
+
 #[cfg(test)]
 mod tests {
-  use std::{collections::HashMap, fs};
+  use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    process::Command,
+  };
 
-  use crate::config::Config;
+  use parcel_sourcemap::SourceMap;
+
+  use regex::Regex;
+
+  use crate::{
+    config::{Config, ExternalConfig, ExternalMatcher, ExternalTarget, Mode, OutputConfig},
+    utils::node_available,
+    Compiler,
+  };
 
   use super::*;
 
+  /// 在 `./index.js` 顶部插入一行注释，并返回对应的 source map
+  struct PluginBanner;
+
+  impl Plugin for PluginBanner {
+    fn name(&self) -> &str {
+      "TestPluginBanner"
+    }
+
+    fn transform(
+      &self,
+      params: &TransformHookParams,
+      _context: &Arc<CompilationContext>,
+    ) -> Result<Option<TransformHookResult>> {
+      if params.id != "./index.js" {
+        return Ok(None);
+      }
+
+      // 第 0 行是插入的注释，之后的每一行对应原来的一行
+      let mappings = params
+        .content
+        .as_text()
+        .unwrap()
+        .lines()
+        .enumerate()
+        .map(|(index, _)| if index == 0 { "AAAA" } else { "AACA" })
+        .collect::<Vec<_>>()
+        .join(";");
+
+      Ok(Some(TransformHookResult {
+        content: format!("// banner\n{}", params.content.as_text().unwrap()).into(),
+        module_kind: None,
+        source_map: Some(format!(
+          r#"{{"version":3,"sources":["./index.js"],"names":[],"mappings":";{mappings}"}}"#
+        )),
+      }))
+    }
+  }
+
   #[test]
   fn test_load() {
-    let context = CompilationContext::new(Config::default(), vec![]);
+    let context = CompilationContext::new(Config::default(), vec![]).unwrap();
     let plugin_script = PluginScript::new();
 
     let res = plugin_script
@@ -305,13 +564,232 @@ mod tests {
             .to_string_lossy()
             .to_string(),
           query: HashMap::new(),
+          module_kind: ModuleKind::Js,
         },
         &Arc::new(context),
       )
       .unwrap()
       .unwrap();
 
-    assert!(!res.content.is_empty());
+    assert!(!res.content.as_bytes().is_empty());
     assert_eq!(res.module_kind, ModuleKind::Js);
   }
+
+  #[test]
+  fn test_source_map() {
+    let root = fs::canonicalize("../../fixtures/basic").unwrap();
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          dir: "./dist-source-map".to_string(),
+          write: false,
+          source_map: true,
+          ..OutputConfig::default()
+        },
+        ..Config::default()
+      },
+      vec![Arc::new(PluginBanner)],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let output = compiler.output();
+    let code = output.content("./index.js").unwrap();
+
+    assert!(code.ends_with("\n//# sourceMappingURL=index.js.map"));
+    assert_eq!(output.entries["main"], vec!["./index.js", "./index.js.map"]);
+    assert_eq!(
+      output.resources["./index.js.map"].kind,
+      ResourceKind::SourceMap
+    );
+
+    let mut source_map = SourceMap::from_json(
+      &root.to_string_lossy(),
+      output.content("./index.js.map").unwrap(),
+    )
+    .unwrap();
+    let find_original = |source_map: &mut SourceMap, needle: &str| {
+      let (line, column) = crate::source_map::offset_to_position(code, code.find(needle).unwrap());
+      let original = source_map
+        .find_closest_mapping(line, column)
+        .unwrap()
+        .original
+        .unwrap();
+
+      (
+        source_map.get_source(original.source).unwrap().to_string(),
+        original.original_line,
+        original.original_column,
+      )
+    };
+
+    // index.js 经过 PluginBanner 和 EsmVisitor 两次转换，仍然映射到原始代码
+    assert_eq!(
+      find_original(&mut source_map, "console.log("),
+      ("index.js".to_string(), 3, 0)
+    );
+    assert_eq!(
+      find_original(&mut source_map, "const {bar} = __toyRequire__"),
+      ("index.js".to_string(), 1, 0)
+    );
+    assert_eq!(
+      find_original(&mut source_map, "function foz()"),
+      ("foz.js".to_string(), 0, 7)
+    );
+  }
+
+  /// 用 node 执行 `fixtures/define` 在 `mode` 下的构建结果，返回代码和输出
+  fn run_define(mode: Mode) -> (String, String) {
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/define")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        mode,
+        output: OutputConfig {
+          write: false,
+          ..OutputConfig::default()
+        },
+        define: BTreeMap::from([
+          ("__VERSION__".to_string(), "'1.0.0'".to_string()),
+          ("__FEATURE__".to_string(), "true".to_string()),
+        ]),
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let code = compiler.output().content("./index.js").unwrap().to_string();
+    let output = Command::new("node").arg("-e").arg(&code).output().unwrap();
+
+    assert!(output.status.success(), "{:?}", output);
+    (code, String::from_utf8(output.stdout).unwrap())
+  }
+
+  #[test]
+  fn test_define() {
+    if !node_available() {
+      return;
+    }
+
+    let (code, stdout) = run_define(Mode::Production);
+    assert_eq!(stdout, "production only\nproduction_1.0.0_true_prod\n");
+    assert!(!code.contains("development only"));
+    assert!(!code.contains("process.env"));
+
+    let (code, stdout) = run_define(Mode::Development);
+    assert_eq!(
+      stdout,
+      "development only\nwarn\ndevelopment_1.0.0_true_dev\n"
+    );
+    assert!(!code.contains("production only"));
+  }
+
+  #[test]
+  fn test_define_visitor() {
+    let defines = Config {
+      mode: Mode::Production,
+      ..Config::default()
+    }
+    .defines();
+    let code = "if (process.env.NODE_ENV === 'production') {\n  a();\n} else {\n  b();\n}\nc(import.meta.env.DEV || d);\n";
+    let program = OxcProgram::build(code.to_string(), SourceType::default());
+    let mut define_visitor = DefineVisitor::new(&defines);
+    program.with_program(|program| define_visitor.visit_program(&program.0));
+
+    // 被移除的分支保留换行，`c()` 仍然在第 6 行
+    let (result, source_map) = apply_edits("/", "./a.js", code, define_visitor.edits).unwrap();
+
+    assert_eq!(result, "{\n  a();\n}\n\n\nc((d));\n");
+
+    // `d` 在结果中向左偏移了，source map 映射回原始代码中的列，sourcesContent 为原始代码
+    let mut source_map = SourceMap::from_json("/", &source_map).unwrap();
+    let mapping = source_map.find_closest_mapping(5, 3).unwrap();
+    let original = mapping.original.unwrap();
+
+    assert_eq!((original.original_line, original.original_column), (5, 25));
+    assert_eq!(
+      source_map.get_source_content(original.source).unwrap(),
+      code
+    );
+  }
+
+  #[test]
+  fn test_define_visitor_scope() {
+    let defines = BTreeMap::from([
+      ("__X__".to_string(), "1 + 1".to_string()),
+      ("__DEV__".to_string(), "true".to_string()),
+      (
+        "process.env.NODE_ENV".to_string(),
+        "\"production\"".to_string(),
+      ),
+    ]);
+    let code = "a(__X__ * 2, { __X__ }, __DEV__);\nfunction f(__DEV__) {\n  return __DEV__;\n}\nfunction g() {\n  if (x) { var process = {}; }\n  return process.env.NODE_ENV;\n}\n{\n  const __X__ = 3;\n  b(__X__);\n}\nc(__X__, process.env.NODE_ENV);\n";
+    let program = OxcProgram::build(code.to_string(), SourceType::default());
+    let mut define_visitor = DefineVisitor::new(&defines);
+    program.with_program(|program| define_visitor.visit_program(&program.0));
+
+    // 非字面量的值用括号包裹，局部变量不替换
+    assert_eq!(
+      apply_edits("/", "./a.js", code, define_visitor.edits)
+        .unwrap()
+        .0,
+      "a((1 + 1) * 2, { __X__: (1 + 1) }, true);\nfunction f(__DEV__) {\n  return __DEV__;\n}\nfunction g() {\n  if (x) { var process = {}; }\n  return process.env.NODE_ENV;\n}\n{\n  const __X__ = 3;\n  b(__X__);\n}\nc((1 + 1), \"production\");\n"
+    );
+  }
+
+  #[test]
+  fn test_externals() {
+    let root = fs::canonicalize("../../fixtures/externals").unwrap();
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        output: OutputConfig {
+          write: false,
+          ..OutputConfig::default()
+        },
+        externals: vec![
+          ExternalConfig {
+            matcher: ExternalMatcher::Exact("react".to_string()),
+            target: ExternalTarget::Global("React".to_string()),
+          },
+          ExternalConfig {
+            matcher: ExternalMatcher::Prefix("date-fns/".to_string()),
+            target: ExternalTarget::Import,
+          },
+          ExternalConfig {
+            matcher: ExternalMatcher::Pattern(Regex::new("^day").unwrap()),
+            target: ExternalTarget::Import,
+          },
+        ],
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let output = compiler.output();
+    let code = output.content("./index.js").unwrap();
+
+    // 内联的运行时位于 import 之前，import 声明会被提升
+    assert!(code.contains(
+      "import * as _date_fns_format$toyExternal from 'date-fns/format';\n\
+       import * as _dayjs$toyExternal from 'dayjs';\n"
+    ));
+    assert!(code.contains("const React = globalThis.React, {useState} = globalThis.React;"));
+    assert!(code.contains("const dayjs = _dayjs$toyExternal;"));
+    assert!(code.contains("const {format} = _date_fns_format$toyExternal;"));
+    assert!(code.contains("__toyRequire__('./foo.js')"));
+    assert!(!code.contains("__toyRequire__('react')"));
+    // 外部依赖不会被打包
+    assert_eq!(output.resources.len(), 1);
+  }
 }
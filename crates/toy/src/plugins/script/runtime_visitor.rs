@@ -3,7 +3,7 @@ use oxc::ast::{
   AstBuilder, VisitMut,
 };
 
-/// 给入口 resource 注入模块系统 runtime
+/// 把模块和入口 id 作为参数传给入口 resource 的启动代码
 pub struct RuntimeVisitor<'a> {
   ast_builder: &'a AstBuilder<'a>,
   modules_object_expr: Expression<'a>,
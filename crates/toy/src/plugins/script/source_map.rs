@@ -0,0 +1,124 @@
+use oxc::{
+  ast::{
+    ast::{Program, Statement},
+    AstBuilder,
+  },
+  codegen::{Codegen, CodegenOptions},
+  span::GetSpan,
+};
+use parcel_sourcemap::{OriginalLocation, SourceMap};
+
+use crate::{
+  error::{CompilationError, Result},
+  module::module::Module,
+  source_map::{compose_chain, merge_into, offset_to_position},
+};
+
+/// 生成入口 JS resource_pot 的 source map。
+///
+/// oxc 的 codegen 还不支持生成 source map，这里以顶层语句为粒度建立映射：
+/// 单独生成模块中每一条顶层语句的代码，按顺序在 bundle 代码里查找它的第一行，
+/// 映射到语句在模块代码中的位置，再通过模块的 `source_map_chain` 映射回 transform 之前的代码。
+/// `modules` 中的 program 是经过 `EsmVisitor` 转换后的 ast
+pub fn build_source_map<'a>(
+  ast_builder: &'a AstBuilder<'a>,
+  root: &str,
+  resource_pot_id: &str,
+  code: &str,
+  modules: &[(&Module, &Program<'a>)],
+) -> Result<String> {
+  let mut source_map = SourceMap::new(root);
+
+  for (module, program) in modules {
+    let mut module_source_map = module_source_map(ast_builder, root, code, module, program);
+
+    compose_chain(&mut module_source_map, &module.source_map_chain)
+      .and_then(|_| merge_into(&mut source_map, &module_source_map))
+      .map_err(|err| source_map_error(&module.id, err))?;
+  }
+
+  source_map
+    .to_json(None)
+    .map_err(|err| source_map_error(resource_pot_id, err.to_string()))
+}
+
+/// 单个模块的 source map，从 bundle 代码映射到模块代码
+fn module_source_map<'a>(
+  ast_builder: &'a AstBuilder<'a>,
+  root: &str,
+  code: &str,
+  module: &Module,
+  program: &Program<'a>,
+) -> SourceMap {
+  let mut source_map = SourceMap::new(root);
+  let module_code = &module.meta.as_script().code;
+  let source = source_map.add_source(&module.id);
+  let _ = source_map.set_source_content(source as usize, module_code);
+
+  // 模块在 bundle 中以 `'<module_id>': function(...) { ... }` 的形式出现
+  let Some(mut cursor) = code
+    .find(&format!("'{}':", module.id))
+    .map(|index| index + module.id.len() + 3)
+  else {
+    return source_map;
+  };
+
+  for stmt in &program.body {
+    let span = stmt.span();
+
+    // 被删除的 import / export 语句，以及 `EsmVisitor` 生成的导出语句没有对应的原始位置
+    if matches!(stmt, Statement::EmptyStatement(_)) || span.end == 0 {
+      continue;
+    }
+
+    let stmt_code = codegen_statement(ast_builder, program, stmt);
+    let Some(first_line) = stmt_code
+      .lines()
+      .next()
+      .map(str::trim)
+      .filter(|l| !l.is_empty())
+    else {
+      continue;
+    };
+    let Some(index) = code[cursor..].find(first_line) else {
+      continue;
+    };
+
+    let (generated_line, generated_column) = offset_to_position(code, cursor + index);
+    let (original_line, original_column) = offset_to_position(module_code, span.start as usize);
+
+    source_map.add_mapping(
+      generated_line,
+      generated_column,
+      Some(OriginalLocation::new(
+        original_line,
+        original_column,
+        source,
+        None,
+      )),
+    );
+    cursor += index + first_line.len();
+  }
+
+  source_map
+}
+
+fn codegen_statement<'a>(
+  ast_builder: &'a AstBuilder<'a>,
+  program: &Program<'a>,
+  stmt: &Statement<'a>,
+) -> String {
+  let program = ast_builder.program(
+    stmt.span(),
+    program.source_type,
+    ast_builder.new_vec(),
+    None,
+    ast_builder.new_vec_single(ast_builder.copy(stmt)),
+  );
+
+  Codegen::<false>::new(0, CodegenOptions).build(&program)
+}
+
+fn source_map_error(id: &str, message: String) -> CompilationError {
+  CompilationError::GenericError(format!("Generate source map for `{id}` failed: {message}"))
+}
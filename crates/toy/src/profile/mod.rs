@@ -0,0 +1,219 @@
+use std::{
+  cell::Cell,
+  collections::BTreeMap,
+  fs,
+  path::Path,
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+  },
+  thread,
+  time::Instant,
+};
+
+use serde::Serialize;
+
+use crate::{
+  error::{CompilationError, Result},
+  Compiler,
+};
+
+/// Chrome trace_event 格式中的一个事件，可以在 `chrome://tracing` 或 Perfetto 中查看
+#[derive(Debug, Serialize)]
+struct TraceEvent {
+  name: String,
+  cat: &'static str,
+  /// `X` 表示带持续时间的完整事件，`M` 表示元数据事件
+  ph: &'static str,
+  /// 开始时间，单位为微秒
+  #[serde(skip_serializing_if = "Option::is_none")]
+  ts: Option<u128>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  dur: Option<u128>,
+  pid: u32,
+  tid: u64,
+  args: BTreeMap<&'static str, String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace<'a> {
+  trace_events: &'a [TraceEvent],
+  display_time_unit: &'static str,
+}
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+  static THREAD_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+/// 当前线程的 id，按照线程第一次记录事件的顺序分配
+fn current_thread_id() -> u64 {
+  THREAD_ID.with(|id| {
+    if id.get() == 0 {
+      id.set(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed));
+    }
+
+    id.get()
+  })
+}
+
+/// 记录每个插件每个钩子的耗时
+pub struct Profiler {
+  start: Instant,
+  events: Mutex<Vec<TraceEvent>>,
+  /// 线程 id -> 线程名
+  threads: Mutex<BTreeMap<u64, String>>,
+}
+
+impl Profiler {
+  pub fn new() -> Self {
+    Self {
+      start: Instant::now(),
+      events: Mutex::new(vec![]),
+      threads: Mutex::new(BTreeMap::new()),
+    }
+  }
+
+  /// 记录一次从 `start` 开始到现在的钩子调用
+  pub fn record(
+    &self,
+    plugin: &str,
+    hook: &'static str,
+    module_id: Option<String>,
+    start: Instant,
+  ) {
+    let tid = current_thread_id();
+    let mut args = BTreeMap::from([("plugin", plugin.to_string()), ("hook", hook.to_string())]);
+
+    if let Some(module_id) = module_id {
+      args.insert("module_id", module_id);
+    }
+
+    let event = TraceEvent {
+      name: format!("{plugin}.{hook}"),
+      cat: hook,
+      ph: "X",
+      ts: Some(start.duration_since(self.start).as_micros()),
+      dur: Some(start.elapsed().as_micros()),
+      pid: std::process::id(),
+      tid,
+      args,
+    };
+
+    self.events.lock().unwrap().push(event);
+    self.threads.lock().unwrap().entry(tid).or_insert_with(|| {
+      thread::current()
+        .name()
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("thread-{tid}"))
+    });
+  }
+
+  /// 把目前记录的所有事件写入 `path`
+  pub fn write(&self, path: &Path) -> Result<()> {
+    let events = self.events.lock().unwrap();
+    let threads = self.threads.lock().unwrap();
+    let mut trace_events = threads
+      .iter()
+      .map(|(tid, name)| TraceEvent {
+        name: "thread_name".to_string(),
+        cat: "__metadata",
+        ph: "M",
+        ts: None,
+        dur: None,
+        pid: std::process::id(),
+        tid: *tid,
+        args: BTreeMap::from([("name", name.clone())]),
+      })
+      .collect::<Vec<_>>();
+
+    trace_events.extend(events.iter().map(|event| TraceEvent {
+      name: event.name.clone(),
+      args: event.args.clone(),
+      ..*event
+    }));
+
+    let content = serde_json::to_vec(&Trace {
+      trace_events: &trace_events,
+      display_time_unit: "ms",
+    })
+    .map_err(|err| CompilationError::GenericError(format!("Serialize profile failed: {err}")))?;
+
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).map_err(profile_write_error)?;
+    }
+
+    fs::write(path, content).map_err(profile_write_error)
+  }
+}
+
+impl Default for Profiler {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+fn profile_write_error(err: std::io::Error) -> CompilationError {
+  CompilationError::GenericError(format!("Write profile failed: {err}"))
+}
+
+impl Compiler {
+  /// 开启 profile 时，把目前记录的所有事件写入 `config.profile.path`
+  pub(crate) fn write_profile(&self) -> Result<()> {
+    match (&self.context.profiler, &self.context.config.profile) {
+      (Some(profiler), Some(profile_config)) => {
+        profiler.write(&Path::new(&self.context.config.root).join(&profile_config.path))
+      }
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{collections::BTreeMap, fs};
+
+  use crate::{
+    config::{Config, ProfileConfig},
+    Compiler,
+  };
+
+  #[test]
+  fn test_write_profile() {
+    let path = std::env::temp_dir().join(format!("toy-profile-{}.json", std::process::id()));
+    let mut compiler = Compiler::new(
+      Config {
+        root: fs::canonicalize("../../fixtures/basic")
+          .unwrap()
+          .to_string_lossy()
+          .to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        profile: Some(ProfileConfig {
+          path: path.to_string_lossy().to_string(),
+        }),
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+    compiler.compile().unwrap();
+
+    let trace: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+    let events = trace["traceEvents"].as_array().unwrap();
+
+    assert!(events.iter().any(|event| {
+      event["name"] == "ToyPluginScript.parse" && event["args"]["module_id"] == "./index.js"
+    }));
+    assert!(events
+      .iter()
+      .any(|event| event["name"] == "ToyPluginScript.render_resource_pot"));
+    assert!(events
+      .iter()
+      .filter(|event| event["ph"] == "X")
+      .all(|event| event["dur"].is_u64() && event["tid"].is_u64()));
+
+    fs::remove_file(path).unwrap();
+  }
+}
@@ -0,0 +1,253 @@
+use std::{
+  fmt,
+  io::{self, IsTerminal, Write},
+  sync::Mutex,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// 日志级别，级别越高输出越详细
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+  Error,
+  Warn,
+  Info,
+  Debug,
+}
+
+impl fmt::Display for LogLevel {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      Self::Error => "error",
+      Self::Warn => "warn",
+      Self::Info => "info",
+      Self::Debug => "debug",
+    };
+
+    f.write_str(name)
+  }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+  /// 适合终端阅读的格式
+  #[default]
+  Pretty,
+  /// 每行一个 json 对象，适合被其他工具消费
+  Json,
+}
+
+#[derive(Debug, Default)]
+pub struct LogConfig {
+  /// 输出的最高日志级别，为 `None` 时不输出任何日志
+  pub level: Option<LogLevel>,
+  pub format: LogFormat,
+}
+
+/// 编译过程中的日志都会通过 Reporter 输出
+pub trait Reporter: Send + Sync {
+  /// 是否需要输出该级别的日志，用于跳过昂贵的日志格式化
+  fn enabled(&self, level: LogLevel) -> bool;
+
+  fn report(&self, level: LogLevel, message: &str);
+}
+
+/// 输出日志，只有 reporter 开启了对应级别时才会格式化日志内容
+///
+/// ```ignore
+/// report!(context, LogLevel::Debug, "resolve_result: {resolve_result:#?}");
+/// ```
+macro_rules! report {
+  ($context:expr, $level:expr, $($arg:tt)+) => {
+    if $context.reporter.enabled($level) {
+      $context.reporter.report($level, &format!($($arg)+));
+    }
+  };
+}
+
+pub(crate) use report;
+
+pub fn create_reporter(config: &LogConfig) -> Box<dyn Reporter> {
+  match config.format {
+    LogFormat::Pretty => Box::new(
+      PrettyReporter::new(config.level, Box::new(io::stderr()))
+        .with_color(io::stderr().is_terminal()),
+    ),
+    LogFormat::Json => Box::new(JsonReporter::new(config.level, Box::new(io::stdout()))),
+  }
+}
+
+/// 带颜色的终端日志
+pub struct PrettyReporter {
+  level: Option<LogLevel>,
+  colored: bool,
+  writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl PrettyReporter {
+  pub fn new(level: Option<LogLevel>, writer: Box<dyn Write + Send>) -> Self {
+    Self {
+      level,
+      colored: false,
+      writer: Mutex::new(writer),
+    }
+  }
+
+  /// 是否使用 ANSI 颜色输出日志级别
+  pub fn with_color(mut self, colored: bool) -> Self {
+    self.colored = colored;
+    self
+  }
+}
+
+impl Reporter for PrettyReporter {
+  fn enabled(&self, level: LogLevel) -> bool {
+    self.level.is_some_and(|max_level| level <= max_level)
+  }
+
+  fn report(&self, level: LogLevel, message: &str) {
+    if !self.enabled(level) {
+      return;
+    }
+
+    let label = if self.colored {
+      let color = match level {
+        LogLevel::Error => "31",
+        LogLevel::Warn => "33",
+        LogLevel::Info => "36",
+        LogLevel::Debug => "90",
+      };
+
+      format!("\x1b[{color}m{level}\x1b[0m")
+    } else {
+      level.to_string()
+    };
+
+    let mut writer = self.writer.lock().unwrap();
+    let _ = writeln!(writer, "[toy] {label}: {message}");
+  }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+  level: LogLevel,
+  message: &'a str,
+  /// 毫秒时间戳
+  timestamp: u128,
+}
+
+/// json lines 格式的日志
+pub struct JsonReporter {
+  level: Option<LogLevel>,
+  writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonReporter {
+  pub fn new(level: Option<LogLevel>, writer: Box<dyn Write + Send>) -> Self {
+    Self {
+      level,
+      writer: Mutex::new(writer),
+    }
+  }
+}
+
+impl Reporter for JsonReporter {
+  fn enabled(&self, level: LogLevel) -> bool {
+    self.level.is_some_and(|max_level| level <= max_level)
+  }
+
+  fn report(&self, level: LogLevel, message: &str) {
+    if !self.enabled(level) {
+      return;
+    }
+
+    let line = JsonLine {
+      level,
+      message,
+      timestamp: SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default(),
+    };
+
+    if let Ok(line) = serde_json::to_string(&line) {
+      let mut writer = self.writer.lock().unwrap();
+      let _ = writeln!(writer, "{line}");
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use super::*;
+
+  /// 把输出写到共享 buffer 中，便于断言
+  #[derive(Clone, Default)]
+  struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+  impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.lock().unwrap().extend_from_slice(buf);
+      Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  impl SharedBuffer {
+    fn lines(&self) -> Vec<String> {
+      String::from_utf8(self.0.lock().unwrap().clone())
+        .unwrap()
+        .lines()
+        .map(|line| line.to_string())
+        .collect()
+    }
+  }
+
+  #[test]
+  fn test_pretty_reporter() {
+    let buffer = SharedBuffer::default();
+    let reporter = PrettyReporter::new(Some(LogLevel::Warn), Box::new(buffer.clone()));
+
+    reporter.report(LogLevel::Error, "boom");
+    reporter.report(LogLevel::Warn, "careful");
+    reporter.report(LogLevel::Info, "hidden");
+
+    assert!(!reporter.enabled(LogLevel::Debug));
+    assert_eq!(buffer.lines().len(), 2);
+    assert!(buffer.lines()[0].ends_with("boom"));
+    assert!(buffer.lines()[1].ends_with("careful"));
+  }
+
+  #[test]
+  fn test_json_reporter() {
+    let buffer = SharedBuffer::default();
+    let reporter = JsonReporter::new(Some(LogLevel::Debug), Box::new(buffer.clone()));
+
+    reporter.report(LogLevel::Info, "hello \"toy\"");
+
+    let lines = buffer.lines();
+    let value: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+
+    assert_eq!(value["level"], "info");
+    assert_eq!(value["message"], "hello \"toy\"");
+  }
+
+  #[test]
+  fn test_quiet_by_default() {
+    let buffer = SharedBuffer::default();
+    let reporter = PrettyReporter::new(LogConfig::default().level, Box::new(buffer.clone()));
+
+    reporter.report(LogLevel::Error, "boom");
+
+    assert!(!reporter.enabled(LogLevel::Error));
+    assert!(buffer.lines().is_empty());
+  }
+}
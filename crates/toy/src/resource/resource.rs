@@ -1,6 +1,10 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+use crate::content::Content;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ResourceKind {
   Runtime,
   Html,
@@ -11,14 +15,14 @@ pub enum ResourceKind {
   Custom(String),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Resource {
   pub name: String,
-  pub content: String,
+  pub content: Content,
   pub resource_kind: ResourceKind,
   /// whether the resource is emitted
   pub emitted: bool,
   pub resource_pot_id: String,
 }
 
-pub type ResourceMap = HashMap<String, Resource>;
+pub type ResourceMap = BTreeMap<String, Resource>;
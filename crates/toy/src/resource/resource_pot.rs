@@ -1,11 +1,7 @@
-use oxc::{allocator::Box as OxcBox, ast::ast::Program};
-use std::{any::Any, collections::HashMap};
+use std::{any::Any, collections::BTreeMap};
 use swc_html::ast::Document;
 
-use crate::{
-  module::module::{ModuleKind, ScriptModuleMeta},
-  oxc::{OxcProgram, OxcProgramWrapper},
-};
+use crate::{content::Content, module::module::ModuleKind, resource::resource::ResourceMap};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ResourcePotKind {
@@ -34,6 +30,8 @@ pub enum ResourcePotMeta {
   Html(HtmlResourcePotMeta),
   Css(CssResourcePotMeta),
   Js(JsResourcePotMeta),
+  Runtime(RuntimeResourcePotMeta),
+  Asset(AssetResourcePotMeta),
   Custom(Box<dyn Any + Send + Sync>),
 }
 
@@ -82,6 +80,22 @@ pub struct CssResourcePotMeta {
 pub struct JsResourcePotMeta {
   pub ast: Option<Box<dyn Any + Send + Sync>>,
   pub code: String,
+  /// `config.output.source_map` 开启时生成的 source map
+  pub source_map: Option<String>,
+  /// 代码中包含原生 import（保留为 import 的外部依赖），需要以 `<script type="module">` 加载
+  pub esm: bool,
+}
+
+#[derive(Debug)]
+pub struct RuntimeResourcePotMeta {
+  /// 模块系统以及插件添加的运行时模块的代码
+  pub code: String,
+}
+
+#[derive(Debug)]
+pub struct AssetResourcePotMeta {
+  /// 模块 id -> 资源内容，每个静态资源模块生成一个单独的资源
+  pub assets: BTreeMap<String, Content>,
 }
 
 #[derive(Debug)]
@@ -92,6 +106,8 @@ pub struct ResourcePot {
   pub module_ids: Vec<String>,
   pub resource_ids: Vec<String>,
   pub meta: ResourcePotMeta,
+  /// render_resource_pot、generate_resources 时插件通过 `emit_file` 添加的资源
+  pub emitted_files: ResourceMap,
 }
 
 impl ResourcePot {
@@ -103,8 +119,9 @@ impl ResourcePot {
       module_ids: vec![id],
       resource_ids: vec![],
       meta: ResourcePotMeta::Custom(Box::new(())),
+      emitted_files: ResourceMap::new(),
     }
   }
 }
 
-pub type ResourcePotMap = HashMap<String, ResourcePot>;
+pub type ResourcePotMap = BTreeMap<String, ResourcePot>;
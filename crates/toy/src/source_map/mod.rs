@@ -0,0 +1,148 @@
+use parcel_sourcemap::{OriginalLocation, SourceMap};
+
+/// 把代码中的字节偏移转换成 source map 中的位置，行和列都从 0 开始，列按 UTF-16 编码单元计算
+pub fn offset_to_position(code: &str, offset: usize) -> (u32, u32) {
+  let before = &code[..offset];
+  let line = before.matches('\n').count();
+  let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+  let column = before[line_start..].encode_utf16().count();
+
+  (line as u32, column as u32)
+}
+
+/// 把按升序排列的多个字节偏移转换成位置，只扫描一遍代码
+pub fn offsets_to_positions(code: &str, offsets: &[usize]) -> Vec<(u32, u32)> {
+  let mut line = 0;
+  let mut line_start = 0;
+  let mut last = 0;
+
+  offsets
+    .iter()
+    .map(|&offset| {
+      for (index, _) in code[last..offset].match_indices('\n') {
+        line += 1;
+        line_start = last + index + 1;
+      }
+      last = offset;
+
+      let column = code[line_start..offset].encode_utf16().count();
+      (line as u32, column as u32)
+    })
+    .collect()
+}
+
+/// 把 transform 阶段产生的 source map 链合并到 `source_map` 上。
+/// `source_map` 原本映射到最后一次 transform 的结果，合并后映射到 load 得到的代码
+pub fn compose_chain(source_map: &mut SourceMap, chain: &[String]) -> Result<(), String> {
+  for map in chain.iter().rev() {
+    let mut map =
+      SourceMap::from_json(&source_map.project_root, map).map_err(|err| err.to_string())?;
+    source_map
+      .extends(&mut map)
+      .map_err(|err| err.to_string())?;
+  }
+
+  Ok(())
+}
+
+/// 把 `source_map` 中所有指向原始代码的映射追加到 `target`，
+/// 与 `SourceMap::add_sourcemap` 不同，不会覆盖 `target` 中同一行已有的映射
+pub fn merge_into(target: &mut SourceMap, source_map: &SourceMap) -> Result<(), String> {
+  for mapping in source_map.get_mappings() {
+    let Some(original) = mapping.original else {
+      continue;
+    };
+
+    let source = source_map
+      .get_source(original.source)
+      .map_err(|err| err.to_string())?;
+    let source_index = target.add_source(source);
+
+    if let Ok(content) = source_map.get_source_content(original.source) {
+      target
+        .set_source_content(source_index as usize, content)
+        .map_err(|err| err.to_string())?;
+    }
+
+    let name_index = match original.name {
+      Some(name) => {
+        Some(target.add_name(source_map.get_name(name).map_err(|err| err.to_string())?))
+      }
+      None => None,
+    };
+
+    target.add_mapping(
+      mapping.generated_line,
+      mapping.generated_column,
+      Some(OriginalLocation::new(
+        original.original_line,
+        original.original_column,
+        source_index,
+        name_index,
+      )),
+    );
+  }
+
+  Ok(())
+}
+
+/// 在代码开头插入 `lines` 行之后，调整 source map 中生成代码的行号
+pub fn offset_lines(root: &str, source_map: &str, lines: u32) -> Result<String, String> {
+  let mut source_map = SourceMap::from_json(root, source_map).map_err(|err| err.to_string())?;
+
+  source_map
+    .offset_lines(0, lines as i64)
+    .map_err(|err| err.to_string())?;
+  source_map.to_json(None).map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_offset_to_position() {
+    let code = "const a = 1;\nconst 中 = 2;\n";
+
+    assert_eq!(offset_to_position(code, 0), (0, 0));
+    assert_eq!(offset_to_position(code, 6), (0, 6));
+    assert_eq!(offset_to_position(code, 13), (1, 0));
+    // `中` 占 3 个字节，但只占 1 个 UTF-16 编码单元
+    assert_eq!(offset_to_position(code, 22), (1, 7));
+  }
+
+  #[test]
+  fn test_offsets_to_positions() {
+    let code = "const a = 1;\nconst 中 = 2;\n";
+
+    assert_eq!(
+      offsets_to_positions(code, &[0, 6, 13, 22]),
+      vec![(0, 0), (0, 6), (1, 0), (1, 7)]
+    );
+  }
+
+  #[test]
+  fn test_compose_chain() {
+    // a.ts 第 1 行 -> transform 结果第 0 行
+    let transform_map = r#"{"version":3,"sources":["a.ts"],"sourcesContent":["\nlet a = 1"],"names":[],"mappings":"AACA"}"#;
+    let mut source_map = SourceMap::new("/");
+    let source = source_map.add_source("a.js");
+    source_map.add_mapping(2, 4, Some(OriginalLocation::new(0, 0, source, None)));
+
+    compose_chain(&mut source_map, &[transform_map.to_string()]).unwrap();
+
+    let mut target = SourceMap::new("/");
+    target.add_source("other.js");
+    merge_into(&mut target, &source_map).unwrap();
+
+    let mapping = target.find_closest_mapping(2, 4).unwrap();
+    let original = mapping.original.unwrap();
+
+    assert_eq!(target.get_source(original.source).unwrap(), "a.ts");
+    assert_eq!(
+      target.get_source_content(original.source).unwrap(),
+      "\nlet a = 1"
+    );
+    assert_eq!((original.original_line, original.original_column), (1, 0));
+  }
+}
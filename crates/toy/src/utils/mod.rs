@@ -1,4 +1,9 @@
-use std::path::PathBuf;
+use std::{
+  fmt::Write,
+  path::{Path, PathBuf},
+};
+
+use ring::digest::{digest, SHA256};
 
 pub fn fulfill_root_prefix(path: &str, root: &str) -> String {
   if path.starts_with("./") {
@@ -7,3 +12,59 @@ pub fn fulfill_root_prefix(path: &str, root: &str) -> String {
     path.to_string()
   }
 }
+
+/// Convert a path string into a relative path, if it's inside the root.
+/// If it's outside of the root or it's not an absolute path, return it as is.
+pub fn to_relative(path: &str, root: &str) -> String {
+  let path = Path::new(path);
+  let root = Path::new(root);
+
+  // If the path is not a child of the root, or not absolute, return it as is.
+  if !path.is_absolute() || path == root || !path.starts_with(root) {
+    return path.to_string_lossy().to_string();
+  }
+
+  // Otherwise, it's a child of the root. Convert it into a relative path of the root.
+  let relative = path
+    .strip_prefix(root)
+    .unwrap()
+    .to_string_lossy()
+    .to_string();
+
+  "./".to_string() + &relative
+}
+
+/// 内容的 hash，取 sha256 的前 8 位十六进制字符，用于资源名
+pub fn content_hash(content: &[u8]) -> String {
+  let mut hash = sha256_hex(content);
+  hash.truncate(8);
+  hash
+}
+
+/// 内容的 sha256，64 位十六进制字符
+pub fn sha256_hex(content: &[u8]) -> String {
+  to_hex(digest(&SHA256, content).as_ref())
+}
+
+/// 小写十六进制编码
+pub fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().fold(String::new(), |mut hex, byte| {
+    let _ = write!(hex, "{byte:02x}");
+    hex
+  })
+}
+
+/// 测试中用 node 执行构建结果，没有安装 node 时输出提示并返回 `false`，调用方跳过这部分测试
+#[cfg(test)]
+pub fn node_available() -> bool {
+  let available = std::process::Command::new("node")
+    .arg("--version")
+    .output()
+    .is_ok_and(|output| output.status.success());
+
+  if !available {
+    eprintln!("`node` is not found in PATH, skipped the checks that run the output with node");
+  }
+
+  available
+}
@@ -0,0 +1,344 @@
+use std::{
+  collections::{BTreeSet, HashSet},
+  ops::ControlFlow,
+  path::{Path, PathBuf},
+  sync::mpsc::{channel, Receiver},
+  time::Duration,
+};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{
+  error::{CompilationError, Result},
+  Compiler,
+};
+
+/// 在这段时间内发生的文件变化会被合并成一次重新编译
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(50);
+
+/// 每一次（重新）编译完成后产生的事件，监听文件出错时也会产生一个 `changed_paths` 为空的失败事件
+pub struct WatchEvent {
+  /// 触发这次编译的文件，首次编译时为空
+  pub changed_paths: Vec<String>,
+  pub result: Result<()>,
+}
+
+impl Compiler {
+  /// watch 模式。先完整编译一次，然后监听 load 阶段读取过的文件，
+  /// 文件变化时调用插件的 `watch_change` 钩子，再增量编译并输出新的产物。
+  ///
+  /// 每次编译完成后都会调用 `on_event`，返回 `ControlFlow::Break` 时退出 watch 模式。
+  ///
+  /// 编译失败时不会写入任何产物，`output.dir` 中保留的是上一次成功编译的结果，
+  /// 并且下一次文件变化时会进行全量编译。编译失败后，被监听目录中新建的文件也会触发编译，
+  /// 例如先写了 `import './Foo'` 再创建 `Foo.js`。
+  pub fn watch<F>(&mut self, mut on_event: F) -> Result<()>
+  where
+    F: FnMut(&WatchEvent) -> ControlFlow<()>,
+  {
+    let (sender, receiver) = channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+    let mut watched_dirs = HashSet::new();
+
+    let result = self.compile();
+    let mut need_full_rebuild = result.is_err();
+
+    self.watch_files(&mut watcher, &mut watched_dirs)?;
+
+    let event = WatchEvent {
+      changed_paths: vec![],
+      result,
+    };
+
+    if on_event(&event).is_break() {
+      return Ok(());
+    }
+
+    loop {
+      let mut errors = vec![];
+      let changed_paths = self.wait_changed_paths(&receiver, need_full_rebuild, &mut errors)?;
+
+      // 单个文件系统事件出错时通知调用方，继续监听
+      for err in errors {
+        let event = WatchEvent {
+          changed_paths: vec![],
+          result: Err(err),
+        };
+
+        if on_event(&event).is_break() {
+          return Ok(());
+        }
+      }
+
+      if changed_paths.is_empty() {
+        continue;
+      }
+
+      let result = self
+        .context
+        .plugin_container
+        .watch_change(&changed_paths, &self.context)
+        .and_then(|_| {
+          if need_full_rebuild {
+            // 上一次编译失败时，module_graph 等状态可能不完整，需要全量编译
+            self.context.reset();
+            self.compile()
+          } else {
+            self.update(changed_paths.clone())
+          }
+        });
+
+      need_full_rebuild = result.is_err();
+
+      // 新增的依赖也需要被监听
+      self.watch_files(&mut watcher, &mut watched_dirs)?;
+
+      let event = WatchEvent {
+        changed_paths,
+        result,
+      };
+
+      if on_event(&event).is_break() {
+        return Ok(());
+      }
+    }
+  }
+
+  /// 监听被读取过的文件所在的目录。
+  /// 监听目录而不是文件本身，是因为很多编辑器保存文件时会先删除再重新创建文件
+  fn watch_files(
+    &self,
+    watcher: &mut RecommendedWatcher,
+    watched_dirs: &mut HashSet<PathBuf>,
+  ) -> Result<()> {
+    let watch_files = self.context.watch_files.read().unwrap();
+
+    for file in watch_files.iter() {
+      if let Some(dir) = Path::new(file).parent() {
+        if dir.exists() && watched_dirs.insert(dir.to_path_buf()) {
+          watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// 阻塞直到有文件变化，返回发生变化的、被监听的文件，`include_created` 为 `true` 时还包括
+  /// 被监听目录中新建的文件。文件系统事件的错误记录到 `errors` 中，只有监听停止时才返回错误
+  fn wait_changed_paths(
+    &self,
+    receiver: &Receiver<notify::Result<Event>>,
+    include_created: bool,
+    errors: &mut Vec<CompilationError>,
+  ) -> Result<Vec<String>> {
+    let mut changed_paths = BTreeSet::new();
+    let mut collect = |event: notify::Result<Event>| match event {
+      Ok(event) => self.collect_changed_paths(event, include_created, &mut changed_paths),
+      Err(err) => errors.push(watch_error(err)),
+    };
+
+    let event = receiver
+      .recv()
+      .map_err(|err| CompilationError::GenericError(format!("Watch stopped: {err}")))?;
+    collect(event);
+
+    while let Ok(event) = receiver.recv_timeout(DEBOUNCE_DURATION) {
+      collect(event);
+    }
+
+    Ok(changed_paths.into_iter().collect())
+  }
+
+  fn collect_changed_paths(
+    &self,
+    event: Event,
+    include_created: bool,
+    changed_paths: &mut BTreeSet<String>,
+  ) {
+    if !matches!(
+      event.kind,
+      EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+    ) {
+      return;
+    }
+
+    let watch_files = self.context.watch_files.read().unwrap();
+    // 上一次编译可能因为引用了还不存在的文件而失败，这些文件不在 watch_files 中
+    let created = include_created && matches!(event.kind, EventKind::Create(_));
+
+    for path in event.paths {
+      let is_created_file = created && path.is_file();
+      let path = path.to_string_lossy().to_string();
+
+      if is_created_file || watch_files.contains(&path) {
+        changed_paths.insert(path);
+      }
+    }
+  }
+}
+
+fn watch_error(err: notify::Error) -> CompilationError {
+  CompilationError::GenericError(format!("Watch failed: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    collections::BTreeMap,
+    fs,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    sync::{
+      atomic::{AtomicBool, Ordering},
+      mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+  };
+
+  use crate::{config::Config, Compiler};
+
+  /// 等待每一次编译事件的最长时间
+  const WATCH_TIMEOUT: Duration = Duration::from_secs(20);
+
+  /// 在 `root` 下启动 watch，首次编译完成后持续调用 `change`，直到观察到重新编译，
+  /// 返回首次编译和重新编译的 `(changed_paths, 是否成功)`
+  fn watch_rebuild(
+    root: &Path,
+    change: impl Fn(usize) + Send + Sync + 'static,
+  ) -> ((Vec<String>, bool), (Vec<String>, bool)) {
+    let mut compiler = Compiler::new(
+      Config {
+        root: root.to_string_lossy().to_string(),
+        input: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+        ..Config::default()
+      },
+      vec![],
+    )
+    .unwrap();
+
+    let done = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    let change = Arc::new(change);
+
+    // watch 会一直阻塞，放到单独的线程中，没有收到文件系统事件时测试在超时后失败，而不是一直挂起
+    thread::spawn(move || {
+      let mut count = 0;
+
+      compiler
+        .watch(|event| {
+          count += 1;
+          let _ = sender.send((event.changed_paths.clone(), event.result.is_ok()));
+
+          if count == 1 {
+            let change = change.clone();
+            let done = done.clone();
+
+            // 持续修改文件，直到观察到重新编译
+            thread::spawn(move || {
+              for i in 0..50 {
+                thread::sleep(Duration::from_millis(100));
+
+                if done.load(Ordering::SeqCst) {
+                  break;
+                }
+
+                change(i);
+              }
+            });
+
+            ControlFlow::Continue(())
+          } else {
+            done.store(true, Ordering::SeqCst);
+            ControlFlow::Break(())
+          }
+        })
+        .unwrap();
+    });
+
+    let next_event = |name: &str| {
+      receiver
+        .recv_timeout(WATCH_TIMEOUT)
+        .unwrap_or_else(|_| panic!("Timed out waiting for the {name}"))
+    };
+
+    let initial = next_event("initial build");
+    let rebuild = next_event("rebuild");
+
+    (initial, rebuild)
+  }
+
+  fn create_root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("toy-watch-{name}-{}", std::process::id()));
+
+    if root.exists() {
+      fs::remove_dir_all(&root).unwrap();
+    }
+
+    fs::create_dir_all(&root).unwrap();
+    root
+  }
+
+  #[test]
+  fn test_watch_rebuild_on_change() {
+    let root = create_root("change");
+    fs::write(
+      root.join("index.js"),
+      "import { foo } from './foo';\nfoo();",
+    )
+    .unwrap();
+    fs::write(root.join("foo.js"), "export function foo() {}").unwrap();
+
+    let foo_path = root.join("foo.js");
+    let (initial, rebuild) = watch_rebuild(&root, move |i| {
+      let _ = fs::write(
+        &foo_path,
+        format!("export function foo() {{ return {i}; }}"),
+      );
+    });
+
+    assert!(initial.1);
+    assert!(rebuild.1);
+    assert_eq!(
+      rebuild.0,
+      vec![root.join("foo.js").to_string_lossy().to_string()]
+    );
+    assert!(fs::read_to_string(root.join("dist/index.js"))
+      .unwrap()
+      .contains("return"));
+
+    fs::remove_dir_all(root).unwrap();
+  }
+
+  #[test]
+  fn test_watch_rebuild_on_create_missing_import() {
+    let root = create_root("create");
+    fs::write(
+      root.join("index.js"),
+      "import { foo } from './foo';\nfoo();",
+    )
+    .unwrap();
+
+    let foo_path = root.join("foo.js");
+    let (initial, rebuild) = watch_rebuild(&root, move |_| {
+      if !foo_path.exists() {
+        fs::write(&foo_path, "export function foo() { return 'created'; }").unwrap();
+      }
+    });
+
+    // foo.js 不存在时编译失败，创建之后重新编译成功
+    assert!(!initial.1);
+    assert!(rebuild.1, "{:?}", rebuild.0);
+    assert!(rebuild
+      .0
+      .contains(&root.join("foo.js").to_string_lossy().to_string()));
+    assert!(fs::read_to_string(root.join("dist/index.js"))
+      .unwrap()
+      .contains("created"));
+
+    fs::remove_dir_all(root).unwrap();
+  }
+}
@@ -0,0 +1,356 @@
+use std::{
+  env, fs,
+  net::TcpListener,
+  ops::ControlFlow,
+  path::{Path, PathBuf},
+  process::ExitCode,
+  thread,
+  time::Instant,
+};
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use toy::{
+  config::{load_config_file, Config, Mode},
+  error::{CompilationError, Result},
+  Compiler,
+};
+
+use crate::{diagnostics::Diagnostics, graph, serve};
+
+/// 编译失败
+pub const EXIT_BUILD_FAILED: u8 = 1;
+/// 参数或者配置文件不合法，与 clap 解析参数失败时的退出码一致
+pub const EXIT_CONFIG_ERROR: u8 = 2;
+
+#[derive(Debug, Parser)]
+#[command(name = "toy", version, about = "A toy bundler powered by oxc")]
+pub struct Cli {
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+  #[command(about = "Build and write resources to the output dir")]
+  Build(ConfigArgs),
+  #[command(about = "Build, then rebuild when watched files change")]
+  Watch(ConfigArgs),
+  #[command(about = "Watch and serve the output dir over http")]
+  Serve {
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[arg(long, default_value = "127.0.0.1", help = "Host to listen on")]
+    host: String,
+    #[arg(long, default_value_t = 3000, help = "Port to listen on")]
+    port: u16,
+  },
+  #[command(about = "Print the module graph")]
+  Graph {
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[arg(long, value_enum, default_value_t = GraphFormat::Text, help = "Output format")]
+    format: GraphFormat,
+  },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum GraphFormat {
+  /// 每个入口一棵依赖树
+  Text,
+  /// graphviz 的 dot 格式
+  Dot,
+}
+
+/// 覆盖配置文件的参数
+#[derive(Debug, Default, Args)]
+struct ConfigArgs {
+  #[arg(long, help = "Project root [default: current dir]")]
+  root: Option<PathBuf>,
+  #[arg(
+    long,
+    help = "Config file [default: toy.config.json or toy.config.toml under root]"
+  )]
+  config: Option<PathBuf>,
+  #[arg(
+    long,
+    value_parser = parse_input,
+    help = "Entry as `name=path` or `path` (named `main`), can be repeated"
+  )]
+  input: Vec<(String, String)>,
+  #[arg(
+    long = "outDir",
+    visible_alias = "out-dir",
+    help = "Output dir, relative to root"
+  )]
+  out_dir: Option<String>,
+  #[arg(
+    long,
+    value_parser = parse_mode,
+    help = "development or production [default: `mode` in config file, or development]"
+  )]
+  mode: Option<Mode>,
+}
+
+impl ConfigArgs {
+  /// 读取配置文件，再用命令行参数覆盖
+  fn load(&self) -> Result<Config> {
+    let root = match &self.root {
+      Some(root) => fs::canonicalize(root).map_err(|err| {
+        CompilationError::GenericError(format!("Invalid root `{}`: {err}", root.display()))
+      })?,
+      None => env::current_dir().map_err(|err| CompilationError::GenericError(err.to_string()))?,
+    };
+    let root = root.to_string_lossy().to_string();
+
+    let mut config = match &self.config {
+      Some(path) => {
+        let mut config = load_config_file(path)?;
+        // 显式指定的 root 优先于配置文件中的 root
+        if self.root.is_some() {
+          config.root = root;
+        }
+        config
+      }
+      None => Config::load(&root)?,
+    };
+
+    if !self.input.is_empty() {
+      config.input = self.input.iter().cloned().collect();
+    }
+    if let Some(out_dir) = &self.out_dir {
+      config.output.dir = out_dir.clone();
+    }
+    if let Some(mode) = self.mode {
+      config.mode = mode;
+    }
+
+    Ok(config)
+  }
+}
+
+/// `name=path` 或者 `path`
+fn parse_input(value: &str) -> std::result::Result<(String, String), String> {
+  match value.split_once('=') {
+    Some((name, _)) if name.is_empty() => Err("entry name is empty".to_string()),
+    Some((_, path)) if path.is_empty() => Err("entry path is empty".to_string()),
+    Some((name, path)) => Ok((name.to_string(), path.to_string())),
+    None => Ok(("main".to_string(), value.to_string())),
+  }
+}
+
+fn parse_mode(value: &str) -> std::result::Result<Mode, String> {
+  match value {
+    "development" => Ok(Mode::Development),
+    "production" => Ok(Mode::Production),
+    _ => Err("expect `development` or `production`".to_string()),
+  }
+}
+
+impl Cli {
+  pub fn run(self) -> ExitCode {
+    let diagnostics = Diagnostics::stderr();
+    let config_args = match &self.command {
+      Command::Build(config) | Command::Watch(config) => config,
+      Command::Serve { config, .. } | Command::Graph { config, .. } => config,
+    };
+
+    let config = match config_args.load() {
+      Ok(config) => config,
+      Err(err) => {
+        diagnostics.error(&err);
+        return ExitCode::from(EXIT_CONFIG_ERROR);
+      }
+    };
+
+    match self.command {
+      Command::Build(_) => build(config, &diagnostics),
+      Command::Watch(_) => watch(config, &diagnostics),
+      Command::Serve { host, port, .. } => serve(config, &host, port, &diagnostics),
+      Command::Graph { format, .. } => print_graph(config, format, &diagnostics),
+    }
+  }
+}
+
+fn build(mut config: Config, diagnostics: &Diagnostics) -> ExitCode {
+  config.output.write = true;
+
+  let start = Instant::now();
+  let out_dir = out_dir(&config);
+  let mut compiler = match create_compiler(config, diagnostics) {
+    Ok(compiler) => compiler,
+    Err(code) => return code,
+  };
+
+  match compiler.compile() {
+    Ok(()) => {
+      diagnostics.summary(&compiler.output(), &out_dir, start.elapsed());
+      ExitCode::SUCCESS
+    }
+    Err(err) => {
+      diagnostics.error(&err);
+      ExitCode::from(EXIT_BUILD_FAILED)
+    }
+  }
+}
+
+fn watch(mut config: Config, diagnostics: &Diagnostics) -> ExitCode {
+  config.output.write = true;
+
+  let mut compiler = match create_compiler(config, diagnostics) {
+    Ok(compiler) => compiler,
+    Err(code) => return code,
+  };
+  let mut start = Instant::now();
+
+  let result = compiler.watch(|event| {
+    if !event.changed_paths.is_empty() {
+      diagnostics.changed(&event.changed_paths);
+    }
+
+    match &event.result {
+      Ok(()) => diagnostics.watch_summary(start.elapsed()),
+      Err(err) => diagnostics.error(err),
+    }
+
+    start = Instant::now();
+    ControlFlow::Continue(())
+  });
+
+  match result {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      diagnostics.error(&err);
+      ExitCode::from(EXIT_BUILD_FAILED)
+    }
+  }
+}
+
+fn serve(config: Config, host: &str, port: u16, diagnostics: &Diagnostics) -> ExitCode {
+  let listener = match TcpListener::bind((host, port)) {
+    Ok(listener) => listener,
+    Err(err) => {
+      diagnostics.error(&CompilationError::GenericError(format!(
+        "Can not listen on {host}:{port}: {err}"
+      )));
+      return ExitCode::from(EXIT_CONFIG_ERROR);
+    }
+  };
+
+  let dir = out_dir(&config);
+  diagnostics.serving(&format!("http://{host}:{port}"), &dir);
+  thread::spawn(move || serve::serve(listener, dir));
+
+  watch(config, diagnostics)
+}
+
+fn print_graph(mut config: Config, format: GraphFormat, diagnostics: &Diagnostics) -> ExitCode {
+  config.output.write = false;
+
+  let mut compiler = match create_compiler(config, diagnostics) {
+    Ok(compiler) => compiler,
+    Err(code) => return code,
+  };
+
+  if let Err(err) = compiler.compile() {
+    diagnostics.error(&err);
+    return ExitCode::from(EXIT_BUILD_FAILED);
+  }
+
+  let output = compiler.output();
+  let graph = match format {
+    GraphFormat::Text => graph::render_text(&output),
+    GraphFormat::Dot => graph::render_dot(&output),
+  };
+
+  print!("{graph}");
+  ExitCode::SUCCESS
+}
+
+/// 创建 `Compiler`，插件顺序存在环等错误时输出错误并返回退出码
+fn create_compiler(
+  config: Config,
+  diagnostics: &Diagnostics,
+) -> std::result::Result<Compiler, ExitCode> {
+  Compiler::new(config, vec![]).map_err(|err| {
+    diagnostics.error(&err);
+    ExitCode::from(EXIT_CONFIG_ERROR)
+  })
+}
+
+/// 输出目录的绝对路径
+fn out_dir(config: &Config) -> PathBuf {
+  Path::new(&config.root).join(&config.output.dir)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use super::*;
+
+  #[test]
+  fn test_parse_args() {
+    let cli = Cli::try_parse_from([
+      "toy",
+      "build",
+      "--input",
+      "./index.html",
+      "--input",
+      "admin=./admin.html",
+      "--outDir",
+      "./build",
+      "--mode",
+      "development",
+    ])
+    .unwrap();
+
+    let Command::Build(args) = cli.command else {
+      panic!("expect build command");
+    };
+
+    assert_eq!(
+      args.input,
+      vec![
+        ("main".to_string(), "./index.html".to_string()),
+        ("admin".to_string(), "./admin.html".to_string())
+      ]
+    );
+    assert_eq!(args.out_dir.as_deref(), Some("./build"));
+    assert_eq!(args.mode, Some(Mode::Development));
+
+    assert!(Cli::try_parse_from(["toy", "build", "--input", "=./index.js"]).is_err());
+    assert!(Cli::try_parse_from(["toy", "build", "--mode", "test"]).is_err());
+  }
+
+  #[test]
+  fn test_load_config() {
+    let root = fs::canonicalize("../../fixtures/basic").unwrap();
+    let args = ConfigArgs {
+      root: Some(root.clone()),
+      input: vec![("main".to_string(), "./index.js".to_string())],
+      out_dir: Some("./build".to_string()),
+      mode: Some(Mode::Production),
+      ..ConfigArgs::default()
+    };
+    let config = args.load().unwrap();
+
+    assert_eq!(config.root, root.to_string_lossy());
+    assert_eq!(
+      config.input,
+      BTreeMap::from([("main".to_string(), "./index.js".to_string())])
+    );
+    assert_eq!(config.output.dir, "./build");
+    assert_eq!(config.mode, Mode::Production);
+
+    let args = ConfigArgs {
+      root: Some(PathBuf::from("./not-exists")),
+      ..ConfigArgs::default()
+    };
+
+    assert!(args
+      .load()
+      .unwrap_err()
+      .to_string()
+      .starts_with("Invalid root `./not-exists`"));
+  }
+}
@@ -0,0 +1,163 @@
+use std::{
+  io::{self, IsTerminal},
+  path::Path,
+  time::Duration,
+};
+
+use toy::{error::CompilationError, output::BuildOutput};
+
+/// 输出编译结果、错误和警告到 stderr，终端中带颜色
+pub struct Diagnostics {
+  colored: bool,
+}
+
+impl Diagnostics {
+  pub fn stderr() -> Self {
+    Self {
+      colored: io::stderr().is_terminal(),
+    }
+  }
+
+  pub fn error(&self, err: &CompilationError) {
+    eprint!("{}", self.render_error(err));
+  }
+
+  /// 构建完成：警告、输出的资源和耗时
+  pub fn summary(&self, output: &BuildOutput, out_dir: &Path, elapsed: Duration) {
+    eprint!("{}", self.render_summary(output, out_dir, elapsed));
+  }
+
+  pub fn watch_summary(&self, elapsed: Duration) {
+    eprintln!(
+      "{} in {}ms, waiting for changes...",
+      self.paint("32", "built"),
+      elapsed.as_millis()
+    );
+  }
+
+  pub fn changed(&self, paths: &[String]) {
+    for path in paths {
+      eprintln!("{} {path}", self.paint("36", "changed"));
+    }
+  }
+
+  pub fn serving(&self, url: &str, dir: &Path) {
+    eprintln!("{} {} at {url}", self.paint("36", "serving"), dir.display());
+  }
+
+  /// 多个错误逐个输出，每个错误的后续行缩进对齐
+  fn render_error(&self, err: &CompilationError) -> String {
+    let errors = match err {
+      CompilationError::Multiple(errors) => errors.iter().collect(),
+      err => vec![err],
+    };
+
+    let mut rendered = String::new();
+
+    for (index, err) in errors.iter().enumerate() {
+      let label = if errors.len() > 1 {
+        format!("error[{}/{}]", index + 1, errors.len())
+      } else {
+        "error".to_string()
+      };
+
+      rendered.push_str(&self.render_message(&self.paint("31", &label), &err.to_string()));
+    }
+
+    rendered
+  }
+
+  fn render_summary(&self, output: &BuildOutput, out_dir: &Path, elapsed: Duration) -> String {
+    let mut rendered = String::new();
+
+    for warning in &output.warnings {
+      rendered.push_str(&self.render_message(&self.paint("33", "warning"), warning));
+    }
+
+    let names = output
+      .resources
+      .keys()
+      .map(|name| {
+        out_dir
+          .join(name.trim_start_matches("./"))
+          .display()
+          .to_string()
+      })
+      .collect::<Vec<_>>();
+    let width = names.iter().map(|name| name.len()).max().unwrap_or(0);
+
+    for (name, resource) in names.iter().zip(output.resources.values()) {
+      let size = format_size(resource.content.as_bytes().len());
+      rendered.push_str(&format!("  {name:<width$}  {}\n", self.paint("2", &size)));
+    }
+
+    rendered.push_str(&format!(
+      "{} {} resource(s) in {}ms\n",
+      self.paint("32", "built"),
+      output.resources.len(),
+      elapsed.as_millis()
+    ));
+
+    rendered
+  }
+
+  fn render_message(&self, label: &str, message: &str) -> String {
+    let mut lines = message.lines();
+    let mut rendered = format!("{label}: {}\n", lines.next().unwrap_or_default());
+
+    for line in lines {
+      rendered.push_str(&format!("  {line}\n"));
+    }
+
+    rendered
+  }
+
+  fn paint(&self, color: &str, text: &str) -> String {
+    if self.colored {
+      format!("\x1b[{color}m{text}\x1b[0m")
+    } else {
+      text.to_string()
+    }
+  }
+}
+
+fn format_size(size: usize) -> String {
+  if size < 1024 {
+    format!("{size} B")
+  } else {
+    format!("{:.2} kB", size as f64 / 1024.0)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_render_error() {
+    let diagnostics = Diagnostics { colored: false };
+    let err = CompilationError::Multiple(vec![
+      CompilationError::ParseError {
+        id: "./a.js".to_string(),
+        message: "Unexpected token".to_string(),
+      },
+      CompilationError::GenericError("boom".to_string()),
+    ]);
+
+    assert_eq!(
+      diagnostics.render_error(&err),
+      "error[1/2]: Parse `./a.js` failed.\n  Error: Unexpected token\nerror[2/2]: boom\n"
+    );
+    assert_eq!(
+      Diagnostics { colored: true }
+        .render_error(&CompilationError::GenericError("boom".to_string())),
+      "\x1b[31merror\x1b[0m: boom\n"
+    );
+  }
+
+  #[test]
+  fn test_format_size() {
+    assert_eq!(format_size(512), "512 B");
+    assert_eq!(format_size(1536), "1.50 kB");
+  }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use toy::output::BuildOutput;
+
+/// 每个入口输出一棵依赖树，已经输出过的模块标记为 `(*)` 且不再展开
+///
+/// ```text
+/// main ./index.js
+/// ├── ./foo.js
+/// │   └── ./foz.js
+/// └── ./bar.js
+/// ```
+pub fn render_text(output: &BuildOutput) -> String {
+  let mut rendered = String::new();
+
+  for (name, entry_id) in &output.entry_modules {
+    let mut visited = HashSet::from([entry_id.as_str()]);
+
+    rendered.push_str(&format!("{name} {entry_id}\n"));
+    render_dependencies(output, entry_id, "", &mut visited, &mut rendered);
+  }
+
+  rendered
+}
+
+fn render_dependencies<'a>(
+  output: &'a BuildOutput,
+  id: &str,
+  prefix: &str,
+  visited: &mut HashSet<&'a str>,
+  rendered: &mut String,
+) {
+  let Some(dependencies) = output.modules.get(id) else {
+    return;
+  };
+
+  for (index, dep_id) in dependencies.iter().enumerate() {
+    let is_last = index == dependencies.len() - 1;
+    let (branch, indent) = if is_last {
+      ("└── ", "    ")
+    } else {
+      ("├── ", "│   ")
+    };
+
+    if visited.insert(dep_id) {
+      rendered.push_str(&format!("{prefix}{branch}{dep_id}\n"));
+      render_dependencies(
+        output,
+        dep_id,
+        &format!("{prefix}{indent}"),
+        visited,
+        rendered,
+      );
+    } else {
+      rendered.push_str(&format!("{prefix}{branch}{dep_id} (*)\n"));
+    }
+  }
+}
+
+/// graphviz 的 dot 格式，入口模块使用方框
+pub fn render_dot(output: &BuildOutput) -> String {
+  let mut rendered = String::from("digraph {\n");
+
+  for entry_id in output.entry_modules.values() {
+    rendered.push_str(&format!("  {entry_id:?} [shape=box];\n"));
+  }
+
+  for (id, dependencies) in &output.modules {
+    for dep_id in dependencies {
+      rendered.push_str(&format!("  {id:?} -> {dep_id:?};\n"));
+    }
+  }
+
+  rendered.push_str("}\n");
+  rendered
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+
+  use super::*;
+
+  fn mock_output() -> BuildOutput {
+    BuildOutput {
+      entry_modules: BTreeMap::from([("main".to_string(), "./index.js".to_string())]),
+      modules: BTreeMap::from([
+        (
+          "./index.js".to_string(),
+          vec!["./foo.js".to_string(), "./bar.js".to_string()],
+        ),
+        ("./foo.js".to_string(), vec!["./bar.js".to_string()]),
+        ("./bar.js".to_string(), vec!["./index.js".to_string()]),
+      ]),
+      ..BuildOutput::default()
+    }
+  }
+
+  #[test]
+  fn test_render_text() {
+    assert_eq!(
+      render_text(&mock_output()),
+      "main ./index.js\n\
+       ├── ./foo.js\n\
+       │   └── ./bar.js\n\
+       │       └── ./index.js (*)\n\
+       └── ./bar.js (*)\n"
+    );
+  }
+
+  #[test]
+  fn test_render_dot() {
+    let dot = render_dot(&mock_output());
+
+    assert!(dot.starts_with("digraph {\n  \"./index.js\" [shape=box];\n"));
+    assert!(dot.contains("  \"./foo.js\" -> \"./bar.js\";\n"));
+  }
+}
@@ -0,0 +1,13 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+use cli::Cli;
+
+mod cli;
+mod diagnostics;
+mod graph;
+mod serve;
+
+fn main() -> ExitCode {
+  Cli::parse().run()
+}
@@ -0,0 +1,163 @@
+use std::{
+  fs,
+  io::{self, BufRead, BufReader, Write},
+  net::{TcpListener, TcpStream},
+  path::{Component, Path, PathBuf},
+  thread,
+};
+
+/// 静态文件服务，提供 `dir` 中的文件，目录返回其中的 `index.html`
+pub fn serve(listener: TcpListener, dir: PathBuf) {
+  for stream in listener.incoming().flatten() {
+    let dir = dir.clone();
+    thread::spawn(move || {
+      let _ = handle_connection(stream, &dir);
+    });
+  }
+}
+
+fn handle_connection(mut stream: TcpStream, dir: &Path) -> io::Result<()> {
+  let mut request_line = String::new();
+  BufReader::new(&stream).read_line(&mut request_line)?;
+
+  let mut parts = request_line.split_whitespace();
+  let (method, url) = (
+    parts.next().unwrap_or_default(),
+    parts.next().unwrap_or("/"),
+  );
+
+  if method != "GET" && method != "HEAD" {
+    return write_response(
+      &mut stream,
+      "405 Method Not Allowed",
+      "text/plain",
+      b"",
+      false,
+    );
+  }
+
+  let file = resolve_path(dir, url).and_then(|path| fs::read(&path).ok().map(|body| (path, body)));
+
+  match file {
+    Some((path, body)) => write_response(
+      &mut stream,
+      "200 OK",
+      content_type(&path),
+      &body,
+      method == "GET",
+    ),
+    None => write_response(
+      &mut stream,
+      "404 Not Found",
+      "text/plain",
+      b"Not Found",
+      method == "GET",
+    ),
+  }
+}
+
+fn write_response(
+  stream: &mut TcpStream,
+  status: &str,
+  content_type: &str,
+  body: &[u8],
+  with_body: bool,
+) -> io::Result<()> {
+  write!(
+    stream,
+    "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+    body.len()
+  )?;
+
+  if with_body {
+    stream.write_all(body)?;
+  }
+
+  stream.flush()
+}
+
+/// 请求路径对应的文件，忽略 query 和 hash，不允许访问 `dir` 之外的文件
+fn resolve_path(dir: &Path, url: &str) -> Option<PathBuf> {
+  let path = url.split(['?', '#']).next().unwrap_or_default();
+  let path = Path::new(path.trim_start_matches('/'));
+
+  if path
+    .components()
+    .any(|component| !matches!(component, Component::Normal(_) | Component::CurDir))
+  {
+    return None;
+  }
+
+  let path = dir.join(path);
+
+  if path.is_dir() {
+    Some(path.join("index.html"))
+  } else {
+    Some(path)
+  }
+}
+
+fn content_type(path: &Path) -> &'static str {
+  match path.extension().and_then(|ext| ext.to_str()) {
+    Some("html") => "text/html; charset=utf-8",
+    Some("js" | "mjs") => "text/javascript; charset=utf-8",
+    Some("css") => "text/css; charset=utf-8",
+    Some("json" | "map") => "application/json",
+    Some("svg") => "image/svg+xml",
+    Some("png") => "image/png",
+    Some("jpg" | "jpeg") => "image/jpeg",
+    Some("gif") => "image/gif",
+    Some("webp") => "image/webp",
+    Some("woff2") => "font/woff2",
+    Some("wasm") => "application/wasm",
+    Some("txt") => "text/plain; charset=utf-8",
+    _ => "application/octet-stream",
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Read;
+
+  use super::*;
+
+  #[test]
+  fn test_resolve_path() {
+    let dir = fs::canonicalize("../../fixtures/basic").unwrap();
+
+    assert_eq!(
+      resolve_path(&dir, "/index.js?t=1"),
+      Some(dir.join("index.js"))
+    );
+    assert_eq!(resolve_path(&dir, "/"), Some(dir.join("index.html")));
+    assert_eq!(resolve_path(&dir, "/../Cargo.toml"), None);
+    assert_eq!(
+      content_type(Path::new("a.js")),
+      "text/javascript; charset=utf-8"
+    );
+  }
+
+  #[test]
+  fn test_serve() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let dir = fs::canonicalize("../../fixtures/basic").unwrap();
+
+    thread::spawn(move || serve(listener, dir));
+
+    let request = |path: &str| {
+      let mut stream = TcpStream::connect(addr).unwrap();
+      write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+      let mut response = String::new();
+      stream.read_to_string(&mut response).unwrap();
+      response
+    };
+
+    let response = request("/bar.js");
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/javascript"));
+    assert!(response.ends_with(&fs::read_to_string("../../fixtures/basic/bar.js").unwrap()));
+
+    assert!(request("/missing.js").starts_with("HTTP/1.1 404 Not Found\r\n"));
+  }
+}
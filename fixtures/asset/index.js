@@ -0,0 +1,3 @@
+import './logo.png';
+
+console.log('asset');
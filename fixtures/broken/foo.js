@@ -0,0 +1,3 @@
+import './missing';
+
+export function foo() {}
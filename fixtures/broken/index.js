@@ -0,0 +1,5 @@
+import { foo } from './foo';
+import { bar } from './bar';
+import './syntax';
+
+foo(bar);
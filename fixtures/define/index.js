@@ -0,0 +1,14 @@
+import { log } from './log';
+
+if (process.env.NODE_ENV !== 'production') {
+  log('development only');
+} else {
+  log('production only');
+}
+
+const mode = import.meta.env.MODE;
+const flags = { __FEATURE__, dev: import.meta.env.DEV ? 'dev' : 'prod' };
+
+process.env.NODE_ENV === 'development' && log('warn');
+
+log(`${mode}_${__VERSION__}_${flags.__FEATURE__}_${flags.dev}`);
@@ -0,0 +1,4 @@
+import { left } from './left';
+import { right } from './right';
+
+console.log(`${left()}_${right()}`);
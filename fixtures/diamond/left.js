@@ -0,0 +1,5 @@
+import { shared } from './shared';
+
+export function left() {
+  return 'left_' + shared();
+}
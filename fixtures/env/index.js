@@ -0,0 +1,4 @@
+const env = import.meta.env;
+
+console.log(import.meta.env.TOY_API_URL);
+console.log(`${env.TOY_TITLE}_${env.MODE}_${env.SECRET_KEY}`);
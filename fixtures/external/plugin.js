@@ -0,0 +1,51 @@
+// 用于测试 PluginExternal 的进程外插件，每行一条 JSON-RPC 消息
+const readline = require('readline');
+
+const handlers = {
+  initialize() {
+    return {
+      name: 'test-external-plugin',
+      hooks: ['resolve', 'load', 'transform'],
+      enforce: 'pre',
+    };
+  },
+  resolve({ source }) {
+    if (source.startsWith('@/')) {
+      return { id: `./${source.slice(2)}.js` };
+    }
+
+    return null;
+  },
+  load({ id }) {
+    if (id === './fail.js') {
+      throw new Error(`can not load ${id}`);
+    }
+
+    if (id === './foo.js') {
+      return { content: "export const foo = 'original';", moduleKind: 'js' };
+    }
+
+    return null;
+  },
+  transform({ content }) {
+    return { content: content.replace("'original'", "'transformed'") };
+  },
+};
+
+readline.createInterface({ input: process.stdin }).on('line', (line) => {
+  const { id, method, params } = JSON.parse(line);
+  let response;
+
+  // 不响应，用于测试请求超时
+  if (method === 'load' && params.id === './hang.js') {
+    return;
+  }
+
+  try {
+    response = { jsonrpc: '2.0', id, result: handlers[method](params) };
+  } catch (err) {
+    response = { jsonrpc: '2.0', id, error: { code: 1, message: err.message } };
+  }
+
+  process.stdout.write(JSON.stringify(response) + '\n');
+});
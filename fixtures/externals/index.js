@@ -0,0 +1,6 @@
+import React, { useState } from 'react';
+import * as dayjs from 'dayjs';
+import { format } from 'date-fns/format';
+import { foo } from './foo';
+
+console.log(React, useState, dayjs, format, foo);
@@ -0,0 +1,4 @@
+import { routes } from 'virtual:routes';
+import 'virtual:theme';
+
+console.log(routes);